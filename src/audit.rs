@@ -0,0 +1,228 @@
+//! Security audit of risky constructs in a plugin, since proxied plugins often run with broader trust than marketplace ones
+
+use full_moon::{
+    ast::{Assignment, Call, Expression, FunctionCall, Suffix, Var, VarExpression},
+    node::Node,
+    visitors::Visitor,
+};
+use rbx_dom_weak::{types::Ref, Instance, WeakDom};
+use serde::Serialize;
+
+use crate::{
+    dom::{
+        extension::{nth_arg, nth_arg_string, AffixExt, ArgValue, ForEachAction, HasAffixes, InstanceExt, TokenRefExt, WeakDomExt},
+        rbx_path::DotPath,
+    },
+    is_script_class,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+/// Overall outcome of an audit, decided by the most severe finding
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verdict {
+    /// Nothing or only low severity findings
+    Pass,
+    /// Medium severity findings that a reviewer should look at
+    Review,
+    /// High severity findings, like running code that isn't part of the plugin
+    Reject,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditKind {
+    HttpService,
+    /// `require` with a numeric asset id, which loads code from outside the plugin
+    RequireById,
+    Loadstring,
+    MarketplaceService,
+    TeleportService,
+    /// Accessing or parenting instances into ServerStorage
+    ServerStorage,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditFinding {
+    pub kind: AuditKind,
+    pub severity: Severity,
+    /// Path of the script in the format of script.Parent.Child, `script` is the main script
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditReport {
+    pub findings: Vec<AuditFinding>,
+    pub verdict: Verdict,
+    /// Scripts that couldn't be parsed, so they weren't audited
+    pub unparsed: Vec<String>,
+}
+
+/// Audits the main script and every script below it for risky constructs
+pub fn audit(tree: &WeakDom, main: Ref) -> AuditReport {
+    let mut findings = Vec::new();
+    let mut unparsed = Vec::new();
+
+    if let Some(main) = tree.get_by_ref(main) {
+        audit_script(main, &DotPath::default(), &mut findings, &mut unparsed);
+        tree.foreach_descendant(
+            main,
+            &mut |child, path| {
+                if is_script_class(&child.class) {
+                    audit_script(child, path, &mut findings, &mut unparsed);
+                }
+                ForEachAction::Continue
+            },
+            0,
+        );
+    }
+
+    // unparsed scripts could hide anything
+    let verdict = match findings.iter().map(|finding| finding.severity).max() {
+        Some(Severity::High) => Verdict::Reject,
+        Some(Severity::Medium) => Verdict::Review,
+        _ if !unparsed.is_empty() => Verdict::Review,
+        _ => Verdict::Pass,
+    };
+
+    AuditReport {
+        findings,
+        verdict,
+        unparsed,
+    }
+}
+
+fn audit_script(script: &Instance, path: &DotPath, findings: &mut Vec<AuditFinding>, unparsed: &mut Vec<String>) {
+    let Some(source) = script.source() else {
+        return;
+    };
+    let path = path.to_string();
+
+    let Ok(ast) = full_moon::parse(source) else {
+        unparsed.push(path);
+        return;
+    };
+    let mut auditor = Auditor::default();
+    auditor.visit_ast(&ast);
+
+    findings.extend(auditor.findings.into_iter().map(|(kind, severity, line, message)| AuditFinding {
+        kind,
+        severity,
+        path: path.clone(),
+        line,
+        message,
+    }));
+}
+
+#[derive(Default)]
+struct Auditor {
+    findings: Vec<(AuditKind, Severity, Option<usize>, String)>,
+}
+
+impl Auditor {
+    fn report(&mut self, kind: AuditKind, severity: Severity, node: &impl Node, message: impl Into<String>) {
+        let line = node.start_position().map(|position| position.line());
+        self.findings.push((kind, severity, line, message.into()));
+    }
+
+    fn audit_services<T: HasAffixes + Node>(&mut self, node: &T) {
+        for suffix in node.suffixes() {
+            let Suffix::Call(Call::MethodCall(method_call)) = suffix else {
+                continue;
+            };
+            if !matches!(method_call.name().identifier(), Some("GetService" | "FindService")) {
+                continue;
+            }
+
+            match nth_arg_string(method_call.args(), 0) {
+                Some("HttpService") => {
+                    self.report(AuditKind::HttpService, Severity::Medium, node, "uses HttpService, which can send data anywhere")
+                }
+                Some("MarketplaceService") => self.report(
+                    AuditKind::MarketplaceService,
+                    Severity::Medium,
+                    node,
+                    "uses MarketplaceService, which can prompt purchases",
+                ),
+                Some("TeleportService") => self.report(
+                    AuditKind::TeleportService,
+                    Severity::Medium,
+                    node,
+                    "uses TeleportService, which can move players to other places",
+                ),
+                Some("ServerStorage") => {
+                    self.report(AuditKind::ServerStorage, Severity::Low, node, "accesses ServerStorage")
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether the expression is or indexes `ServerStorage`, like `game.ServerStorage` or `game:GetService("ServerStorage")`
+fn references_server_storage(expression: &Expression) -> bool {
+    let is_server_storage = |suffix: &Suffix| match suffix {
+        Suffix::Call(Call::MethodCall(method_call)) => nth_arg_string(method_call.args(), 0) == Some("ServerStorage"),
+        suffix => suffix.identifier() == Some("ServerStorage"),
+    };
+
+    match expression {
+        Expression::Var(Var::Name(name)) => name.identifier() == Some("ServerStorage"),
+        Expression::Var(Var::Expression(var)) => var.suffixes().any(is_server_storage),
+        Expression::FunctionCall(call) => call.suffixes().any(is_server_storage),
+        Expression::Parentheses { expression, .. } => references_server_storage(expression),
+        _ => false,
+    }
+}
+
+impl Visitor for Auditor {
+    fn visit_function_call(&mut self, node: &FunctionCall) {
+        self.audit_services(node);
+
+        match node.prefix().identifier() {
+            Some("loadstring") => {
+                self.report(AuditKind::Loadstring, Severity::High, node, "runs code from a string with loadstring")
+            }
+            Some("require") => {
+                if let Some(Suffix::Call(Call::AnonymousCall(args))) = node.suffixes().next() {
+                    if let Some(ArgValue::Number(id)) = nth_arg(args, 0) {
+                        self.report(
+                            AuditKind::RequireById,
+                            Severity::High,
+                            node,
+                            format!("requires asset {id}, which loads code from outside the plugin"),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_var_expression(&mut self, node: &VarExpression) {
+        self.audit_services(node);
+    }
+
+    fn visit_assignment(&mut self, node: &Assignment) {
+        let parents = node.variables().iter().zip(node.expressions().iter()).any(|(variable, expression)| {
+            let is_parent = match variable {
+                Var::Expression(var) => var.suffixes().last().and_then(|suffix| suffix.identifier()) == Some("Parent"),
+                _ => false,
+            };
+            is_parent && references_server_storage(expression)
+        });
+
+        if parents {
+            self.report(AuditKind::ServerStorage, Severity::Medium, node, "parents instances into ServerStorage");
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Benchmark entry points that run the whole pipeline against synthetic plugins, so that regressions in parsing,
+//! traversing and printing can be compared release to release
+//!
+//! Enabled with the `bench` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pluginproxy_transpiler::bench::{run, Corpus};
+//!
+//! let result = run(Corpus::Small, 1).unwrap();
+//! assert_eq!(result.scripts, 6);
+//! assert!(result.bytes > 0);
+//! println!("{result}");
+//! ```
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use rbx_dom_weak::WeakDom;
+
+use crate::{
+    dom::extension::{ForEachAction, InstanceExt, WeakDomExt},
+    error::Problem,
+    is_script_class,
+    testing::PluginFixture,
+    DomTranspiler, RbxFileType,
+};
+
+/// Synthetic plugins to benchmark against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corpus {
+    /// A main script with 5 modules
+    Small,
+    /// A main script with 100 modules in nested folders
+    Medium,
+    /// A main script with 1000 modules in nested folders
+    Huge,
+    /// A main script with 50 modules using type annotations, generics, compound assignments,
+    /// if expressions and interpolated strings
+    HeavyLuau,
+}
+
+impl Corpus {
+    pub const ALL: &'static [Corpus] = &[Corpus::Small, Corpus::Medium, Corpus::Huge, Corpus::HeavyLuau];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Corpus::Small => "small",
+            Corpus::Medium => "medium",
+            Corpus::Huge => "huge",
+            Corpus::HeavyLuau => "heavy-luau",
+        }
+    }
+
+    /// Amount of modules below the main script
+    pub fn modules(self) -> usize {
+        match self {
+            Corpus::Small => 5,
+            Corpus::Medium => 100,
+            Corpus::Huge => 1000,
+            Corpus::HeavyLuau => 50,
+        }
+    }
+
+    /// Builds the plugin dom of the corpus, the same every time
+    pub fn build(self) -> WeakDom {
+        let modules = self.modules();
+        let mut main = String::new();
+        for index in 0..modules {
+            main.push_str(&format!("local module{index} = require(script.{})\n", module_path(index).replace('/', ".")));
+        }
+        main.push_str(&plugin_source());
+
+        let mut fixture = PluginFixture::new(&main);
+        for index in 0..modules {
+            let source = match self {
+                Corpus::HeavyLuau => luau_module_source(index),
+                _ => module_source(index),
+            };
+            fixture = fixture.module(&module_path(index), &source);
+        }
+        fixture.build()
+    }
+}
+
+/// Modules are spread over folders of 25, like the libraries of bigger plugins
+fn module_path(index: usize) -> String {
+    format!("Lib{}/Module{index}", index / 25)
+}
+
+fn plugin_source() -> String {
+    String::from(
+        r#"local toolbar = plugin:CreateToolbar("Bench")
+local button = toolbar:CreateButton("Run", "Runs the bench", "rbxassetid://0")
+local selection = game:GetService("Selection")
+local theme = settings().Studio.Theme
+button.Click:Connect(function()
+    for _, instance in selection:Get() do
+        print(instance:GetFullName(), theme)
+    end
+end)
+"#,
+    )
+}
+
+fn module_source(index: usize) -> String {
+    format!(
+        r#"local Module = {{}}
+Module.__index = Module
+
+local ChangeHistoryService = game:GetService("ChangeHistoryService")
+
+function Module.new(name)
+    local self = setmetatable({{}}, Module)
+    self.name = name
+    self.index = {index}
+    self.values = {{}}
+    return self
+end
+
+function Module:update(count)
+    ChangeHistoryService:SetWaypoint("Update " .. self.name)
+    for i = 1, count do
+        self.values[i] = (self.values[i] or 0) + i * {index}
+        if self.values[i] > 1000 then
+            self.values[i] = self.values[i] % 1000
+        end
+    end
+    return plugin:GetSetting(self.name)
+end
+
+function Module:describe()
+    return string.format("%s has %d values", self.name, #self.values)
+end
+
+return Module
+"#
+    )
+}
+
+fn luau_module_source(index: usize) -> String {
+    format!(
+        r#"--!strict
+export type Entry<T> = {{ key: string, value: T, children: {{ Entry<T> }}? }}
+type Callback = (entry: Entry<number>, depth: number) -> boolean
+
+local Module = {{}}
+
+local function walk<T>(entry: Entry<T>, depth: number, callback: (Entry<T>, number) -> boolean): number
+    local count = if callback(entry, depth) then 1 else 0
+    for _, child in entry.children or {{}} do
+        count += walk(child, depth + 1, callback)
+    end
+    return count
+end
+
+function Module.count(root: Entry<number>, limit: number?): number
+    local callback: Callback = function(entry, depth)
+        if depth > (limit or {index}) then
+            return false
+        end
+        return entry.value // 2 == 0
+    end
+    return walk(root, 0, callback)
+end
+
+function Module.describe(root: Entry<number>): string
+    local total = Module.count(root)
+    local label = `{{root.key}} has {{total}} entries at {index}`
+    plugin:SetSetting(root.key, total :: any)
+    return label
+end
+
+return Module
+"#
+    )
+}
+
+/// Time spent on each stage of the pipeline for a corpus, averaged over the iterations
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub corpus: Corpus,
+    pub iterations: u32,
+    /// Amount of transpiled scripts
+    pub scripts: usize,
+    /// Length of the encoded plugin
+    pub bytes: usize,
+    /// Parsing the sources on their own
+    pub parse: Duration,
+    /// Printing the parsed sources back on their own
+    pub print: Duration,
+    /// Transpiling the whole tree, which parses, traverses and prints every script
+    pub transpile: Duration,
+    /// Encoding the transpiled plugin as rbxm
+    pub encode: Duration,
+}
+
+impl BenchResult {
+    /// Estimate of the time spent traversing, the transpile time without parsing and printing
+    pub fn traverse(&self) -> Duration {
+        self.transpile.saturating_sub(self.parse + self.print)
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} scripts, parse {:?}, traverse {:?}, print {:?}, transpile {:?}, encode {:?} ({} bytes)",
+            self.corpus.name(),
+            self.scripts,
+            self.parse,
+            self.traverse(),
+            self.print,
+            self.transpile,
+            self.encode,
+            self.bytes
+        )
+    }
+}
+
+/// Runs the pipeline against a corpus, a fresh dom is built for every iteration and isn't part of the timings
+///
+/// # Arguments
+///
+/// * `corpus` - Plugin to run against
+/// * `iterations` - Amount of runs to average, at least 1
+pub fn run(corpus: Corpus, iterations: u32) -> Result<BenchResult, Problem> {
+    let iterations = iterations.max(1);
+    let mut result = BenchResult {
+        corpus,
+        iterations,
+        scripts: 0,
+        bytes: 0,
+        parse: Duration::ZERO,
+        print: Duration::ZERO,
+        transpile: Duration::ZERO,
+        encode: Duration::ZERO,
+    };
+
+    for _ in 0..iterations {
+        let tree = corpus.build();
+        let sources = sources(&tree);
+
+        let start = Instant::now();
+        let asts = sources
+            .iter()
+            .map(|source| full_moon::parse(source).map_err(Problem::TranspilerError))
+            .collect::<Result<Vec<_>, _>>()?;
+        result.parse += start.elapsed();
+
+        let start = Instant::now();
+        for ast in &asts {
+            std::hint::black_box(ast.to_string());
+        }
+        result.print += start.elapsed();
+
+        let mut transpiler = DomTranspiler::new(tree)?;
+        let start = Instant::now();
+        transpiler.transpile_tree()?;
+        result.transpile += start.elapsed();
+        result.scripts = transpiler.metrics().scripts.len();
+
+        let mut output = Vec::new();
+        let start = Instant::now();
+        transpiler.save_to_writer(&mut output, RbxFileType::Binary)?;
+        result.encode += start.elapsed();
+        result.bytes = output.len();
+    }
+
+    result.parse /= iterations;
+    result.print /= iterations;
+    result.transpile /= iterations;
+    result.encode /= iterations;
+    Ok(result)
+}
+
+/// Runs the pipeline against every corpus
+pub fn run_all(iterations: u32) -> Result<Vec<BenchResult>, Problem> {
+    Corpus::ALL.iter().map(|corpus| run(*corpus, iterations)).collect()
+}
+
+/// Sources of every script in the dom
+fn sources(tree: &WeakDom) -> Vec<String> {
+    let mut sources = Vec::new();
+    tree.foreach_descendant(
+        tree.root(),
+        &mut |child, _| {
+            if is_script_class(&child.class) {
+                sources.extend(child.source().map(String::from));
+            }
+            ForEachAction::Continue
+        },
+        0,
+    );
+    sources
+}
@@ -0,0 +1,173 @@
+//! Capabilities a plugin needs from the proxy host, so runtimes can refuse plugins requesting ones they don't grant
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    census::{ApiKind, Census},
+    report::{Diagnostic, Metrics},
+    rules::{self, RuleRegistry},
+};
+
+/// `settings()` is called through the plugin globals
+pub const SETTINGS: &str = "settings";
+/// Studio only enums are indexed from the proxied `Enums`
+pub const ENUMS: &str = "enums";
+/// The proxied `plugin` is used
+pub const PLUGIN: &str = "plugin";
+/// CoreGui is accessed, which hosts usually replace with a virtual container
+pub const COREGUI: &str = "coregui";
+/// `plugin:CreateToolbar`
+pub const TOOLBAR: &str = "toolbar";
+/// `plugin:CreateDockWidgetPluginGui`
+pub const WIDGETS: &str = "widgets";
+/// `plugin:CreatePluginAction`
+pub const ACTIONS: &str = "actions";
+/// `plugin:CreatePluginMenu`
+pub const MENUS: &str = "menus";
+/// `plugin:GetMouse`
+pub const MOUSE: &str = "mouse";
+/// `plugin:GetSetting` and `plugin:SetSetting`
+pub const PLUGIN_SETTINGS: &str = "plugin-settings";
+/// The Selection service
+pub const SELECTION: &str = "selection";
+/// Requests sent with HttpService, see [`crate::census::HTTP_REQUESTS`]
+pub const HTTP: &str = "http";
+/// Inserting assets, like `InsertService:LoadAsset` or `plugin:ImportFbxRig`, see [`crate::ASSET_INSERTIONS`]
+pub const ASSETS: &str = "assets";
+/// The mode Studio is in as the host reports it, see [`crate::TranspileOptions::proxy_run_context`]
+pub const RUN_CONTEXT: &str = "run-context";
+/// Asset lookups and thumbnails served by the host, see [`crate::TranspileOptions::proxy_thumbnails`]
+pub const THUMBNAILS: &str = "thumbnails";
+/// Prompting purchases or checking monetization policies, see [`crate::MONETIZATION_CALLS`]
+pub const MONETIZATION: &str = "monetization";
+/// Change signals of proxied objects, like `settings().Studio:GetPropertyChangedSignal("Theme")`
+pub const SIGNALS: &str = "signals";
+
+/// Plugin members that need a capability
+const PLUGIN_MEMBERS: &[(&str, &str)] = &[
+    ("CreateToolbar", TOOLBAR),
+    ("CreateDockWidgetPluginGui", WIDGETS),
+    ("CreateDockWidgetPluginGuiAsync", WIDGETS),
+    ("CreatePluginAction", ACTIONS),
+    ("CreatePluginMenu", MENUS),
+    ("GetMouse", MOUSE),
+    ("GetSetting", PLUGIN_SETTINGS),
+    ("SetSetting", PLUGIN_SETTINGS),
+];
+
+/// Services that need a capability
+const SERVICES: &[(&str, &str)] = &[("CoreGui", COREGUI), ("Selection", SELECTION)];
+
+/// Where the capability manifest is embedded in the output, see [`crate::DomTranspiler::embed_capabilities`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CapabilityEmbed {
+    /// A comma separated string attribute on the main script
+    Attribute,
+    /// A ModuleScript under the main script that returns `{ needs = { ... } }`
+    Module,
+}
+
+impl std::str::FromStr for CapabilityEmbed {
+    type Err = String;
+
+    fn from_str(embed: &str) -> Result<Self, Self::Err> {
+        match embed.to_ascii_lowercase().as_str() {
+            "attribute" => Ok(CapabilityEmbed::Attribute),
+            "module" => Ok(CapabilityEmbed::Module),
+            _ => Err(format!("unknown place for the capabilities '{embed}', expected attribute or module")),
+        }
+    }
+}
+
+/// Capabilities a plugin needs, in a machine-readable form for proxy runtimes
+#[derive(Clone, Debug, Serialize)]
+pub struct CapabilityManifest {
+    /// Version of the transpiler that produced the manifest
+    pub version: &'static str,
+    /// Names of the needed capabilities, sorted
+    pub needs: Vec<&'static str>,
+}
+
+impl CapabilityManifest {
+    /// Derives the capabilities from the API usage of the original scripts and what the transpiler did
+    pub fn new(census: &Census, metrics: &Metrics, diagnostics: &[Diagnostic]) -> Self {
+        let mut needs = Vec::new();
+
+        if metrics.rules.contains_key(rules::SETTINGS) || census.get(ApiKind::Global, "settings").is_some() {
+            needs.push(SETTINGS);
+        }
+        if metrics.requirements.enums {
+            needs.push(ENUMS);
+        }
+        if metrics.requirements.signals {
+            needs.push(SIGNALS);
+        }
+        if metrics.requirements.plugin || census.get(ApiKind::Global, "plugin").is_some() {
+            needs.push(PLUGIN);
+        }
+        let uses_coregui = diagnostics.iter().any(|diagnostic| diagnostic.rule == rules::COREGUI);
+        if uses_coregui || metrics.rules.contains_key(rules::COREGUI) {
+            needs.push(COREGUI);
+        }
+        let inserts_assets = diagnostics.iter().any(|diagnostic| diagnostic.rule == rules::ASSET_INSERTION);
+        if inserts_assets || metrics.rules.contains_key(rules::ASSET_INSERTION) {
+            needs.push(ASSETS);
+        }
+        let uses_thumbnails = diagnostics.iter().any(|diagnostic| diagnostic.rule == rules::THUMBNAILS);
+        if uses_thumbnails || metrics.rules.contains_key(rules::THUMBNAILS) {
+            needs.push(THUMBNAILS);
+        }
+        let monetizes = diagnostics.iter().any(|diagnostic| diagnostic.rule == rules::MONETIZATION);
+        if monetizes || metrics.rules.contains_key(rules::MONETIZATION) {
+            needs.push(MONETIZATION);
+        }
+        if metrics.rules.contains_key(rules::RUN_CONTEXT) {
+            needs.push(RUN_CONTEXT);
+        }
+        for (member, capability) in PLUGIN_MEMBERS {
+            if census.get(ApiKind::PluginMember, member).is_some() {
+                needs.push(capability);
+            }
+        }
+        for (service, capability) in SERVICES {
+            if census.get(ApiKind::Service, service).is_some() {
+                needs.push(capability);
+            }
+        }
+
+        // HttpService alone only encodes JSON and generates GUIDs, sending requests is what phones home
+        if census.has_kind(ApiKind::HttpRequest) {
+            needs.push(HTTP);
+        }
+
+        needs.sort_unstable();
+        needs.dedup();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            needs,
+        }
+    }
+
+    /// Adds the capabilities of the registered rules that rewrote something, like those added with
+    /// [`crate::DomTranspiler::add_rule`]
+    pub fn add_rule_capabilities(&mut self, registry: &RuleRegistry, metrics: &Metrics) {
+        for rule in registry.iter().filter(|rule| metrics.rules.contains_key(rule.name())) {
+            self.needs.extend(rule.capability());
+        }
+        self.needs.sort_unstable();
+        self.needs.dedup();
+    }
+
+    /// Lua source of a module returning the manifest
+    pub fn to_lua(&self) -> String {
+        self.to_lua_with_comment(Some(crate::GENERATED_COMMENT))
+    }
+
+    /// Lua source of a module returning the manifest, below a comment marking it generated unless it's `None`
+    pub fn to_lua_with_comment(&self, comment: Option<&str>) -> String {
+        let needs = self.needs.iter().map(|need| format!("\"{need}\"")).collect::<Vec<_>>().join(", ");
+        let comment = comment.map(|comment| format!("-- {}\n", crate::single_line(comment))).unwrap_or_default();
+        format!("{comment}return {{ version = \"{}\", needs = {{ {needs} }} }}\n", self.version)
+    }
+}
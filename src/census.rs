@@ -0,0 +1,353 @@
+//! Census of the engine APIs that a plugin references, for vetting third-party plugins before proxying them
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use full_moon::{
+    ast::{Call, Expression, FunctionCall, Index, LocalAssignment, Prefix, Suffix, Var, VarExpression},
+    visitors::Visitor,
+};
+use rbx_dom_weak::{types::Ref, Instance, WeakDom};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{
+    dom::{
+        extension::{nth_arg_string, AffixExt, ForEachAction, HasAffixes, InstanceExt, TokenRefExt, WeakDomExt},
+        rbx_path::DotPath,
+    },
+    is_script_class,
+};
+
+/// Globals of the Roblox environment that are counted when referenced
+pub const KNOWN_GLOBALS: &[&str] = &[
+    "game",
+    "workspace",
+    "plugin",
+    "settings",
+    "UserSettings",
+    "Enum",
+    "Instance",
+    "require",
+    "loadstring",
+    "getfenv",
+    "setfenv",
+    "shared",
+    "_G",
+    "task",
+    "wait",
+    "spawn",
+    "delay",
+    "tick",
+    "debug",
+    "DockWidgetPluginGuiInfo",
+    "PluginDrag",
+    "PluginManager",
+];
+
+/// Methods of HttpService that send requests, counted as [`ApiKind::HttpRequest`]
+pub const HTTP_REQUESTS: &[&str] = &["GetAsync", "PostAsync", "RequestAsync"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiKind {
+    /// A global of the environment, see [`KNOWN_GLOBALS`]
+    Global,
+    /// A service got with `GetService` or `FindService`
+    Service,
+    /// A family of `Enum`, like `KeyCode`
+    EnumFamily,
+    /// A method or property of `plugin`
+    PluginMember,
+    /// A class created with `Instance.new`
+    InstanceClass,
+    /// A request sent with HttpService, directly or through a local holding it, see [`HTTP_REQUESTS`]
+    HttpRequest,
+}
+
+impl ApiKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ApiKind::Global => "global",
+            ApiKind::Service => "service",
+            ApiKind::EnumFamily => "enum-family",
+            ApiKind::PluginMember => "plugin-member",
+            ApiKind::InstanceClass => "instance-class",
+            ApiKind::HttpRequest => "http-request",
+        }
+    }
+}
+
+/// Format for exporting a census, see [`Census::render`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CensusFormat {
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for CensusFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "json" => Ok(CensusFormat::Json),
+            "csv" => Ok(CensusFormat::Csv),
+            _ => Err(format!("unknown census format '{format}', expected json or csv")),
+        }
+    }
+}
+
+/// How often an API is referenced and by which scripts
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiUsage {
+    pub kind: ApiKind,
+    pub name: String,
+    pub count: usize,
+    /// Paths of the scripts referencing it in the format of script.Parent.Child
+    pub scripts: BTreeSet<String>,
+}
+
+/// Every API referenced across the scripts of a plugin
+#[derive(Clone, Debug, Default)]
+pub struct Census {
+    usages: BTreeMap<(ApiKind, String), ApiUsage>,
+}
+
+impl Census {
+    /// Counts the APIs referenced by the main script and every script below it, scripts that can't be parsed are skipped
+    pub fn collect(tree: &WeakDom, main: Ref) -> Self {
+        let mut census = Census::default();
+        let Some(main) = tree.get_by_ref(main) else {
+            return census;
+        };
+
+        census.add_script(main, &DotPath::default());
+        tree.foreach_descendant(
+            main,
+            &mut |child, path| {
+                if is_script_class(&child.class) {
+                    census.add_script(child, path);
+                }
+                ForEachAction::Continue
+            },
+            0,
+        );
+
+        census
+    }
+
+    fn add_script(&mut self, script: &Instance, path: &DotPath) {
+        let Some(source) = script.source() else {
+            return;
+        };
+        let path = path.to_string();
+        match full_moon::parse(source) {
+            Ok(ast) => {
+                let mut collector = ApiCollector::default();
+                collector.visit_ast(&ast);
+                for (kind, name) in collector.references {
+                    self.add(kind, name, &path);
+                }
+            }
+            Err(_) => warn!("Skipped {path} in the API census, it can't be parsed"),
+        }
+    }
+
+    fn add(&mut self, kind: ApiKind, name: String, path: &str) {
+        let usage = self.usages.entry((kind, name.clone())).or_insert_with(|| ApiUsage {
+            kind,
+            name,
+            count: 0,
+            scripts: BTreeSet::new(),
+        });
+        usage.count += 1;
+        usage.scripts.insert(path.to_string());
+    }
+
+    /// Every referenced API, ordered by kind and name
+    pub fn usages(&self) -> impl Iterator<Item = &ApiUsage> {
+        self.usages.values()
+    }
+
+    pub fn get(&self, kind: ApiKind, name: &str) -> Option<&ApiUsage> {
+        self.usages.get(&(kind, name.to_string()))
+    }
+
+    /// Whether any API of the kind is referenced
+    pub fn has_kind(&self, kind: ApiKind) -> bool {
+        self.usages().any(|usage| usage.kind == kind)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.usages.is_empty()
+    }
+
+    /// Renders the census in the format
+    pub fn render(&self, format: CensusFormat) -> String {
+        match format {
+            CensusFormat::Json => self.to_json(),
+            CensusFormat::Csv => self.to_csv(),
+        }
+    }
+
+    /// Renders the census as a JSON array of [`ApiUsage`]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.usages().collect::<Vec<_>>()).unwrap()
+    }
+
+    /// Renders the census as CSV with a header, the scripts are separated by spaces
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::census::Census;
+    ///
+    /// assert_eq!(Census::default().to_csv(), "kind,name,count,scripts\n");
+    /// ```
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,name,count,scripts\n");
+        for usage in self.usages() {
+            let scripts = usage.scripts.iter().cloned().collect::<Vec<_>>().join(" ");
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                usage.kind.as_str(),
+                csv_field(&usage.name),
+                usage.count,
+                csv_field(&scripts)
+            ));
+        }
+        csv
+    }
+}
+
+/// Quotes the field if it contains a separator, quote or newline
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Collects the APIs referenced in a script, in the order they appear
+#[derive(Default)]
+struct ApiCollector {
+    references: Vec<(ApiKind, String)>,
+    /// Names of the locals holding HttpService
+    http_services: HashSet<String>,
+}
+
+impl ApiCollector {
+    fn collect_affixes<T: HasAffixes>(&mut self, node: &T) {
+        let prefix = node.prefix().identifier();
+        let mut suffixes = node.suffixes();
+        let first = suffixes.next();
+
+        match (prefix, first) {
+            (Some("Enum"), Some(suffix)) => {
+                if let Some(family) = suffix.identifier() {
+                    self.references.push((ApiKind::EnumFamily, family.to_string()));
+                }
+            }
+            (Some("plugin"), Some(suffix)) => {
+                if let Some(member) = member_name(suffix) {
+                    self.references.push((ApiKind::PluginMember, member.to_string()));
+                }
+            }
+            (Some("Instance"), Some(suffix)) if suffix.identifier() == Some("new") => {
+                if let Some(Suffix::Call(Call::AnonymousCall(args))) = suffixes.next() {
+                    if let Some(class) = nth_arg_string(args, 0) {
+                        self.references.push((ApiKind::InstanceClass, class.to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let mut previous = None;
+        for suffix in node.suffixes() {
+            if let Suffix::Call(Call::MethodCall(method_call)) = suffix {
+                if matches!(method_call.name().identifier(), Some("GetService" | "FindService")) {
+                    if let Some(service) = nth_arg_string(method_call.args(), 0) {
+                        self.references.push((ApiKind::Service, service.to_string()));
+                    }
+                }
+
+                let on_http_service = match previous {
+                    Some(previous) => is_http_service(previous),
+                    None => prefix.is_some_and(|prefix| self.http_services.contains(prefix)),
+                };
+                match method_call.name().identifier() {
+                    Some(method) if on_http_service && HTTP_REQUESTS.contains(&method) => {
+                        self.references.push((ApiKind::HttpRequest, method.to_string()));
+                    }
+                    _ => {}
+                }
+            }
+            previous = Some(suffix);
+        }
+    }
+
+    fn collect_global(&mut self, name: Option<&str>) {
+        if let Some(name) = name.filter(|name| KNOWN_GLOBALS.contains(name)) {
+            self.references.push((ApiKind::Global, name.to_string()));
+        }
+    }
+}
+
+/// Whether the suffix gets HttpService, like `:GetService("HttpService")` or `.HttpService`
+fn is_http_service(suffix: &Suffix) -> bool {
+    match suffix {
+        Suffix::Call(Call::MethodCall(method_call)) => {
+            matches!(method_call.name().identifier(), Some("GetService" | "FindService"))
+                && nth_arg_string(method_call.args(), 0) == Some("HttpService")
+        }
+        suffix => suffix.identifier() == Some("HttpService"),
+    }
+}
+
+/// Name of a field or method indexed by the suffix
+fn member_name(suffix: &Suffix) -> Option<&str> {
+    match suffix {
+        Suffix::Index(Index::Dot { name, .. }) => name.identifier(),
+        Suffix::Call(Call::MethodCall(method_call)) => method_call.name().identifier(),
+        _ => None,
+    }
+}
+
+impl Visitor for ApiCollector {
+    fn visit_prefix(&mut self, node: &Prefix) {
+        self.collect_global(node.identifier());
+    }
+
+    fn visit_var(&mut self, node: &Var) {
+        if let Var::Name(name) = node {
+            self.collect_global(name.identifier());
+        }
+    }
+
+    fn visit_var_expression(&mut self, node: &VarExpression) {
+        self.collect_affixes(node);
+    }
+
+    fn visit_function_call(&mut self, node: &FunctionCall) {
+        self.collect_affixes(node);
+    }
+
+    fn visit_local_assignment(&mut self, node: &LocalAssignment) {
+        for (name, expression) in node.names().iter().zip(node.expressions()) {
+            let Some(name) = name.identifier() else {
+                continue;
+            };
+            let holds_http_service = match expression {
+                Expression::FunctionCall(call) => call.suffixes().last().is_some_and(is_http_service),
+                Expression::Var(Var::Expression(var)) => var.suffixes().last().is_some_and(is_http_service),
+                _ => false,
+            };
+            if holds_http_service {
+                self.http_services.insert(name.to_owned());
+            } else {
+                self.http_services.remove(name);
+            }
+        }
+    }
+}
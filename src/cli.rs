@@ -1,17 +1,44 @@
 use std::{
     fs,
-    io::{self, Write},
-    path::PathBuf,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
 };
 
-use clap::Parser;
-use log::info;
-use rfd::FileDialog;
+use clap::{Args, Parser, Subcommand};
+use log::{info, warn};
 
-use pluginproxy_transpiler::{error::Problem, RbxFileType};
+use pluginproxy_transpiler::{
+    audit::{self, AuditReport, Severity, Verdict},
+    capabilities::{CapabilityEmbed, HTTP},
+    dom::rbx_path::is_valid_identifier,
+    error::Problem,
+    info::plugin_id_from_contents,
+    report::{group_diagnostics, Diagnostic, Metrics, ReportFormat},
+    runtime::RuntimeDescriptor,
+    DomTranspiler, EmbedOriginal, Indent, RbxFileType, SettingsNamespace, TestModules, MAIN_SEARCH_DEPTH,
+};
+
+mod census;
+mod config;
+mod diff;
+mod doctor;
+mod inspect;
+mod install;
+mod lint;
+mod log_file;
+mod mem_stats;
+mod rpc;
+mod select;
+mod vendor;
+
+use log_file::{LogWriter, LOG_FILE_NAME, SCRIPT_TARGET};
+use mem_stats::{CountingAllocator, MemStats};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
-type LogFile = Arc<RwLock<Option<fs::File>>>;
+type LogFile = Arc<RwLock<Option<LogWriter>>>;
 struct WrappedLogger {
     log: env_logger::Logger,
     log_file: LogFile,
@@ -26,8 +53,8 @@ impl log::Log for WrappedLogger {
         if self.enabled(record.metadata()) {
             self.log.log(record);
 
-            if let Some(ref mut log_file) = &mut *self.log_file.write().unwrap() {
-                log_file.write_all(format!("{}\r\n", record.args()).as_bytes()).ok();
+            if let Some(log_file) = &mut *self.log_file.write().unwrap() {
+                log_file.write(record);
             }
         }
     }
@@ -37,77 +64,890 @@ impl log::Log for WrappedLogger {
 
 #[derive(Parser)]
 #[clap(author, version, about)]
+#[command(args_conflicts_with_subcommands = true)]
 struct TranspilerCliArgs {
-    #[arg(short = 'i')]
-    input: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    transpile: TranspileArgs,
+
+    #[command(flatten)]
+    verbosity: Verbosity,
+}
 
-    #[arg(value_name = "OUTPUT")]
+/// Log target that is always shown, even when quiet
+const SUMMARY_TARGET: &str = "summary";
+
+#[derive(Args)]
+struct Verbosity {
+    /// Log more, -v lists every transpiled script with the rules applied and every diagnostic, -vv logs every rewrite
+    #[arg(short, long, action = clap::ArgAction::Count, global = true, env = "PLUGINPROXY_VERBOSE")]
+    verbose: u8,
+
+    /// Only log warnings, errors and the final summary
+    #[arg(short, long, action = clap::ArgAction::SetTrue, global = true, conflicts_with = "verbose", env = "PLUGINPROXY_QUIET")]
+    quiet: bool,
+}
+
+impl Verbosity {
+    fn level(&self) -> log::LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => log::LevelFilter::Warn,
+            (false, 0) => log::LevelFilter::Info,
+            (false, 1) => log::LevelFilter::Debug,
+            (false, _) => log::LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Transpile a plugin without saving it and print the changes made to every script
+    Diff(diff::DiffArgs),
+    /// Check that an existing output matches what the input transpiles to, printing the differences
+    Check(diff::CheckArgs),
+    /// Compare two transpiled outputs instance by instance, listing added and removed instances,
+    /// changed sources and changed properties
+    Compare(diff::CompareArgs),
+    /// Check the plugin for structural issues, without transpiling it
+    Lint(lint::LintArgs),
+    /// Check that a file can be transpiled, with guidance for every problem found
+    Doctor(doctor::DoctorArgs),
+    /// List the scripts of a plugin and which scripts they require
+    Inspect(inspect::InspectArgs),
+    /// List every engine API the plugin references, for vetting it before proxying
+    Census(census::CensusArgs),
+    /// Answer JSON-RPC requests read line by line from stdin, for build pipelines like Lune scripts
+    Rpc(rpc::RpcArgs),
+}
+
+/// Path that stands for stdin as the input and stdout as the output
+const STANDARD_STREAM: &str = "-";
+
+/// Value of --plugin-id that derives the id from the contents of the input
+const PLUGIN_ID_FROM_INPUT: &str = "auto";
+
+/// Value of --settings-namespace that uses the name of the plugin
+const NAMESPACE_FROM_PLUGIN: &str = "auto";
+
+/// Every option can also be set with a `PLUGINPROXY_*` environment variable, flags take precedence
+#[derive(Args)]
+struct TranspileArgs {
+    /// Plugin file to transpile, `-` reads it from stdin with the format detected from its contents.
+    /// Repeat it to transpile several plugins with the same options, each is saved next to its input
+    #[arg(short = 'i', env = "PLUGINPROXY_INPUT")]
+    input: Vec<PathBuf>,
+
+    /// File to save the output to, `-` writes it to stdout as binary unless --output-format is set.
+    /// Defaults to the input's name with a -proxied suffix next to it, like MyPlugin-proxied.rbxm
+    #[arg(value_name = "OUTPUT", env = "PLUGINPROXY_OUTPUT")]
     output: Option<PathBuf>,
 
-    #[arg(long, visible_alias = "libs", action = clap::ArgAction::SetTrue)]
+    /// Run as a post-build step of `rojo build --output -`: reads the model from stdin unless an input is given,
+    /// and keeps the directives of the main script like `--!strict`. Sources are parsed as Luau
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_FROM_ROJO")]
+    from_rojo: bool,
+
+    #[arg(long, visible_alias = "libs", action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_INCLUDE_LIBS")]
     #[arg(help = r"Include all libraries, even non-plugin ones like React or Fusion.
     Use this if the plugin depends on a module with the same name as a standard library
     and requires plugin-specific methods.")]
     include_libs: bool,
 
+    /// Format of the input file, determined from its extension by default (binary, xml)
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_INPUT_FORMAT")]
+    input_format: Option<RbxFileType>,
+
+    /// Format of the output file, determined from its extension by default (binary, xml)
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_OUTPUT_FORMAT")]
+    output_format: Option<RbxFileType>,
+
     /// Disable saving logs to file
-    #[arg(long, action = clap::ArgAction::SetTrue)]
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_NO_LOGS")]
     no_logs: bool,
+
+    /// Main script to transpile when the file contains multiple, by name or full name (e.g. Plugins.MyPlugin.Main)
+    #[arg(long, value_name = "NAME", env = "PLUGINPROXY_SELECT_SCRIPT")]
+    select_script: Option<String>,
+
+    /// Full name of the instance containing the plugin (e.g. Workspace.Plugins), everything else in the file
+    /// is freed right after decoding, use this for places with large assets
+    #[arg(long, value_name = "PATH", env = "PLUGINPROXY_ONLY_PATH")]
+    only_path: Option<String>,
+
+    /// Save the whole file instead of only the plugin, keeps references to instances outside of the plugin
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_FULL_TREE")]
+    full_tree: bool,
+
+    /// Reduce memory usage for large inputs like places, by clearing the properties of instances that aren't saved
+    /// and freeing original sources early
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_LOW_MEMORY")]
+    low_memory: bool,
+
+    /// Print the peak allocated bytes and peak RSS of the decode, transpile and encode phases in the summary,
+    /// for choosing between the default and --low-memory on constrained machines (RSS is only measured on Linux)
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_MEM_STATS")]
+    mem_stats: bool,
+
+    /// Delete instances matching the glob pattern before transpiling, e.g. "**/Tests/**" (paths look like script/Lib/Tests/)
+    #[arg(long = "strip", value_name = "PATTERN", value_delimiter = ',', env = "PLUGINPROXY_STRIP")]
+    strip_paths: Vec<String>,
+
+    /// What to do with TestEZ and Jest-Lua test modules (keep, skip, strip)
+    #[arg(long, value_name = "ACTION", env = "PLUGINPROXY_TEST_MODULES")]
+    test_modules: Option<TestModules>,
+
+    /// Extra glob pattern of test module paths, like **/Specs/** (repeatable)
+    #[arg(long = "test-path", value_name = "PATTERN", value_delimiter = ',', env = "PLUGINPROXY_TEST_PATHS")]
+    test_paths: Vec<String>,
+
+    /// Transpile only the main script and leave the modules below it untouched, for plugins that only use the plugin API
+    /// in the entry point
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_MAIN_ONLY")]
+    main_only: bool,
+
+    /// Remove ModuleScripts that are never required from the main script
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PRUNE_UNUSED")]
+    prune_unused: bool,
+
+    /// Convert LocalScripts below the main script into modules with a run function, and rewrite
+    /// `script.X.Disabled = false` into `require(script.X).run()`
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_LOCAL_SCRIPTS_AS_MODULES")]
+    local_scripts_as_modules: bool,
+
+    /// Generate a main module that runs every top-level Script in order, for plugins without a single main script
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "select_script", env = "PLUGINPROXY_LOADER")]
+    loader: bool,
+
+    /// Levels below the root searched for the main script before searching the whole tree, 0 searches the whole tree [default: 2]
+    #[arg(long, value_name = "DEPTH", env = "PLUGINPROXY_SEARCH_DEPTH")]
+    search_depth: Option<u8>,
+
+    /// Write the original and transpiled source of every modified script, with the rules applied, to a JSON file
+    #[arg(long, value_name = "FILE", env = "PLUGINPROXY_SNAPSHOT")]
+    snapshot: Option<PathBuf>,
+
+    /// Write the transpiled line of every original statement line, per script, to a JSON file for translating stack traces
+    #[arg(long, value_name = "FILE", env = "PLUGINPROXY_SOURCE_MAP")]
+    source_map: Option<PathBuf>,
+
+    /// Write the SHA-256 of every script's original and transpiled source to <OUTPUT>.manifest.json,
+    /// for verifying later that the output wasn't modified
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_MANIFEST")]
+    manifest: bool,
+
+    /// Write the capabilities the plugin needs from the host (settings, enums, toolbar, ...) to <OUTPUT>.capabilities.json
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_CAPABILITIES")]
+    capabilities: bool,
+
+    /// Write a report of the scripts, rules fired, diagnostics and capabilities to <OUTPUT>.report.<md|html|json>,
+    /// for attaching to a pull request or a review
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_REPORT_FORMAT")]
+    report_format: Option<ReportFormat>,
+
+    /// Embed the capabilities the plugin needs in the output (attribute, module), implies --capabilities
+    #[arg(long, value_name = "WHERE", env = "PLUGINPROXY_EMBED_CAPABILITIES")]
+    embed_capabilities: Option<CapabilityEmbed>,
+
+    /// JSON description of the proxy runtime the plugin is for, warns about globals and capabilities it doesn't provide
+    #[arg(long, value_name = "FILE", env = "PLUGINPROXY_RUNTIME")]
+    runtime: Option<PathBuf>,
+
+    /// Fail without saving when the runtime doesn't provide what the plugin needs, instead of warning
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_STRICT_RUNTIME")]
+    strict_runtime: bool,
+
+    /// Fail without saving when the plugin sends HTTP requests, unless the config allows them with allow_http = true
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_STRICT_CAPABILITIES")]
+    strict_capabilities: bool,
+
+    /// Insert a PluginProxyMetadata Configuration into the output, with the transpiler version, options and a hash of the sources
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_METADATA")]
+    metadata: bool,
+
+    /// Embed a stable id of the plugin in the main script's wrapper and as an attribute, so proxy hosts can keep its
+    /// settings, widgets and toolbars across re-transpiles. Without a value the id is derived from the input file
+    #[arg(long, value_name = "ID", num_args = 0..=1, require_equals = true, default_missing_value = PLUGIN_ID_FROM_INPUT)]
+    #[arg(env = "PLUGINPROXY_PLUGIN_ID")]
+    plugin_id: Option<String>,
+
+    /// Prefix the keys of plugin:GetSetting and plugin:SetSetting with a namespace, so proxied plugins storing the same key
+    /// don't collide in the host's settings store. Without a value the namespace is the plugin's name
+    #[arg(long, value_name = "NAMESPACE", num_args = 0..=1, require_equals = true, default_missing_value = NAMESPACE_FROM_PLUGIN)]
+    #[arg(env = "PLUGINPROXY_SETTINGS_NAMESPACE")]
+    settings_namespace: Option<String>,
+
+    /// Flag risky constructs like HttpService, loadstring or requiring by asset id before transpiling,
+    /// with an overall verdict, the findings are listed in <OUTPUT>.audit.json
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_AUDIT")]
+    audit: bool,
+
+    /// Keep the original source of scripts that fail to transpile instead of aborting,
+    /// the failures are listed in <OUTPUT>.failures.json
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_ALLOW_FAILURES")]
+    allow_failures: bool,
+
+    /// Copy the output into the local Roblox Studio plugins folder, named after the plugin,
+    /// replacing the previous install
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_INSTALL")]
+    install: bool,
+
+    /// Plugins folder used by --install, found automatically on Windows and macOS
+    #[arg(long, value_name = "DIR", env = "PLUGINPROXY_PLUGINS_DIR")]
+    plugins_dir: Option<PathBuf>,
+
+    /// Keep the source of scripts that can't be parsed, like minified ones, untouched instead of aborting,
+    /// plugin API usage in them isn't rewritten and they're reported with the unparsed rule
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PASSTHROUGH_UNPARSED")]
+    passthrough_unparsed: bool,
+
+    /// Name of the variable holding the plugin globals in the generated code [default: _proxyGlobals]
+    #[arg(long, value_name = "NAME", value_parser = lua_identifier, env = "PLUGINPROXY_GLOBALS_NAME")]
+    globals_name: Option<String>,
+
+    /// Indentation of the generated code and of the wrapped main script, "tab" or a number of spaces [default: tab]
+    #[arg(long, value_name = "INDENT", env = "PLUGINPROXY_INDENT")]
+    indent: Option<Indent>,
+
+    /// Wording of the comment marking generated code [default: Autogenerated with PluginProxy Transpiler]
+    #[arg(long, value_name = "TEXT", env = "PLUGINPROXY_GENERATED_COMMENT")]
+    generated_comment: Option<String>,
+
+    /// Leave out the comment marking generated code
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "generated_comment")]
+    #[arg(env = "PLUGINPROXY_NO_GENERATED_COMMENT")]
+    no_generated_comment: bool,
+
+    /// Insert a Globals module under the main script and require it by name, instead of requiring the main script by depth
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_GLOBALS_MODULE")]
+    globals_module: bool,
+
+    /// Keep the original source of every modified script in the output, for debugging in Studio (attribute, string-value)
+    #[arg(long, value_name = "WHERE", env = "PLUGINPROXY_EMBED_ORIGINAL")]
+    embed_original: Option<EmbedOriginal>,
+
+    /// Luau directive to put at the top of the main script, e.g. strict, nocheck or "optimize 2" (repeatable)
+    #[arg(long = "directive", value_name = "DIRECTIVE", value_delimiter = ',', env = "PLUGINPROXY_DIRECTIVES")]
+    directives: Vec<String>,
+
+    /// Add unload and reload hooks next to init in the main script, for hosts that hot-swap plugin versions
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_HOT_RELOAD")]
+    hot_reload: bool,
+
+    /// Add a cleanup and an unload function to the main script that the host can call to tear the plugin down,
+    /// plugin.Unloading connections are made on the cleanup
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_UNLOAD_CLEANUP")]
+    unload_cleanup: bool,
+
+    /// Run the plugin only once even if the host calls init again, later calls return the same exports
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_MEMOIZE_INIT")]
+    memoize_init: bool,
+
+    /// Route calls that insert assets, like InsertService:LoadAsset and plugin:ImportFbxRig, through _proxyGlobals.assets
+    /// so the host can sandbox them, instead of only reporting them
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PROXY_ASSETS")]
+    proxy_assets: bool,
+
+    /// Route RunService:IsEdit(), IsStudio(), IsRunMode() and IsRunning() through _proxyGlobals.runContext,
+    /// so the host controls which mode the plugin believes Studio is in
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PROXY_RUN_CONTEXT")]
+    proxy_run_context: bool,
+
+    /// Route reads of what's below CoreGui, like game.CoreGui.SomePanel, through _proxyGlobals.coreGui,
+    /// the virtual container hosts expose instead of the real CoreGui, instead of only reporting them
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PROXY_COREGUI")]
+    proxy_coregui: bool,
+
+    /// Route AssetService calls, ContentProvider:PreloadAsync and rbxthumb:// URLs through _proxyGlobals.assets,
+    /// so the host can substitute its own thumbnail pipeline, instead of only reporting them
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PROXY_THUMBNAILS")]
+    proxy_thumbnails: bool,
+
+    /// Strip statements that prompt purchases or check monetization policies, like MarketplaceService:PromptPurchase,
+    /// for hosts that forbid purchase prompts from proxied plugins
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_STRIP_MONETIZATION")]
+    strip_monetization: bool,
+
+    /// Make the generated code assert that the host provides the plugin globals and every capability the script uses,
+    /// with an error naming what's missing instead of "attempt to index nil"
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_DEBUG_RUNTIME")]
+    debug_runtime: bool,
+
+    /// Keep every original statement on its original line, so stack traces can be mapped back to the original source
+    /// (the main script moves down by one line)
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PRESERVE_LINES")]
+    preserve_lines: bool,
+
+    /// Emit the generated locals as one `local _proxyGlobals, plugin, Enums = ...` line, which moves the original code
+    /// down less and keeps diffs of extracted outputs small
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_COMBINE_LOCALS")]
+    combine_locals: bool,
+
+    /// Put the generated locals after the leading `local X = require(...)` block of scripts instead of at the very top
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_PLACE_AFTER_REQUIRES")]
+    place_after_requires: bool,
+
+    /// Prefix of comments at the top of scripts to keep above the generated code, e.g. "!" or "selene:" (repeatable)
+    #[arg(long = "header-comment", value_name = "PREFIX", value_delimiter = ',', env = "PLUGINPROXY_HEADER_COMMENTS")]
+    header_comments: Vec<String>,
+
+    /// Service that game:GetService is proxied for (repeatable), for hosts whose game facade only provides some.
+    /// Every other service is got from the real game and reported. All services are proxied by default
+    #[arg(long = "allow-service", value_name = "SERVICE", value_delimiter = ',', env = "PLUGINPROXY_ALLOWED_SERVICES")]
+    allowed_services: Vec<String>,
+
+    /// Download the marketplace modules that the plugin requires by id like require(1234567) and insert them into the
+    /// plugin, so proxy hosts can load them. Needs network access and a build with the vendor feature
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_VENDOR_ASSET_REQUIRES")]
+    vendor_asset_requires: bool,
+
+    /// Rules that won't rewrite anything, comma separated in the environment variable
+    #[arg(long = "disable-rule", value_name = "RULE", value_delimiter = ',', env = "PLUGINPROXY_DISABLE_RULES")]
+    disabled_rules: Vec<String>,
+
+    /// Rules that are off by default to enable, like require-cycle, comma separated in the environment variable
+    #[arg(long = "enable-rule", value_name = "RULE", value_delimiter = ',', env = "PLUGINPROXY_ENABLE_RULES")]
+    enabled_rules: Vec<String>,
+
+    /// Config file to use instead of pluginproxy.toml in the working directory
+    #[arg(long, value_name = "FILE", env = "PLUGINPROXY_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Named profile from the config file to apply on top of its top level options
+    #[arg(long, value_name = "NAME", env = "PLUGINPROXY_PROFILE")]
+    profile: Option<String>,
 }
 
-fn routine(log_file: LogFile) -> Result<(), Problem> {
-    let cli = TranspilerCliArgs::parse();
+/// Errors of the command line itself, on top of the problems of the library
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error(transparent)]
+    Problem(#[from] Problem),
+    #[error("{0} can't be given when transpiling several inputs, each input is saved next to it with its own artifacts")]
+    BatchOption(&'static str),
+    #[error("{0} inputs failed to transpile")]
+    BatchFailed(usize),
+}
 
-    info!("PluginProxy Transpiler {}", env!("CARGO_PKG_VERSION"));
-    let in_file = match cli.input {
-        Some(path) => {
-            RbxFileType::from_path(&path)?;
-            path
+impl CliError {
+    /// See [`Problem::frame`]
+    fn frame(&self) -> Option<&str> {
+        match self {
+            CliError::Problem(problem) => problem.frame(),
+            _ => None,
+        }
+    }
+
+    /// See [`Problem::report`]
+    fn report(&self) -> String {
+        match self {
+            CliError::Problem(problem) => problem.report(),
+            error => error.to_string(),
         }
-        None => {
-            let msg = "Select a Roblox binary/xml file containing a plugin";
-            info!("{msg}");
-            FileDialog::new()
-                .set_title(msg)
-                .add_filter("Roblox", &["rbxm", "rbxl", "rbxmx", "rbxlx"])
-                .pick_file()
-                .ok_or(Problem::RFDCancel)?
+    }
+}
+
+fn routine(cli: TranspilerCliArgs, log_file: LogFile) -> Result<(), CliError> {
+    info!("PluginProxy Transpiler {}", env!("CARGO_PKG_VERSION"));
+    let result = match cli.command {
+        Some(Command::Diff(args)) => diff::diff(args),
+        Some(Command::Check(args)) => diff::check(args),
+        Some(Command::Compare(args)) => diff::compare(args),
+        Some(Command::Lint(args)) => lint::lint(args),
+        Some(Command::Doctor(args)) => doctor::doctor(args),
+        Some(Command::Inspect(args)) => inspect::inspect(args),
+        Some(Command::Census(args)) => census::census(args),
+        Some(Command::Rpc(args)) => rpc::rpc(args),
+        None => return transpile(cli.transpile, cli.verbosity.verbose > 0, log_file),
+    };
+    Ok(result?)
+}
+
+/// Output next to the input and named after it, like `MyPlugin.rbxmx` -> `MyPlugin-proxied.rbxmx`, in the given format
+/// or else the format of the input, so inputs with different names or formats never share an output
+fn default_output(in_file: &Path, format: Option<RbxFileType>) -> PathBuf {
+    let stem = match in_file.file_stem() {
+        Some(stem) if in_file.as_os_str() != STANDARD_STREAM => stem.to_string_lossy(),
+        _ => "out".into(),
+    };
+    let extension = match format.or_else(|| RbxFileType::from_path(in_file).ok()) {
+        Some(RbxFileType::XML) => "rbxmx",
+        Some(RbxFileType::Binary) | None => "rbxm",
+    };
+    in_file.with_file_name(format!("{stem}-proxied.{extension}"))
+}
+
+/// Parses --globals-name, which becomes a local in the generated code so it has to be a Lua identifier
+fn lua_identifier(name: &str) -> Result<String, String> {
+    match is_valid_identifier(name) {
+        true => Ok(name.to_string()),
+        false => Err(format!("\"{name}\" isn't a valid Lua identifier")),
+    }
+}
+
+/// Transpiles every input with the config and runtime loaded once, inputs after a failed one are still transpiled
+/// and a combined summary follows the summaries of the inputs
+fn transpile(cli: TranspileArgs, verbose: bool, log_file: LogFile) -> Result<(), CliError> {
+    let config = config::Config::load(cli.config.as_deref())?.resolve(cli.profile.as_deref())?;
+    let runtime = match cli.runtime.as_ref().or(config.runtime.as_ref()) {
+        Some(path) => Some(RuntimeDescriptor::load(path)?),
+        None => None,
+    };
+
+    let known_rules = config.known_rules();
+    for (flag, rules) in [("--enable-rule", &cli.enabled_rules), ("--disable-rule", &cli.disabled_rules)] {
+        for rule in rules.iter().filter(|rule| !known_rules.contains(&rule.as_str())) {
+            warn!("Unknown rule '{rule}' in {flag}, known rules are: {}", known_rules.join(", "));
         }
+    }
+
+    let from_rojo = cli.from_rojo || config.from_rojo.unwrap_or(false);
+    if from_rojo && !cfg!(feature = "luau") {
+        warn!("Built without the luau feature, Luau syntax in the model can't be parsed");
+    }
+    let inputs = match cli.input.as_slice() {
+        [] if from_rojo => vec![PathBuf::from(STANDARD_STREAM)],
+        [] => vec![pick_input()?],
+        inputs => inputs.to_vec(),
     };
-    let input_dir = in_file.parent().ok_or(Problem::InvalidPath)?;
+    if inputs.len() == 1 {
+        transpile_input(&cli, &config, runtime.as_ref(), inputs[0].clone(), verbose, &log_file)?;
+        return Ok(());
+    }
+    let batch_options = [
+        ("An output", cli.output.is_some() || config.output.is_some()),
+        ("A snapshot file", cli.snapshot.is_some() || config.snapshot.is_some()),
+        ("A source map file", cli.source_map.is_some() || config.source_map.is_some()),
+    ];
+    if let Some((option, _)) = batch_options.into_iter().find(|(_, given)| *given) {
+        return Err(CliError::BatchOption(option));
+    }
 
-    let out_file = match cli.output {
+    let mut failed = 0;
+    let mut scripts = 0;
+    let mut rewrites = 0;
+    for in_file in inputs.iter().cloned() {
+        info!("Transpiling {}", in_file.display());
+        match transpile_input(&cli, &config, runtime.as_ref(), in_file.clone(), verbose, &log_file) {
+            Ok(metrics) => {
+                scripts += metrics.scripts.len();
+                rewrites += metrics.total_rewrites();
+            }
+            Err(error) => {
+                failed += 1;
+                log::error!("Couldn't transpile {}: {}", in_file.display(), error.report());
+            }
+        }
+    }
+    if let Some(log_file) = &mut *log_file.write().unwrap() {
+        log_file.finish()?;
+    }
+
+    info!(
+        target: SUMMARY_TARGET,
+        "Done with {} plugins! Transpiled {} of them, {scripts} scripts with {rewrites} rewrites",
+        inputs.len(),
+        inputs.len() - failed
+    );
+    match failed {
+        0 => Ok(()),
+        failed => Err(CliError::BatchFailed(failed)),
+    }
+}
+
+/// Transpiles one input, saving it next to the input unless an output was given
+///
+/// # Returns
+/// Metrics of the transpiled plugin for the combined summary
+fn transpile_input(
+    cli: &TranspileArgs,
+    config: &config::Options,
+    runtime: Option<&RuntimeDescriptor>,
+    in_file: PathBuf,
+    verbose: bool,
+    log_file: &LogFile,
+) -> Result<Metrics, Problem> {
+    let batch = cli.input.len() > 1;
+    let include_libs = cli.include_libs || config.include_libs.unwrap_or(false);
+    let no_logs = cli.no_logs || config.no_logs.unwrap_or(false);
+    let allow_failures = cli.allow_failures || config.allow_failures.unwrap_or(false);
+    let low_memory = cli.low_memory || config.low_memory.unwrap_or(false);
+    let prune_unused = cli.prune_unused || config.prune_unused.unwrap_or(false);
+    let globals_module = cli.globals_module || config.globals_module.unwrap_or(false);
+    let snapshot = cli.snapshot.clone().or_else(|| config.snapshot.clone());
+    let source_map = cli.source_map.clone().or_else(|| config.source_map.clone());
+    let manifest = cli.manifest || config.manifest.unwrap_or(false);
+    let embed_capabilities = cli.embed_capabilities.or(config.embed_capabilities);
+    let capabilities = cli.capabilities || config.capabilities.unwrap_or(false) || embed_capabilities.is_some();
+    let strict_capabilities = cli.strict_capabilities || config.strict_capabilities.unwrap_or(false);
+    let report_format = cli.report_format.or(config.report_format);
+    let mut mem_stats = (cli.mem_stats || config.mem_stats.unwrap_or(false)).then(MemStats::new);
+
+    let from_rojo = cli.from_rojo || config.from_rojo.unwrap_or(false);
+    if in_file.as_os_str() != STANDARD_STREAM {
+        RbxFileType::from_path_or(&in_file, cli.input_format)?;
+    }
+
+    let out_file = match cli.output.clone().or_else(|| config.output.clone()) {
+        Some(path) if path.as_os_str() == STANDARD_STREAM => path,
         Some(path) => {
-            RbxFileType::from_path(&path)?;
+            RbxFileType::from_path_or(&path, cli.output_format)?;
             path
         }
-        None => input_dir.join("out.rbxm"),
+        None => default_output(&in_file, cli.output_format.or(cli.input_format)),
     };
     let output_dir = out_file.parent().ok_or(Problem::InvalidPath)?;
 
-    let log_file_name = "PluginProxy-Transpiler.log";
-    if !cli.no_logs {
-        log_file.write().unwrap().replace(
-            fs::File::create(output_dir.join(log_file_name)).map_err(|error| Problem::IOError("create a log file", error))?,
-        );
+    // inputs of a batch share the log file next to the first output
+    if !no_logs && log_file.read().unwrap().is_none() {
+        log_file.write().unwrap().replace(LogWriter::create(output_dir.join(LOG_FILE_NAME))?);
     }
 
-    pluginproxy_transpiler::from_file(&in_file)?
-        .exclude_libs(!cli.include_libs)
-        .transpile_tree()?
-        .save_to_file(&out_file)?;
+    let to_stdout = out_file.as_os_str() == STANDARD_STREAM;
+    let output_format = match cli.output_format {
+        None if to_stdout => RbxFileType::Binary,
+        format => RbxFileType::from_path_or(&out_file, format)?,
+    };
 
-    let end_message = if !cli.no_logs {
-        format!(" Check {log_file_name} for a full log")
+    let search_depth = cli.search_depth.or(config.search_depth).unwrap_or(MAIN_SEARCH_DEPTH);
+    let mut plugin_id = cli.plugin_id.clone().or_else(|| config.plugin_id.clone());
+    if let Some(mem_stats) = &mem_stats {
+        mem_stats.start_phase();
+    }
+    let mut tree = if in_file.as_os_str() == STANDARD_STREAM {
+        let mut contents = Vec::new();
+        io::stdin().lock().read_to_end(&mut contents).map_err(|error| Problem::IOError("read the input from stdin", error))?;
+        let input_format = cli
+            .input_format
+            .or_else(|| RbxFileType::from_contents(&contents))
+            .ok_or_else(|| Problem::InvalidExtension(in_file.clone()))?;
+        if plugin_id.as_deref() == Some(PLUGIN_ID_FROM_INPUT) {
+            plugin_id = Some(plugin_id_from_contents(&contents));
+        }
+        pluginproxy_transpiler::decode_reader(contents.as_slice(), input_format)?
+    } else {
+        let input_format = RbxFileType::from_path_or(&in_file, cli.input_format)?;
+        if plugin_id.as_deref() == Some(PLUGIN_ID_FROM_INPUT) {
+            let contents = fs::read(&in_file).map_err(|error| Problem::IOError("read the input file", error))?;
+            plugin_id = Some(plugin_id_from_contents(&contents));
+        }
+        pluginproxy_transpiler::decode_file_with_format(&in_file, input_format)?
+    };
+    if let Some(only_path) = cli.only_path.as_deref().or(config.only_path.as_deref()) {
+        pluginproxy_transpiler::keep_only_path(&mut tree, only_path)?;
+    }
+    if let Some(mem_stats) = &mut mem_stats {
+        mem_stats.end_phase("decode");
+        mem_stats.start_phase();
+    }
+    let mut transpiler = if cli.loader || config.loader.unwrap_or(false) {
+        DomTranspiler::with_loader_and_search_depth(tree, search_depth)?
+    } else {
+        select::from_tree(tree, cli.select_script.as_deref(), search_depth)?
+    };
+    config.configure(&mut transpiler)?;
+    if cli.audit || config.audit.unwrap_or(false) {
+        let report = audit::audit(transpiler.tree(), transpiler.source_script());
+        log_audit(&report);
+        let mut audit_file = out_file.clone().into_os_string();
+        audit_file.push(".audit.json");
+        let audit_file = PathBuf::from(audit_file);
+        let output = fs::File::create(&audit_file).map_err(|error| Problem::IOError("create the audit file", error))?;
+        serde_json::to_writer_pretty(output, &report).map_err(|error| Problem::JSONError("the audit file", error))?;
+    }
+    if cli.vendor_asset_requires || config.vendor_asset_requires.unwrap_or(false) {
+        vendor::vendor_asset_requires(&mut transpiler)?;
+    }
+    if let Some(globals_name) = cli.globals_name.as_deref().or(config.globals_name.as_deref()) {
+        transpiler.globals_name(globals_name);
+    }
+    if let Some(indent) = cli.indent.or(config.indent) {
+        transpiler.indent(indent);
+    }
+    // the command line overrides the config, either way
+    if cli.no_generated_comment {
+        transpiler.generated_comment(None::<String>);
+    } else if let Some(comment) = &cli.generated_comment {
+        transpiler.generated_comment(Some(comment));
+    } else if config.no_generated_comment.unwrap_or(false) {
+        transpiler.generated_comment(None::<String>);
+    } else if let Some(comment) = &config.generated_comment {
+        transpiler.generated_comment(Some(comment));
+    }
+    transpiler.strip_paths(cli.strip_paths.iter().cloned());
+    transpiler.test_paths(cli.test_paths.iter().cloned());
+    transpiler.test_modules(cli.test_modules.or(config.test_modules).unwrap_or_default());
+    if !cli.directives.is_empty() {
+        transpiler.directives(cli.directives.iter().cloned());
+    } else if let Some(directives) = &config.directives {
+        transpiler.directives(directives.iter().cloned());
+    }
+    if !cli.header_comments.is_empty() {
+        transpiler.header_comments(cli.header_comments.iter().cloned());
+    } else if let Some(header_comments) = &config.header_comments {
+        transpiler.header_comments(header_comments.iter().cloned());
+    }
+    if !cli.allowed_services.is_empty() {
+        transpiler.allowed_services(Some(cli.allowed_services.iter().cloned()));
+    } else if let Some(allowed_services) = &config.allowed_services {
+        transpiler.allowed_services(Some(allowed_services.iter().cloned()));
+    }
+    for rule in &cli.enabled_rules {
+        transpiler.rule(rule, true);
+    }
+    for rule in &cli.disabled_rules {
+        transpiler.rule(rule, false);
+    }
+    transpiler
+        .exclude_libs(!include_libs)
+        .prune_unused(prune_unused)
+        .local_scripts_as_modules(cli.local_scripts_as_modules || config.local_scripts_as_modules.unwrap_or(false))
+        .main_only(cli.main_only || config.main_only.unwrap_or(false))
+        .globals_module(globals_module)
+        .save_full_tree(cli.full_tree || config.full_tree.unwrap_or(false))
+        .low_memory(low_memory)
+        .preserve_lines(cli.preserve_lines || config.preserve_lines.unwrap_or(false))
+        .combine_locals(cli.combine_locals || config.combine_locals.unwrap_or(false))
+        .place_after_requires(cli.place_after_requires || config.place_after_requires.unwrap_or(false))
+        .debug_runtime(cli.debug_runtime || config.debug_runtime.unwrap_or(false))
+        .hot_reload(cli.hot_reload || config.hot_reload.unwrap_or(false))
+        .unload_cleanup(cli.unload_cleanup || config.unload_cleanup.unwrap_or(false))
+        .memoize_init(cli.memoize_init || config.memoize_init.unwrap_or(false))
+        .proxy_assets(cli.proxy_assets || config.proxy_assets.unwrap_or(false))
+        .proxy_run_context(cli.proxy_run_context || config.proxy_run_context.unwrap_or(false))
+        .proxy_coregui(cli.proxy_coregui || config.proxy_coregui.unwrap_or(false))
+        .proxy_thumbnails(cli.proxy_thumbnails || config.proxy_thumbnails.unwrap_or(false))
+        .strip_monetization(cli.strip_monetization || config.strip_monetization.unwrap_or(false))
+        .plugin_id(plugin_id)
+        .settings_namespace(cli.settings_namespace.clone().or_else(|| config.settings_namespace.clone()).map(|namespace| {
+            match namespace.as_str() {
+                NAMESPACE_FROM_PLUGIN => SettingsNamespace::PluginName,
+                _ => SettingsNamespace::Custom(namespace),
+            }
+        }))
+        .embed_original(cli.embed_original.or(config.embed_original))
+        .record_snapshots(snapshot.is_some())
+        .record_source_maps(source_map.is_some())
+        .record_manifest(manifest)
+        // the runtime is checked against the capabilities
+        .record_capabilities(capabilities || strict_capabilities || report_format.is_some() || runtime.is_some())
+        .embed_capabilities(embed_capabilities)
+        .record_metadata(cli.metadata || config.metadata.unwrap_or(false))
+        .allow_failures(allow_failures)
+        .keep_directives(from_rojo)
+        // frames need the original sources, which low-memory mode frees early
+        .code_frames(!low_memory)
+        .passthrough_unparsed(cli.passthrough_unparsed || config.passthrough_unparsed.unwrap_or(false))
+        .transpile_tree()?;
+    if let Some(mem_stats) = &mut mem_stats {
+        mem_stats.end_phase("transpile");
+    }
+
+    if let Some(runtime) = runtime {
+        let missing = runtime.missing(transpiler.metrics(), transpiler.capabilities());
+        if !missing.is_empty() {
+            if cli.strict_runtime || config.strict_runtime.unwrap_or(false) {
+                return Err(Problem::RuntimeIncompatible(runtime.display_name(), missing));
+            }
+            warn!("{} doesn't provide what the plugin needs: {}", runtime.display_name(), missing.join(", "));
+        }
+    }
+    let sends_requests = transpiler.capabilities().is_some_and(|manifest| manifest.needs.contains(&HTTP));
+    if sends_requests && !config.allow_http.unwrap_or(false) {
+        if strict_capabilities {
+            return Err(Problem::HttpNotAllowed);
+        }
+        warn!("The plugin sends HTTP requests, proxy hosts may want to know it phones home");
+    }
+    if let Some(mem_stats) = &mem_stats {
+        mem_stats.start_phase();
+    }
+    if to_stdout {
+        transpiler.save_to_writer(io::stdout().lock(), output_format)?;
+    } else {
+        transpiler.save_to_file_with_format(&out_file, output_format)?;
+    }
+    if let Some(mem_stats) = &mut mem_stats {
+        mem_stats.end_phase("encode");
+    }
+
+    if to_stdout && (cli.install || config.install.unwrap_or(false)) {
+        warn!("Can't install the plugin, it was written to stdout");
+    } else if cli.install || config.install.unwrap_or(false) {
+        let name = transpiler
+            .plugin_info()
+            .and_then(|info| info.name.clone())
+            .or_else(|| in_file.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        let installed = install::install(&out_file, &name, cli.plugins_dir.clone().or_else(|| config.plugins_dir.clone()))?;
+        info!("Installed the plugin to {}", installed.display());
+    }
+
+    log_diagnostics(transpiler.diagnostics(), verbose);
+
+    if !transpiler.failures().is_empty() {
+        let mut failures_file = out_file.clone().into_os_string();
+        failures_file.push(".failures.json");
+        let failures_file = PathBuf::from(failures_file);
+        transpiler.write_failures(&failures_file)?;
+        info!("Listed {} failed scripts in {}", transpiler.failures().len(), failures_file.display());
+    }
+
+    if let Some(snapshot_file) = &snapshot {
+        transpiler.write_snapshots(snapshot_file)?;
+        info!("Wrote {} script snapshots to {}", transpiler.snapshots().len(), snapshot_file.display());
+    }
+
+    if let Some(source_map_file) = &source_map {
+        transpiler.write_source_maps(source_map_file)?;
+        info!("Wrote source maps of {} scripts to {}", transpiler.source_maps().len(), source_map_file.display());
+    }
+
+    if manifest {
+        let mut manifest_file = out_file.clone().into_os_string();
+        manifest_file.push(".manifest.json");
+        let manifest_file = PathBuf::from(manifest_file);
+        transpiler.write_manifest(&manifest_file)?;
+        info!("Wrote checksums of {} scripts to {}", transpiler.manifest().scripts.len(), manifest_file.display());
+    }
+
+    if capabilities {
+        let mut capabilities_file = out_file.clone().into_os_string();
+        capabilities_file.push(".capabilities.json");
+        let capabilities_file = PathBuf::from(capabilities_file);
+        transpiler.write_capabilities(&capabilities_file)?;
+        info!("Wrote the capabilities to {}", capabilities_file.display());
+    }
+
+    if let Some(report_format) = report_format {
+        let mut report_file = out_file.clone().into_os_string();
+        report_file.push(format!(".report.{}", report_format.extension()));
+        let report_file = PathBuf::from(report_file);
+        transpiler.write_report(&report_file, report_format)?;
+        info!("Wrote the report to {}", report_file.display());
+    }
+
+    if let Some(log_file) = &mut *log_file.write().unwrap() {
+        let label = batch.then(|| in_file.display().to_string());
+        log_file.add_scripts(label.as_deref(), &transpiler)?;
+        if !batch {
+            log_file.finish()?;
+        }
+    }
+    let end_message = if !no_logs {
+        format!(" Check {LOG_FILE_NAME} for a full log")
     } else {
         String::new()
     };
 
-    info!("Done!{end_message}");
-    Ok(())
+    let metrics = transpiler.metrics();
+    info!(
+        target: SUMMARY_TARGET,
+        "Done! Transpiled {} scripts with {} rewrites.{end_message}",
+        metrics.scripts.len(),
+        metrics.total_rewrites()
+    );
+    if let Some(mem_stats) = &mem_stats {
+        mem_stats.log();
+    }
+    let requirements = metrics.requirements.names();
+    if !requirements.is_empty() {
+        info!("The plugin needs: {}", requirements.join(", "));
+    }
+    Ok(metrics.clone())
+}
+
+/// Logs the findings of the audit and its verdict
+fn log_audit(report: &AuditReport) {
+    for finding in &report.findings {
+        let line = finding.line.map(|line| format!(":{line}")).unwrap_or_default();
+        let message = format!("[audit {:?}] {}{line}: {}", finding.severity, finding.path, finding.message);
+        match finding.severity {
+            Severity::Low => info!("{message}"),
+            Severity::Medium | Severity::High => warn!("{message}"),
+        }
+    }
+    for path in &report.unparsed {
+        warn!("[audit] {path} couldn't be parsed, so it wasn't audited");
+    }
+
+    let verdict = match report.verdict {
+        Verdict::Pass => "pass",
+        Verdict::Review => "review the findings before trusting the plugin",
+        Verdict::Reject => "reject, the plugin can run code that isn't part of it",
+    };
+    info!("Audit verdict: {verdict} ({} findings)", report.findings.len());
+}
+
+/// Asks for the input with a file dialog, for when the transpiler is started without arguments
+fn pick_input() -> Result<PathBuf, Problem> {
+    let msg = "Select a Roblox binary/xml file containing a plugin";
+    info!("{msg}");
+    rfd::FileDialog::new()
+        .set_title(msg)
+        .add_filter("Roblox", &["rbxm", "rbxl", "rbxmx", "rbxlx"])
+        .pick_file()
+        .ok_or(Problem::RFDCancel)
+}
+
+/// Logs diagnostics, repeated ones are grouped with a count unless `verbose` is set
+fn log_diagnostics(diagnostics: &[Diagnostic], verbose: bool) {
+    if verbose {
+        for diagnostic in diagnostics {
+            let line = diagnostic.line.map(|line| format!(":{line}")).unwrap_or_default();
+            warn!(
+                target: SCRIPT_TARGET,
+                "[{}] {}{line}: {}{}",
+                diagnostic.rule,
+                diagnostic.path,
+                diagnostic.message,
+                frame_suffix(diagnostic)
+            );
+        }
+        return;
+    }
+
+    for group in group_diagnostics(diagnostics) {
+        if group.count > 1 {
+            warn!(
+                target: SCRIPT_TARGET,
+                "[{}] {}: {} (x{}, use --verbose to list all)",
+                group.rule,
+                group.path,
+                group.message,
+                group.count
+            );
+        } else {
+            let line = group.lines.first().map(|line| format!(":{line}")).unwrap_or_default();
+            let frame = diagnostics
+                .iter()
+                .find(|diagnostic| diagnostic.rule == group.rule && diagnostic.path == group.path && diagnostic.message == group.message)
+                .map(frame_suffix)
+                .unwrap_or_default();
+            warn!(target: SCRIPT_TARGET, "[{}] {}{line}: {}{frame}", group.rule, group.path, group.message);
+        }
+    }
+}
+
+/// The code frame of the diagnostic on the lines after its message
+fn frame_suffix(diagnostic: &Diagnostic) -> String {
+    diagnostic.frame.as_ref().map(|frame| format!("\n{frame}")).unwrap_or_default()
+}
+
+/// Whether the transpiler was started without arguments or `PLUGINPROXY_*` variables from a terminal,
+/// like by double clicking it, so the window is kept open until the result was read
+fn launched_interactively() -> bool {
+    std::env::args_os().nth(1).is_none()
+        && !std::env::vars_os().any(|(key, _)| key.to_string_lossy().starts_with("PLUGINPROXY_"))
+        && io::stdin().is_terminal()
+        && io::stdout().is_terminal()
 }
 
 fn main() {
+    let cli = TranspilerCliArgs::parse();
+    let level = cli.verbosity.level();
+
     let env_logger = env_logger::Builder::new()
         .format(|buf, record| {
             let timestamp = buf.timestamp();
@@ -122,9 +962,14 @@ fn main() {
 
             writeln!(buf, "[{timestamp} {style}{level:5}{style:#}] {}", args)
         })
-        .filter_level(log::LevelFilter::Info)
+        .filter_level(level.min(log::LevelFilter::Info))
+        .filter_module(env!("CARGO_CRATE_NAME"), level)
+        .filter_module("pluginproxy_transpiler", level)
+        .filter_module(SUMMARY_TARGET, log::LevelFilter::Info)
         .build();
 
+    // The library reports through `tracing`, with no subscriber installed its events are forwarded to `log`,
+    // so this logger keeps receiving them unchanged
     let log_file = Arc::new(RwLock::new(None));
     let logger = WrappedLogger {
         log: env_logger,
@@ -132,15 +977,23 @@ fn main() {
     };
 
     log::set_boxed_logger(Box::new(logger)).unwrap();
-    log::set_max_level(log::LevelFilter::Info);
+    log::set_max_level(level.max(log::LevelFilter::Info));
 
-    if let Err(error) = routine(log_file) {
+    let result = routine(cli, log_file);
+    if let Err(error) = &result {
         log::error!("Error occurred with PluginProxy Transpiler.");
-        log::error!("{}", error);
+        log::error!("{}", error.report());
+        if let Some(frame) = error.frame() {
+            log::error!("\n{frame}");
+        }
     }
 
-    if std::env::args().nth(1).is_none() {
-        println!("Press Enter to exit...");
+    if launched_interactively() {
+        eprintln!("Press Enter to exit...");
         io::stdin().read_line(&mut String::new()).unwrap();
     }
+
+    if result.is_err() {
+        std::process::exit(1);
+    }
 }
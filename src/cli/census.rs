@@ -0,0 +1,55 @@
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+use log::info;
+
+use pluginproxy_transpiler::{
+    census::{Census, CensusFormat},
+    error::Problem,
+    RbxFileType, MAIN_SEARCH_DEPTH,
+};
+
+use crate::select;
+
+#[derive(Args)]
+pub struct CensusArgs {
+    /// Plugin file to take the census of
+    #[arg(value_name = "INPUT", env = "PLUGINPROXY_INPUT")]
+    input: PathBuf,
+
+    /// Format of the input file, determined from its extension by default (binary, xml)
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_INPUT_FORMAT")]
+    input_format: Option<RbxFileType>,
+
+    /// Main script of the plugin when the file contains multiple, by name or full name
+    #[arg(long, value_name = "NAME", env = "PLUGINPROXY_SELECT_SCRIPT")]
+    select_script: Option<String>,
+
+    /// Levels below the root searched for the main script, 0 searches the whole tree
+    #[arg(long, value_name = "DEPTH", default_value_t = MAIN_SEARCH_DEPTH, env = "PLUGINPROXY_SEARCH_DEPTH")]
+    search_depth: u8,
+
+    /// Format of the census (json, csv)
+    #[arg(long, value_name = "FORMAT", default_value = "json", env = "PLUGINPROXY_CENSUS_FORMAT")]
+    format: CensusFormat,
+
+    /// File to write the census to instead of printing it
+    #[arg(short = 'o', long, value_name = "FILE", env = "PLUGINPROXY_CENSUS_OUTPUT")]
+    output: Option<PathBuf>,
+}
+
+pub fn census(args: CensusArgs) -> Result<(), Problem> {
+    let format = RbxFileType::from_path_or(&args.input, args.input_format)?;
+    let transpiler = select::load(&args.input, format, args.select_script.as_deref(), args.search_depth)?;
+    let census = Census::collect(transpiler.tree(), transpiler.source_script());
+    let rendered = census.render(args.format);
+
+    match &args.output {
+        Some(output) => {
+            fs::write(output, rendered).map_err(|error| Problem::IOError("write the census", error))?;
+            info!("Wrote the census of {} APIs to {}", census.usages().count(), output.display());
+        }
+        None => println!("{}", rendered.trim_end()),
+    }
+    Ok(())
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::warn;
+use serde::{
+    de::{self, Visitor},
+    Deserialize,
+};
+use toml::{Spanned, Value};
+
+use pluginproxy_transpiler::{
+    capabilities::CapabilityEmbed,
+    dom::rbx_path::is_valid_identifier,
+    error::Problem,
+    report::ReportFormat,
+    rules::{self, Rule, RuleDefinition},
+    DomTranspiler, EmbedOriginal, Indent, TestModules,
+};
+
+pub const CONFIG_FILE_NAME: &str = "pluginproxy.toml";
+
+/// Options that can be set in the config file, either at the top level or in a profile
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Options {
+    pub include_libs: Option<bool>,
+    /// Glob patterns of script paths that won't be transpiled
+    pub exclude: Option<Vec<String>>,
+    /// Glob patterns of instance paths that are deleted before transpiling
+    pub strip: Option<Vec<String>>,
+    pub test_modules: Option<TestModules>,
+    /// Glob patterns of test module paths, on top of the built-in ones
+    pub test_paths: Option<Vec<String>>,
+    /// Full name of the instance containing the plugin, everything else is freed after decoding
+    pub only_path: Option<String>,
+    /// Rule names mapped to whether they're enabled
+    pub rules: Option<BTreeMap<String, bool>>,
+    /// Glob patterns of script paths mapped to rule toggles for the matching scripts, on top of `rules`
+    pub path_rules: Option<BTreeMap<String, BTreeMap<String, bool>>>,
+    /// Declarative rules compiled into the rule registry, a profile's are added to the top level ones
+    pub custom_rules: Option<Vec<RuleDefinition>>,
+    /// The custom rules compiled once when resolving the options, every input shares them
+    #[serde(skip)]
+    compiled_rules: Vec<Arc<dyn Rule>>,
+    pub output: Option<PathBuf>,
+    pub snapshot: Option<PathBuf>,
+    pub source_map: Option<PathBuf>,
+    pub manifest: Option<bool>,
+    pub capabilities: Option<bool>,
+    pub embed_capabilities: Option<CapabilityEmbed>,
+    pub report_format: Option<ReportFormat>,
+    pub metadata: Option<bool>,
+    pub no_logs: Option<bool>,
+    pub globals_name: Option<String>,
+    pub indent: Option<Indent>,
+    pub generated_comment: Option<String>,
+    pub no_generated_comment: Option<bool>,
+    pub globals_module: Option<bool>,
+    pub allow_failures: Option<bool>,
+    pub passthrough_unparsed: Option<bool>,
+    pub install: Option<bool>,
+    pub from_rojo: Option<bool>,
+    pub plugins_dir: Option<PathBuf>,
+    pub audit: Option<bool>,
+    pub vendor_asset_requires: Option<bool>,
+    /// JSON description of the target proxy runtime
+    pub runtime: Option<PathBuf>,
+    pub strict_runtime: Option<bool>,
+    pub strict_capabilities: Option<bool>,
+    /// Lets plugins that send HTTP requests through with strict capabilities
+    pub allow_http: Option<bool>,
+    pub prune_unused: Option<bool>,
+    pub local_scripts_as_modules: Option<bool>,
+    pub main_only: Option<bool>,
+    pub search_depth: Option<u8>,
+    pub loader: Option<bool>,
+    pub full_tree: Option<bool>,
+    pub low_memory: Option<bool>,
+    pub mem_stats: Option<bool>,
+    pub preserve_lines: Option<bool>,
+    pub combine_locals: Option<bool>,
+    pub place_after_requires: Option<bool>,
+    pub debug_runtime: Option<bool>,
+    pub hot_reload: Option<bool>,
+    pub unload_cleanup: Option<bool>,
+    pub memoize_init: Option<bool>,
+    pub proxy_assets: Option<bool>,
+    pub proxy_run_context: Option<bool>,
+    pub proxy_coregui: Option<bool>,
+    pub proxy_thumbnails: Option<bool>,
+    /// Strips purchase prompts for hosts that forbid them
+    pub strip_monetization: Option<bool>,
+    /// Stable id of the plugin, "auto" derives it from the input file
+    pub plugin_id: Option<String>,
+    /// Namespace of the keys of plugin settings, "auto" uses the plugin's name
+    pub settings_namespace: Option<String>,
+    /// Luau directives for the top of the main script, like "strict" or "optimize 2"
+    pub directives: Option<Vec<String>>,
+    /// Prefixes of comments at the top of scripts that stay above the generated code, like "!" or "selene:"
+    pub header_comments: Option<Vec<String>>,
+    pub allowed_services: Option<Vec<String>>,
+    pub embed_original: Option<EmbedOriginal>,
+}
+
+impl Options {
+    /// Overrides these options with the ones that are set in `other`
+    fn merge(mut self, other: &Options) -> Self {
+        self.include_libs = other.include_libs.or(self.include_libs);
+        self.exclude = other.exclude.clone().or(self.exclude);
+        self.strip = other.strip.clone().or(self.strip);
+        self.test_modules = other.test_modules.or(self.test_modules);
+        self.test_paths = other.test_paths.clone().or(self.test_paths);
+        self.only_path = other.only_path.clone().or(self.only_path);
+        self.output = other.output.clone().or(self.output);
+        self.snapshot = other.snapshot.clone().or(self.snapshot);
+        self.source_map = other.source_map.clone().or(self.source_map);
+        self.manifest = other.manifest.or(self.manifest);
+        self.capabilities = other.capabilities.or(self.capabilities);
+        self.embed_capabilities = other.embed_capabilities.or(self.embed_capabilities);
+        self.report_format = other.report_format.or(self.report_format);
+        self.metadata = other.metadata.or(self.metadata);
+        self.no_logs = other.no_logs.or(self.no_logs);
+        self.globals_name = other.globals_name.clone().or(self.globals_name);
+        self.indent = other.indent.or(self.indent);
+        self.generated_comment = other.generated_comment.clone().or(self.generated_comment);
+        self.no_generated_comment = other.no_generated_comment.or(self.no_generated_comment);
+        self.globals_module = other.globals_module.or(self.globals_module);
+        self.allow_failures = other.allow_failures.or(self.allow_failures);
+        self.passthrough_unparsed = other.passthrough_unparsed.or(self.passthrough_unparsed);
+        self.install = other.install.or(self.install);
+        self.from_rojo = other.from_rojo.or(self.from_rojo);
+        self.plugins_dir = other.plugins_dir.clone().or(self.plugins_dir);
+        self.audit = other.audit.or(self.audit);
+        self.runtime = other.runtime.clone().or(self.runtime);
+        self.strict_runtime = other.strict_runtime.or(self.strict_runtime);
+        self.strict_capabilities = other.strict_capabilities.or(self.strict_capabilities);
+        self.allow_http = other.allow_http.or(self.allow_http);
+        self.prune_unused = other.prune_unused.or(self.prune_unused);
+        self.local_scripts_as_modules = other.local_scripts_as_modules.or(self.local_scripts_as_modules);
+        self.main_only = other.main_only.or(self.main_only);
+        self.search_depth = other.search_depth.or(self.search_depth);
+        self.loader = other.loader.or(self.loader);
+        self.full_tree = other.full_tree.or(self.full_tree);
+        self.low_memory = other.low_memory.or(self.low_memory);
+        self.mem_stats = other.mem_stats.or(self.mem_stats);
+        self.preserve_lines = other.preserve_lines.or(self.preserve_lines);
+        self.combine_locals = other.combine_locals.or(self.combine_locals);
+        self.place_after_requires = other.place_after_requires.or(self.place_after_requires);
+        self.debug_runtime = other.debug_runtime.or(self.debug_runtime);
+        self.hot_reload = other.hot_reload.or(self.hot_reload);
+        self.unload_cleanup = other.unload_cleanup.or(self.unload_cleanup);
+        self.memoize_init = other.memoize_init.or(self.memoize_init);
+        self.proxy_assets = other.proxy_assets.or(self.proxy_assets);
+        self.proxy_run_context = other.proxy_run_context.or(self.proxy_run_context);
+        self.proxy_coregui = other.proxy_coregui.or(self.proxy_coregui);
+        self.proxy_thumbnails = other.proxy_thumbnails.or(self.proxy_thumbnails);
+        self.strip_monetization = other.strip_monetization.or(self.strip_monetization);
+        self.plugin_id = other.plugin_id.clone().or(self.plugin_id);
+        self.settings_namespace = other.settings_namespace.clone().or(self.settings_namespace);
+        self.vendor_asset_requires = other.vendor_asset_requires.or(self.vendor_asset_requires);
+        self.directives = other.directives.clone().or(self.directives);
+        self.header_comments = other.header_comments.clone().or(self.header_comments);
+        self.allowed_services = other.allowed_services.clone().or(self.allowed_services);
+        self.embed_original = other.embed_original.or(self.embed_original);
+        if let Some(rules) = &other.rules {
+            self.rules.get_or_insert_with(BTreeMap::new).extend(rules.clone());
+        }
+        for (pattern, rules) in other.path_rules.iter().flatten() {
+            let path_rules = self.path_rules.get_or_insert_with(BTreeMap::new);
+            path_rules.entry(pattern.clone()).or_default().extend(rules.clone());
+        }
+        if let Some(custom_rules) = &other.custom_rules {
+            self.custom_rules.get_or_insert_with(Vec::new).extend(custom_rules.iter().cloned());
+        }
+        self
+    }
+
+    /// Names of the built-in rules and of the custom rules, to check the rules toggled by name against
+    pub fn known_rules(&self) -> Vec<&'static str> {
+        rules::ALL.iter().copied().chain(self.compiled_rules.iter().map(|rule| rule.name())).collect()
+    }
+
+    /// Applies the exclusions, strip patterns, custom rules and rule toggles to the transpiler
+    pub fn configure(&self, transpiler: &mut DomTranspiler) -> Result<(), Problem> {
+        if let Some(exclude) = &self.exclude {
+            transpiler.exclude_paths(exclude.iter().cloned());
+        }
+        if let Some(strip) = &self.strip {
+            transpiler.strip_paths(strip.iter().cloned());
+        }
+        if let Some(test_paths) = &self.test_paths {
+            transpiler.test_paths(test_paths.iter().cloned());
+        }
+
+        for rule in &self.compiled_rules {
+            transpiler.add_shared_rule(rule.clone());
+        }
+
+        let known_rules = self.known_rules();
+
+        for (rule, enabled) in self.rules.iter().flatten() {
+            if !known_rules.contains(&rule.as_str()) {
+                warn!("Unknown rule '{rule}' in config, known rules are: {}", known_rules.join(", "));
+            }
+            transpiler.rule(rule, *enabled);
+        }
+        for (pattern, path_rules) in self.path_rules.iter().flatten() {
+            for (rule, enabled) in path_rules {
+                if !known_rules.contains(&rule.as_str()) {
+                    warn!("Unknown rule '{rule}' for '{pattern}' in config, known rules are: {}", known_rules.join(", "));
+                }
+                transpiler.path_rule(pattern, rule, *enabled);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Contents of the config file
+///
+/// ```toml
+/// exclude = ["**/Vendor/**"]
+/// strip = ["**/Tests/**", "**/Stories/**"]
+///
+/// [profile.react-plugin]
+/// include-libs = true
+///
+/// [profile.legacy.rules]
+/// get-service = false
+///
+/// [path-rules."**/Vendor/**"]
+/// get-service = false
+///
+/// [[custom-rules]]
+/// name = "host-mouse"
+/// prefix = "plugin"
+/// method = "GetMouse"
+/// replacement = "{globals}.mouse"
+/// ```
+#[derive(Deserialize, Default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub defaults: Options,
+    #[serde(default)]
+    pub profile: BTreeMap<String, Options>,
+}
+
+impl Config {
+    /// Loads the config file from `file_path`, or `pluginproxy.toml` in the working directory if it exists
+    pub fn load(file_path: Option<&Path>) -> Result<Self, Problem> {
+        let file_path = match file_path {
+            Some(file_path) => file_path.to_path_buf(),
+            None if Path::new(CONFIG_FILE_NAME).is_file() => PathBuf::from(CONFIG_FILE_NAME),
+            None => return Ok(Self::default()),
+        };
+
+        let contents = fs::read_to_string(&file_path).map_err(|error| Problem::IOError("read the config file", error))?;
+        let problems = validate(&contents);
+        if !problems.is_empty() {
+            let message = format!("{} problems\n  {}", problems.len(), problems.join("\n  "));
+            return Err(Problem::InvalidConfig(file_path, message));
+        }
+        toml::from_str(&contents).map_err(|error| Problem::InvalidConfig(file_path, error.to_string()))
+    }
+
+    /// Gets the top level options, overridden by the options of the profile if one is chosen, with the custom rules
+    /// compiled
+    pub fn resolve(&self, profile: Option<&str>) -> Result<Options, Problem> {
+        let mut options = match profile {
+            Some(name) => {
+                let profile = self.profile.get(name).ok_or_else(|| {
+                    Problem::UnknownProfile(name.to_string(), self.profile.keys().cloned().collect())
+                })?;
+                self.defaults.clone().merge(profile)
+            }
+            None => self.defaults.clone(),
+        };
+        for definition in options.custom_rules.iter().flatten() {
+            options.compiled_rules.push(Arc::new(definition.clone().compile()?));
+        }
+        Ok(options)
+    }
+}
+
+/// Keys of a table in the config file with where they are, for reporting problems by line
+type SpannedTable = BTreeMap<Spanned<String>, Spanned<Value>>;
+
+#[derive(Deserialize)]
+struct SpannedProfiles {
+    #[serde(default)]
+    profile: BTreeMap<String, SpannedTable>,
+}
+
+/// Checks the config file for unknown keys, values of the wrong type and invalid glob patterns, all at once
+///
+/// # Returns
+/// Every problem found, prefixed with its line. Syntax errors are left for deserializing, which reports them with the line
+fn validate(contents: &str) -> Vec<String> {
+    let (Ok(table), Ok(profiles)) = (toml::from_str::<SpannedTable>(contents), toml::from_str::<SpannedProfiles>(contents)) else {
+        return Vec::new();
+    };
+
+    let mut problems = Vec::new();
+    for (key, value) in &table {
+        if key.get_ref() == "profile" {
+            if !value.get_ref().is_table() {
+                problems.push(format!("line {}: `profile` must be a table of profiles", line(contents, key.span().start)));
+            }
+            continue;
+        }
+        validate_option(contents, "", key, value, &mut problems);
+    }
+    for (name, options) in &profiles.profile {
+        for (key, value) in options {
+            validate_option(contents, &format!("profile.{name}."), key, value, &mut problems);
+        }
+    }
+    problems
+}
+
+/// Checks one key of the top level or of a profile, `prefix` is put before the key in the problems
+fn validate_option(contents: &str, prefix: &str, key: &Spanned<String>, value: &Spanned<Value>, problems: &mut Vec<String>) {
+    let name = key.get_ref();
+    let line = line(contents, key.span().start);
+    if !option_keys().contains(&name.as_str()) {
+        problems.push(format!("line {line}: unknown key `{prefix}{name}`"));
+        return;
+    }
+
+    // deserializing the key on its own gives the expected type in the error
+    let single = Value::Table(toml::Table::from_iter([(name.clone(), value.get_ref().clone())]));
+    if let Err(error) = single.try_into::<Options>() {
+        problems.push(format!("line {line}: `{prefix}{name}` {}", error.message().trim_end()));
+        return;
+    }
+
+    if name == "custom-rules" {
+        let definitions = value.get_ref().clone().try_into::<Vec<RuleDefinition>>().unwrap_or_default();
+        for definition in definitions {
+            if let Err(error) = definition.validate() {
+                problems.push(format!("line {line}: `{prefix}{name}` has an invalid rule, {error}"));
+            }
+        }
+    }
+
+    if let ("globals-name", Value::String(globals_name)) = (name.as_str(), value.get_ref()) {
+        if !is_valid_identifier(globals_name) {
+            problems.push(format!("line {line}: `{prefix}{name}` \"{globals_name}\" isn't a valid Lua identifier"));
+        }
+    }
+
+    let patterns: Vec<&str> = match (name.as_str(), value.get_ref()) {
+        ("exclude" | "strip" | "test-paths", Value::Array(patterns)) => patterns.iter().filter_map(Value::as_str).collect(),
+        ("path-rules", Value::Table(path_rules)) => path_rules.keys().map(String::as_str).collect(),
+        _ => Vec::new(),
+    };
+    for pattern in patterns {
+        if let Some(problem) = glob_problem(pattern) {
+            problems.push(format!("line {line}: glob pattern \"{pattern}\" in `{prefix}{name}` {problem}"));
+        }
+    }
+}
+
+/// Line of a byte offset in the contents, starting at 1
+fn line(contents: &str, offset: usize) -> usize {
+    contents[..offset.min(contents.len())].matches('\n').count() + 1
+}
+
+/// Why a glob pattern can't match what it's meant to, `None` if it's fine
+fn glob_problem(pattern: &str) -> Option<&'static str> {
+    if pattern.is_empty() {
+        return Some("is empty");
+    }
+
+    let (mut braces, mut brackets) = (0usize, 0usize);
+    let mut chars = pattern.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => {
+                chars.next();
+            }
+            '{' => braces += 1,
+            '}' if braces == 0 => return Some("closes a { that isn't open"),
+            '}' => braces -= 1,
+            '[' => brackets += 1,
+            ']' if brackets == 0 => return Some("closes a [ that isn't open"),
+            ']' => brackets -= 1,
+            _ => {}
+        }
+    }
+    match (braces, brackets) {
+        (0, 0) => None,
+        (0, _) => Some("has an unclosed ["),
+        _ => Some("has an unclosed {"),
+    }
+}
+
+/// Keys of [`Options`], as its derived `Deserialize` asks for them, so new options are known without listing them
+fn option_keys() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("only the fields of a struct are recorded"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit
+            unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    Options::deserialize(FieldNames(&mut fields)).ok();
+    fields
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use anstyle::{AnsiColor, Style};
+use clap::Args;
+use log::info;
+use similar::{udiff::UnifiedHunkHeader, ChangeTag, TextDiff};
+
+use pluginproxy_transpiler::{
+    dom::extension::{ForEachAction, InstanceExt, WeakDomExt},
+    error::Problem,
+    DomTranspiler,
+};
+use rbx_dom_weak::{
+    types::{Ref, Variant},
+    WeakDom,
+};
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Plugin file to transpile
+    #[arg(short = 'i', env = "PLUGINPROXY_INPUT")]
+    input: PathBuf,
+
+    /// Include all libraries, even non-plugin ones like React or Fusion
+    #[arg(long, visible_alias = "libs", action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_INCLUDE_LIBS")]
+    include_libs: bool,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Plugin file to transpile
+    #[arg(short = 'i', env = "PLUGINPROXY_INPUT")]
+    input: PathBuf,
+
+    /// Previously transpiled output to check against
+    #[arg(value_name = "OUTPUT", env = "PLUGINPROXY_OUTPUT")]
+    output: PathBuf,
+
+    /// Include all libraries, even non-plugin ones like React or Fusion
+    #[arg(long, visible_alias = "libs", action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_INCLUDE_LIBS")]
+    include_libs: bool,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(Args)]
+pub struct CompareArgs {
+    /// Previously transpiled output
+    #[arg(value_name = "OLD", env = "PLUGINPROXY_OLD_OUTPUT")]
+    old: PathBuf,
+
+    /// Output to compare against the old one, e.g. from a newer transpiler version or with other options
+    #[arg(value_name = "NEW", env = "PLUGINPROXY_NEW_OUTPUT")]
+    new: PathBuf,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    /// Amount of unchanged lines shown around every change
+    #[arg(long, value_name = "N", default_value_t = 3, env = "PLUGINPROXY_CONTEXT")]
+    context: usize,
+
+    /// Disable colored diffs, also disabled when not writing to a terminal
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "PLUGINPROXY_NO_COLOR")]
+    no_color: bool,
+}
+
+/// Renders unified diffs, with added lines in green and removed lines in red
+struct DiffRenderer {
+    context: usize,
+    header: Style,
+    hunk: Style,
+    added: Style,
+    removed: Style,
+}
+
+impl DiffRenderer {
+    fn new(args: &RenderArgs) -> Self {
+        let color = !args.no_color && io::stdout().is_terminal();
+        let style = |style: Style| if color { style } else { Style::new() };
+
+        Self {
+            context: args.context,
+            header: style(Style::new().bold()),
+            hunk: style(Style::new().fg_color(Some(AnsiColor::Cyan.into()))),
+            added: style(Style::new().fg_color(Some(AnsiColor::Green.into()))),
+            removed: style(Style::new().fg_color(Some(AnsiColor::Red.into()))),
+        }
+    }
+
+    fn write_header(&self, out: &mut impl Write, path: &str) -> io::Result<()> {
+        let header = self.header;
+        writeln!(out, "{header}--- a/{path}{header:#}")?;
+        writeln!(out, "{header}+++ b/{path}{header:#}")
+    }
+
+    fn write_diff(&self, out: &mut impl Write, old: &str, new: &str) -> io::Result<()> {
+        let diff = TextDiff::from_lines(old, new);
+
+        for group in diff.grouped_ops(self.context) {
+            let hunk = self.hunk;
+            writeln!(out, "{hunk}{}{hunk:#}", UnifiedHunkHeader::new(&group))?;
+
+            for op in &group {
+                for change in diff.iter_changes(op) {
+                    let (sign, style) = match change.tag() {
+                        ChangeTag::Delete => ('-', self.removed),
+                        ChangeTag::Insert => ('+', self.added),
+                        ChangeTag::Equal => (' ', Style::new()),
+                    };
+                    writeln!(out, "{style}{sign}{}{style:#}", change.value().trim_end_matches(['\r', '\n']))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the source of the main script and all its descendant scripts by path
+fn script_sources(transpiler: &DomTranspiler) -> BTreeMap<String, String> {
+    let tree = transpiler.tree();
+    let main = tree.get_by_ref(transpiler.source_script()).unwrap();
+
+    let mut sources = BTreeMap::new();
+    if let Some(source) = main.source() {
+        sources.insert(String::from("script"), source.to_string());
+    }
+
+    tree.foreach_descendant(
+        main,
+        &mut |child, path| {
+            if let Some(source) = child.source() {
+                sources.insert(path.to_string(), source.to_string());
+            }
+            ForEachAction::Continue
+        },
+        0,
+    );
+
+    sources
+}
+
+pub fn diff(args: DiffArgs) -> Result<(), Problem> {
+    let mut transpiler = pluginproxy_transpiler::from_file(&args.input)?;
+    transpiler
+        .exclude_libs(!args.include_libs)
+        .record_snapshots(true)
+        .transpile_tree()?;
+
+    let renderer = DiffRenderer::new(&args.render);
+    let mut out = io::stdout().lock();
+    for snapshot in transpiler.snapshots() {
+        renderer
+            .write_header(&mut out, &snapshot.path)
+            .and_then(|_| writeln!(out, "rules: {}", snapshot.rules.join(", ")))
+            .and_then(|_| renderer.write_diff(&mut out, &snapshot.original, &snapshot.transpiled))
+            .map_err(|error| Problem::IOError("write the diff", error))?;
+    }
+
+    info!("{} scripts changed", transpiler.snapshots().len());
+    Ok(())
+}
+
+pub fn check(args: CheckArgs) -> Result<(), Problem> {
+    let mut expected = pluginproxy_transpiler::from_file(&args.input)?;
+    expected.exclude_libs(!args.include_libs).transpile_tree()?;
+    let expected = script_sources(&expected);
+    let actual = script_sources(&pluginproxy_transpiler::from_file(&args.output)?);
+
+    let renderer = DiffRenderer::new(&args.render);
+    let mut out = io::stdout().lock();
+    let mut mismatches = 0;
+    for path in expected.keys().chain(actual.keys()).collect::<BTreeSet<_>>() {
+        let expected_source = expected.get(path).map(String::as_str).unwrap_or_default();
+        let actual_source = actual.get(path).map(String::as_str).unwrap_or_default();
+
+        if expected_source != actual_source {
+            mismatches += 1;
+            renderer
+                .write_header(&mut out, path)
+                .and_then(|_| renderer.write_diff(&mut out, actual_source, expected_source))
+                .map_err(|error| Problem::IOError("write the diff", error))?;
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(Problem::OutputMismatch(mismatches));
+    }
+
+    info!("Output is up to date with the input");
+    Ok(())
+}
+
+/// What an instance of a transpiled artifact holds, properties that point to instances hold the path of the target
+struct InstanceContents {
+    class: String,
+    source: Option<String>,
+    properties: BTreeMap<String, String>,
+}
+
+/// Collects every instance of an artifact by its full name, like `MyPlugin.Modules.Util`,
+/// instances with the same full name are numbered in the order they're found
+fn artifact_contents(tree: &WeakDom) -> BTreeMap<String, InstanceContents> {
+    let mut paths: HashMap<Ref, String> = HashMap::new();
+    let mut taken = HashSet::new();
+    tree.foreach_descendant(
+        tree.root(),
+        &mut |child, path| {
+            let mut full_name = path.components().join(".");
+            let mut duplicate = 1;
+            while taken.contains(&full_name) {
+                duplicate += 1;
+                full_name = format!("{} ({duplicate})", path.components().join("."));
+            }
+            taken.insert(full_name.clone());
+            paths.insert(child.referent(), full_name);
+            ForEachAction::Continue
+        },
+        0,
+    );
+
+    paths
+        .iter()
+        .filter_map(|(referent, path)| {
+            let instance = tree.get_by_ref(*referent)?;
+            let properties = instance
+                .properties
+                .iter()
+                .filter(|(name, value)| name.as_str() != "Source" && !matches!(value, Variant::UniqueId(_)))
+                .map(|(name, value)| {
+                    let value = match value {
+                        Variant::Ref(target) => paths.get(target).cloned().unwrap_or_else(|| String::from("nil")),
+                        value => format!("{value:?}"),
+                    };
+                    (name.clone(), value)
+                })
+                .collect();
+
+            Some((
+                path.clone(),
+                InstanceContents {
+                    class: instance.class.clone(),
+                    source: instance.source().map(String::from),
+                    properties,
+                },
+            ))
+        })
+        .collect()
+}
+
+pub fn compare(args: CompareArgs) -> Result<(), Problem> {
+    let old = artifact_contents(&pluginproxy_transpiler::decode_file(&args.old)?);
+    let new = artifact_contents(&pluginproxy_transpiler::decode_file(&args.new)?);
+
+    let renderer = DiffRenderer::new(&args.render);
+    let mut out = io::stdout().lock();
+    let (mut added, mut removed, mut changed_sources, mut changed_properties) = (0, 0, 0, 0);
+    for path in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+        let written = match (old.get(path), new.get(path)) {
+            (None, Some(instance)) => {
+                added += 1;
+                writeln!(out, "{}+ {path} ({}){:#}", renderer.added, instance.class, renderer.added)
+            }
+            (Some(instance), None) => {
+                removed += 1;
+                writeln!(out, "{}- {path} ({}){:#}", renderer.removed, instance.class, renderer.removed)
+            }
+            (Some(old), Some(new)) => {
+                let mut written = Ok(());
+                let old_source = old.source.as_deref().unwrap_or_default();
+                let new_source = new.source.as_deref().unwrap_or_default();
+                if old_source != new_source {
+                    changed_sources += 1;
+                    written = renderer.write_header(&mut out, path).and_then(|_| renderer.write_diff(&mut out, old_source, new_source));
+                }
+                if old.class != new.class {
+                    changed_properties += 1;
+                    written = written.and_then(|_| writeln!(out, "~ {path}.ClassName: {} -> {}", old.class, new.class));
+                }
+                for name in old.properties.keys().chain(new.properties.keys()).collect::<BTreeSet<_>>() {
+                    let old_value = old.properties.get(name).map(String::as_str).unwrap_or("(none)");
+                    let new_value = new.properties.get(name).map(String::as_str).unwrap_or("(none)");
+                    if old_value != new_value {
+                        changed_properties += 1;
+                        written = written.and_then(|_| writeln!(out, "~ {path}.{name}: {old_value} -> {new_value}"));
+                    }
+                }
+                written
+            }
+            (None, None) => Ok(()),
+        };
+        written.map_err(|error| Problem::IOError("write the comparison", error))?;
+    }
+
+    info!("{added} instances added, {removed} removed, {changed_sources} sources changed, {changed_properties} properties changed");
+    Ok(())
+}
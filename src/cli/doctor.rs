@@ -0,0 +1,155 @@
+use std::{fmt::Display, fs, path::PathBuf};
+
+use clap::Args;
+use log::{info, warn};
+use rbx_dom_weak::WeakDom;
+
+use pluginproxy_transpiler::{dom::extension::WeakDomExt, error::Problem, lint, main_script_candidates, RbxFileType};
+
+/// Services that only exist at the root of a place
+const PLACE_SERVICES: &[&str] = &["Workspace", "Lighting", "ReplicatedStorage", "ServerScriptService", "StarterGui"];
+
+#[derive(Args)]
+pub struct DoctorArgs {
+    /// File to check
+    #[arg(value_name = "INPUT", env = "PLUGINPROXY_INPUT")]
+    input: PathBuf,
+
+    /// Format of the input file, determined from its extension by default (binary, xml)
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_INPUT_FORMAT")]
+    input_format: Option<RbxFileType>,
+}
+
+#[derive(Default)]
+struct Doctor {
+    failures: usize,
+}
+
+impl Doctor {
+    fn pass(&self, message: impl Display) {
+        info!("[ok] {message}");
+    }
+
+    fn note(&self, message: impl Display) {
+        info!("[note] {message}");
+    }
+
+    fn fail(&mut self, message: impl Display, guidance: impl Display) {
+        self.failures += 1;
+        warn!("[fail] {message}");
+        warn!("       {guidance}");
+    }
+
+    fn finish(self) -> Result<(), Problem> {
+        match self.failures {
+            0 => {
+                info!("All checks passed");
+                Ok(())
+            }
+            failures => Err(Problem::ChecksFailed(failures)),
+        }
+    }
+}
+
+pub fn doctor(args: DoctorArgs) -> Result<(), Problem> {
+    let mut doctor = Doctor::default();
+    let file_name = args.input.display();
+
+    let contents = match fs::read(&args.input) {
+        Ok(contents) => {
+            doctor.pass(format!("{file_name} is readable ({} bytes)", contents.len()));
+            contents
+        }
+        Err(error) => {
+            doctor.fail(
+                format!("Could not read {file_name}: {error}"),
+                "Check that the path is correct and that the file isn't locked by another program",
+            );
+            return doctor.finish();
+        }
+    };
+
+    let expected_format = args.input_format.or_else(|| RbxFileType::from_path(&args.input).ok());
+    let format = match (expected_format, RbxFileType::from_contents(&contents)) {
+        (_, None) => {
+            doctor.fail(
+                "The contents are not a Roblox binary or xml file",
+                "Save the plugin from Studio with 'Save to File...' as .rbxm or .rbxmx",
+            );
+            return doctor.finish();
+        }
+        (None, Some(actual)) => {
+            doctor.fail(
+                format!("The extension is not recognized, but the contents are {actual:?}"),
+                "Rename the file to .rbxm/.rbxmx or pass --input-format",
+            );
+            actual
+        }
+        (Some(expected), Some(actual)) if expected != actual => {
+            doctor.fail(
+                format!("The contents are {actual:?}, but the extension or --input-format says {expected:?}"),
+                "Rename the file or pass --input-format matching its contents",
+            );
+            actual
+        }
+        (Some(_), Some(actual)) => {
+            doctor.pass(format!("Recognized the {actual:?} format"));
+            actual
+        }
+    };
+
+    let tree = match pluginproxy_transpiler::decode_reader(contents.as_slice(), format) {
+        Ok(tree) => {
+            doctor.pass("rbx-dom decoded the file");
+            tree
+        }
+        Err(error) => {
+            doctor.fail(
+                format!("rbx-dom could not decode the file: {}", error.report()),
+                format!(
+                    "The file may use features newer than PluginProxy Transpiler {} supports, update it or save the file in the other format",
+                    env!("CARGO_PKG_VERSION")
+                ),
+            );
+            return doctor.finish();
+        }
+    };
+
+    check_plugin(&mut doctor, &tree);
+    doctor.finish()
+}
+
+fn check_plugin(doctor: &mut Doctor, tree: &WeakDom) {
+    let is_place = tree
+        .root()
+        .children()
+        .iter()
+        .filter_map(|child| tree.get_by_ref(*child))
+        .any(|child| PLACE_SERVICES.contains(&child.class.as_str()));
+    if is_place {
+        doctor.note("The file looks like a place rather than a plugin model");
+    }
+
+    let candidates = main_script_candidates(tree);
+    match candidates.as_slice() {
+        [] if is_place => doctor.fail(
+            "No plugin script was found near the root of the place",
+            "Select the plugin's folder in Studio and use 'Save to File...' to export it as a model",
+        ),
+        [] => doctor.fail(
+            "No script was found that could be the main plugin script",
+            "The file doesn't contain a Script, LocalScript or ModuleScript, check that the right model was exported",
+        ),
+        [main] => doctor.pass(format!("Found the main script {}", tree.get_full_name(*main))),
+        _ => doctor.note(format!(
+            "Found {} possible main scripts, pick one with --select-script: {}",
+            candidates.len(),
+            candidates.iter().map(|candidate| tree.get_full_name(*candidate)).collect::<Vec<_>>().join(", ")
+        )),
+    }
+
+    let issues = lint::lint(tree);
+    if !issues.is_empty() {
+        doctor.note(format!("Found {} structural issues, run the lint command for details", issues.len()));
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use clap::Args;
+
+use pluginproxy_transpiler::{
+    error::Problem,
+    graph::{GraphFormat, RequireGraph},
+    RbxFileType, MAIN_SEARCH_DEPTH,
+};
+
+use crate::select;
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Plugin file to inspect
+    #[arg(value_name = "INPUT", env = "PLUGINPROXY_INPUT")]
+    input: PathBuf,
+
+    /// Format of the input file, determined from its extension by default (binary, xml)
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_INPUT_FORMAT")]
+    input_format: Option<RbxFileType>,
+
+    /// Main script to inspect when the file contains multiple, by name or full name
+    #[arg(long, value_name = "NAME", env = "PLUGINPROXY_SELECT_SCRIPT")]
+    select_script: Option<String>,
+
+    /// Levels below the root searched for the main script, 0 searches the whole tree
+    #[arg(long, value_name = "DEPTH", default_value_t = MAIN_SEARCH_DEPTH, env = "PLUGINPROXY_SEARCH_DEPTH")]
+    search_depth: u8,
+
+    /// Print the require graph in a format for rendering it (dot, mermaid)
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_INSPECT_GRAPH")]
+    graph: Option<GraphFormat>,
+}
+
+pub fn inspect(args: InspectArgs) -> Result<(), Problem> {
+    let format = RbxFileType::from_path_or(&args.input, args.input_format)?;
+    let transpiler = select::load(&args.input, format, args.select_script.as_deref(), args.search_depth)?;
+    let graph = RequireGraph::build(transpiler.tree(), transpiler.source_script());
+
+    let mut out = io::stdout().lock();
+    let result = match args.graph {
+        Some(format) => out.write_all(graph.render(format).as_bytes()),
+        None => write_summary(&mut out, &graph),
+    };
+    result.map_err(|error| Problem::IOError("write the inspection", error))
+}
+
+/// Lists every script with the scripts it requires and is required by
+fn write_summary(out: &mut impl Write, graph: &RequireGraph) -> io::Result<()> {
+    for node in graph.nodes() {
+        let kind = if node.is_module { "module" } else { "script" };
+        writeln!(out, "{} ({kind})", node.path)?;
+
+        for required in graph.requires(node) {
+            writeln!(out, "    requires {}", required.path)?;
+        }
+        for require in &node.unresolved {
+            writeln!(out, "    requires {require} (unresolved)")?;
+        }
+        for requiring in graph.required_by(node) {
+            writeln!(out, "    required by {}", requiring.path)?;
+        }
+    }
+
+    Ok(())
+}
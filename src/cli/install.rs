@@ -0,0 +1,44 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use pluginproxy_transpiler::error::Problem;
+
+/// Local plugins folder of Roblox Studio, only known on Windows and macOS
+pub fn plugins_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env::var_os("LOCALAPPDATA").map(|local| PathBuf::from(local).join("Roblox").join("Plugins"))
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Documents").join("Roblox").join("Plugins"))
+    } else {
+        None
+    }
+}
+
+/// File name for the plugin that can't escape the plugins folder or clash with plugins installed by Studio,
+/// which are named after their asset id
+fn safe_file_name(name: &str, extension: &str) -> String {
+    let name = name
+        .chars()
+        .map(|char| if char.is_ascii_alphanumeric() || matches!(char, '-' | '_') { char } else { '_' })
+        .collect::<String>();
+    let name = name.trim_matches('_');
+    let name = if name.is_empty() { "Plugin" } else { name };
+    format!("PluginProxy_{name}.{extension}")
+}
+
+/// Copies the output into the plugins folder under a safe name, replacing the previous install of the same plugin
+///
+/// # Returns
+/// Path of the installed file
+pub fn install(out_file: &Path, name: &str, plugins_dir: Option<PathBuf>) -> Result<PathBuf, Problem> {
+    let plugins_dir = plugins_dir.or_else(self::plugins_dir).ok_or(Problem::PluginsFolderNotFound)?;
+    fs::create_dir_all(&plugins_dir).map_err(|error| Problem::IOError("create the plugins folder", error))?;
+
+    let extension = out_file.extension().and_then(|extension| extension.to_str()).unwrap_or("rbxm");
+    let installed = plugins_dir.join(safe_file_name(name, extension));
+    fs::copy(out_file, &installed).map_err(|error| Problem::IOError("copy the output into the plugins folder", error))?;
+
+    Ok(installed)
+}
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use log::{info, warn};
+
+use pluginproxy_transpiler::{error::Problem, RbxFileType};
+
+#[derive(Args)]
+pub struct LintArgs {
+    /// Plugin file to check
+    #[arg(value_name = "INPUT", env = "PLUGINPROXY_INPUT")]
+    input: PathBuf,
+
+    /// Format of the input file, determined from its extension by default (binary, xml)
+    #[arg(long, value_name = "FORMAT", env = "PLUGINPROXY_INPUT_FORMAT")]
+    input_format: Option<RbxFileType>,
+}
+
+pub fn lint(args: LintArgs) -> Result<(), Problem> {
+    let format = RbxFileType::from_path_or(&args.input, args.input_format)?;
+    let tree = pluginproxy_transpiler::decode_file_with_format(&args.input, format)?;
+
+    let issues = pluginproxy_transpiler::lint::lint(&tree);
+    for issue in &issues {
+        warn!("[{:?}] {}: {}", issue.kind, issue.path, issue.message);
+    }
+
+    if !issues.is_empty() {
+        return Err(Problem::LintIssues(issues.len()));
+    }
+
+    info!("No issues found");
+    Ok(())
+}
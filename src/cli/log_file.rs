@@ -0,0 +1,148 @@
+//! Log file written next to the output. General entries are written as they're logged, the rewrites, diagnostics
+//! and failures of every script follow once transpiling is done, grouped under the path of the script.
+//! A `.log.json` twin has the same contents for tooling. Inputs of a batch share the log file,
+//! their groups are prefixed with the input
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use pluginproxy_transpiler::{error::Problem, DomTranspiler};
+
+pub const LOG_FILE_NAME: &str = "PluginProxy-Transpiler.log";
+
+/// Log target of entries that belong to a script, they're only written to the log file in the script's group
+pub const SCRIPT_TARGET: &str = "script";
+
+#[derive(Serialize)]
+struct LogEntry {
+    level: &'static str,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ScriptDiagnostic {
+    rule: &'static str,
+    line: Option<usize>,
+    message: String,
+}
+
+/// Everything logged about one script
+#[derive(Serialize, Default)]
+struct ScriptGroup {
+    /// Amount of rewrites done by each rule
+    rewrites: BTreeMap<&'static str, usize>,
+    diagnostics: Vec<ScriptDiagnostic>,
+    failure: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LogJson<'a> {
+    entries: &'a [LogEntry],
+    /// Groups by the path of the script in the format of script.Parent.Child
+    scripts: &'a BTreeMap<String, ScriptGroup>,
+}
+
+pub struct LogWriter {
+    file: fs::File,
+    path: PathBuf,
+    entries: Vec<LogEntry>,
+    scripts: BTreeMap<String, ScriptGroup>,
+}
+
+impl LogWriter {
+    pub fn create(path: PathBuf) -> Result<Self, Problem> {
+        Ok(Self {
+            file: fs::File::create(&path).map_err(|error| Problem::IOError("create a log file", error))?,
+            path,
+            entries: Vec::new(),
+            scripts: BTreeMap::new(),
+        })
+    }
+
+    /// Writes a general entry, entries of scripts are left for [`LogWriter::add_scripts`]
+    pub fn write(&mut self, record: &log::Record) {
+        if record.target() == SCRIPT_TARGET {
+            return;
+        }
+        let message = record.args().to_string();
+        self.file.write_all(format!("{message}\r\n").as_bytes()).ok();
+        self.entries.push(LogEntry {
+            level: record.level().as_str(),
+            message,
+        });
+    }
+
+    /// Writes the groups of the scripts of a transpiled input, `label` prefixes their paths in a batch
+    pub fn add_scripts(&mut self, label: Option<&str>, transpiler: &DomTranspiler) -> Result<(), Problem> {
+        let scripts = script_groups(transpiler)
+            .into_iter()
+            .map(|(path, group)| match label {
+                Some(label) => (format!("{label}: {path}"), group),
+                None => (path, group),
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut text = String::new();
+        for (path, group) in &scripts {
+            text.push_str(&format!("\r\n{path}\r\n"));
+            for (rule, count) in &group.rewrites {
+                text.push_str(&format!("    {rule}: {count} rewrites\r\n"));
+            }
+            for diagnostic in &group.diagnostics {
+                let line = diagnostic.line.map(|line| format!(" line {line}")).unwrap_or_default();
+                text.push_str(&format!("    [{}]{line}: {}\r\n", diagnostic.rule, diagnostic.message));
+            }
+            if let Some(failure) = &group.failure {
+                text.push_str(&format!("    failed: {failure}\r\n"));
+            }
+        }
+        self.file
+            .write_all(text.as_bytes())
+            .map_err(|error| Problem::IOError("write the log file", error))?;
+        self.scripts.extend(scripts);
+        Ok(())
+    }
+
+    /// Writes the JSON twin of the log file, with the groups of every input
+    pub fn finish(&mut self) -> Result<(), Problem> {
+        let json_path = json_path(&self.path);
+        let output = fs::File::create(&json_path).map_err(|error| Problem::IOError("create the JSON log file", error))?;
+        let log = LogJson {
+            entries: &self.entries,
+            scripts: &self.scripts,
+        };
+        serde_json::to_writer_pretty(output, &log).map_err(|error| Problem::JSONError("the JSON log file", error))
+    }
+}
+
+/// Groups of the scripts that something was logged about
+fn script_groups(transpiler: &DomTranspiler) -> BTreeMap<String, ScriptGroup> {
+    let mut scripts: BTreeMap<String, ScriptGroup> = BTreeMap::new();
+    for script in transpiler.metrics().scripts.iter().filter(|script| !script.rules.is_empty()) {
+        scripts.entry(script.path.clone()).or_default().rewrites = script.rules.clone();
+    }
+    for diagnostic in transpiler.diagnostics() {
+        scripts.entry(diagnostic.path.clone()).or_default().diagnostics.push(ScriptDiagnostic {
+            rule: diagnostic.rule,
+            line: diagnostic.line,
+            message: diagnostic.message.clone(),
+        });
+    }
+    for failure in transpiler.failures() {
+        scripts.entry(failure.path.clone()).or_default().failure = Some(failure.error.clone());
+    }
+    scripts
+}
+
+/// Path of the JSON twin, the log file's path with `.json` appended
+fn json_path(path: &Path) -> PathBuf {
+    let mut json_path = path.to_path_buf().into_os_string();
+    json_path.push(".json");
+    PathBuf::from(json_path)
+}
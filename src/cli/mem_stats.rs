@@ -0,0 +1,130 @@
+//! Memory used by each phase of transpiling, for `--mem-stats`
+//!
+//! Allocated bytes are counted by [`CountingAllocator`], the global allocator of the binary, once [`MemStats::new`]
+//! enabled counting. Peak RSS is read from `/proc/self/status`, so it's only available on Linux
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use log::info;
+
+use super::SUMMARY_TARGET;
+
+/// Whether allocations are counted, only with `--mem-stats` so other runs don't pay for the atomics
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+fn count_alloc(size: usize) {
+    if ENABLED.load(Ordering::Relaxed) {
+        let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_ALLOCATED.fetch_max(allocated, Ordering::Relaxed);
+    }
+}
+
+fn count_dealloc(size: usize) {
+    if ENABLED.load(Ordering::Relaxed) {
+        // memory allocated before counting was enabled is freed too
+        let _ = ALLOCATED.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |allocated| Some(allocated.saturating_sub(size)));
+    }
+}
+
+/// The system allocator, counting the bytes currently allocated and the peak since the last phase started
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            count_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        count_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                count_alloc(new_size - layout.size());
+            } else {
+                count_dealloc(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
+
+struct PhaseMemory {
+    name: &'static str,
+    /// Most bytes allocated at once during the phase
+    peak_allocated: usize,
+    /// Bytes still allocated when the phase ended
+    allocated: usize,
+    /// Peak resident set size of the process so far, it never goes down
+    peak_rss: Option<usize>,
+}
+
+pub struct MemStats {
+    phases: Vec<PhaseMemory>,
+}
+
+impl MemStats {
+    /// Enables counting allocations, from then on every allocation of the binary is counted
+    pub fn new() -> Self {
+        ENABLED.store(true, Ordering::Relaxed);
+        Self { phases: Vec::new() }
+    }
+
+    /// Starts measuring the peak of a phase from what's allocated now
+    pub fn start_phase(&self) {
+        PEAK_ALLOCATED.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    pub fn end_phase(&mut self, name: &'static str) {
+        self.phases.push(PhaseMemory {
+            name,
+            peak_allocated: PEAK_ALLOCATED.load(Ordering::Relaxed),
+            allocated: ALLOCATED.load(Ordering::Relaxed),
+            peak_rss: peak_rss(),
+        });
+    }
+
+    /// Logs every phase as part of the summary
+    pub fn log(&self) {
+        for phase in &self.phases {
+            let rss = phase.peak_rss.map(|rss| format!(", peak RSS {}", megabytes(rss))).unwrap_or_default();
+            info!(
+                target: SUMMARY_TARGET,
+                "Memory during {}: peak {} allocated, {} after{rss}",
+                phase.name,
+                megabytes(phase.peak_allocated),
+                megabytes(phase.allocated)
+            );
+        }
+    }
+}
+
+fn megabytes(bytes: usize) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Peak resident set size of the process in bytes, the VmHWM line of `/proc/self/status`
+#[cfg(target_os = "linux")]
+fn peak_rss() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse::<usize>().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peak_rss() -> Option<usize> {
+    None
+}
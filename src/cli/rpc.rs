@@ -0,0 +1,140 @@
+//! JSON-RPC over stdin and stdout, so build pipelines like Lune scripts can call the transpiler with structured
+//! requests and results instead of parsing logs
+//!
+//! Every line of stdin is a request `{"id": 1, "method": "transpileSource", "params": {...}}`, answered with one line
+//! of stdout, either `{"id": 1, "result": ...}` or `{"id": 1, "error": {"message": "..."}}`. Logs go to stderr
+//!
+//! Methods:
+//! * `version` - `{ version }`
+//! * `transpileSource` - params `{ source, depth?, globalsName?, disabledRules? }`, result `{ source }`
+//! * `transpileFile` - params `{ input, output, includeLibs?, selectScript?, searchDepth? }`,
+//!   result `{ metrics, diagnostics, failures }`
+
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use pluginproxy_transpiler::{error::Problem, DomTranspiler, RbxFileType, TranspileOptions, MAIN_SEARCH_DEPTH};
+
+use crate::select;
+
+#[derive(Args)]
+pub struct RpcArgs {}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SourceParams {
+    source: String,
+    #[serde(default)]
+    depth: usize,
+    globals_name: Option<String>,
+    #[serde(default)]
+    disabled_rules: BTreeSet<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileParams {
+    input: PathBuf,
+    output: PathBuf,
+    #[serde(default)]
+    include_libs: bool,
+    select_script: Option<String>,
+    search_depth: Option<u8>,
+}
+
+pub fn rpc(_args: RpcArgs) -> Result<(), Problem> {
+    let mut out = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|error| Problem::IOError("read a request from stdin", error))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let outcome = handle(&request.method, request.params);
+                Response {
+                    id: request.id,
+                    error: outcome.as_ref().err().map(|message| json!({ "message": message })),
+                    result: outcome.ok(),
+                }
+            }
+            Err(error) => Response {
+                id: Value::Null,
+                result: None,
+                error: Some(json!({ "message": format!("invalid request: {error}") })),
+            },
+        };
+
+        serde_json::to_writer(&mut out, &response).map_err(|error| Problem::JSONError("a response", error))?;
+        writeln!(out)
+            .and_then(|_| out.flush())
+            .map_err(|error| Problem::IOError("write a response to stdout", error))?;
+    }
+    Ok(())
+}
+
+/// Runs a method, errors are reported to the caller as messages
+fn handle(method: &str, params: Value) -> Result<Value, String> {
+    match method {
+        "version" => Ok(json!({ "version": env!("CARGO_PKG_VERSION") })),
+        "transpileSource" => {
+            let params: SourceParams = serde_json::from_value(params).map_err(|error| format!("invalid params: {error}"))?;
+            let mut options = TranspileOptions {
+                path_depth: params.depth,
+                disabled_rules: params.disabled_rules,
+                ..Default::default()
+            };
+            if let Some(globals_name) = params.globals_name {
+                options.globals_name = globals_name;
+            }
+            let source = DomTranspiler::transpile_script(&params.source, &options).map_err(|error| error.report())?;
+            Ok(json!({ "source": source }))
+        }
+        "transpileFile" => {
+            let params: FileParams = serde_json::from_value(params).map_err(|error| format!("invalid params: {error}"))?;
+            transpile_file(params).map_err(|error| error.report())
+        }
+        _ => Err(format!("unknown method '{method}'")),
+    }
+}
+
+fn transpile_file(params: FileParams) -> Result<Value, Problem> {
+    let input_format = RbxFileType::from_path(&params.input)?;
+    let output_format = RbxFileType::from_path(&params.output)?;
+    let search_depth = params.search_depth.unwrap_or(MAIN_SEARCH_DEPTH);
+    let mut transpiler = select::load(&params.input, input_format, params.select_script.as_deref(), search_depth)?;
+    transpiler.exclude_libs(!params.include_libs).transpile_tree()?;
+    transpiler.save_to_file_with_format(&params.output, output_format)?;
+
+    Ok(json!({
+        "metrics": transpiler.metrics(),
+        "diagnostics": transpiler.diagnostics(),
+        "failures": transpiler.failures(),
+    }))
+}
@@ -0,0 +1,64 @@
+use std::{
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use dialoguer::Select;
+use log::info;
+use rbx_dom_weak::{types::Ref, WeakDom};
+
+use pluginproxy_transpiler::{dom::extension::WeakDomExt, error::Problem, main_script_candidates_within, DomTranspiler, RbxFileType};
+
+/// Decodes the file and creates a transpiler for its main script, searched for within `depth` levels of the root
+///
+/// When the file contains multiple possible main scripts, `selected` chooses one by name or full name,
+/// otherwise the user picks one from a list if the terminal is interactive
+pub fn load(file_path: &Path, format: RbxFileType, selected: Option<&str>, depth: u8) -> Result<DomTranspiler, Problem> {
+    from_tree(pluginproxy_transpiler::decode_file_with_format(file_path, format)?, selected, depth)
+}
+
+/// Same as [`load`], with a tree that was already decoded
+pub fn from_tree(tree: WeakDom, selected: Option<&str>, depth: u8) -> Result<DomTranspiler, Problem> {
+    let candidates = main_script_candidates_within(&tree, depth);
+
+    let source_script = match (selected, candidates.as_slice()) {
+        (_, []) => return Err(Problem::NoMainSource),
+        (Some(selected), _) => find_selected(&tree, &candidates, selected)?,
+        (None, [candidate]) => *candidate,
+        (None, _) => pick(&tree, &candidates)?,
+    };
+
+    info!("Main script: {}", tree.get_full_name(source_script));
+    DomTranspiler::with_source_script(tree, source_script)
+}
+
+fn find_selected(tree: &WeakDom, candidates: &[Ref], selected: &str) -> Result<Ref, Problem> {
+    candidates
+        .iter()
+        .copied()
+        .find(|candidate| tree.get_full_name(*candidate) == selected)
+        .or_else(|| {
+            candidates
+                .iter()
+                .copied()
+                .find(|candidate| tree.get_by_ref(*candidate).is_some_and(|script| script.name == selected))
+        })
+        .ok_or_else(|| Problem::MainSourceNotFound(selected.to_string()))
+}
+
+fn pick(tree: &WeakDom, candidates: &[Ref]) -> Result<Ref, Problem> {
+    let names: Vec<String> = candidates.iter().map(|candidate| tree.get_full_name(*candidate)).collect();
+
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(Problem::AmbiguousMainSource(names));
+    }
+
+    Select::new()
+        .with_prompt("Found multiple possible main scripts, pick one")
+        .items(&names)
+        .default(0)
+        .interact_opt()
+        .map_err(|error| Problem::IOError("pick the main script", io::Error::other(error)))?
+        .map(|index| candidates[index])
+        .ok_or(Problem::NoMainSourceSelected)
+}
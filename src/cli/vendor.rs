@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use log::info;
+
+use pluginproxy_transpiler::{error::Problem, DomTranspiler};
+
+/// Where assets are downloaded from, the id is appended
+const ASSET_DELIVERY_URL: &str = "https://assetdelivery.roblox.com/v1/asset/?id=";
+
+/// Downloads the assets that the plugin requires by id and vendors them, until the vendored modules
+/// don't require any new ones
+pub fn vendor_asset_requires(transpiler: &mut DomTranspiler) -> Result<(), Problem> {
+    let mut vendored = BTreeSet::new();
+    loop {
+        let ids = transpiler.asset_requires().into_iter().filter(|id| !vendored.contains(id)).collect::<Vec<_>>();
+        if ids.is_empty() {
+            break;
+        }
+
+        for id in ids {
+            info!("Downloading asset {id}...");
+            let asset = download(id)?;
+            transpiler.vendor_asset(id, asset)?;
+            vendored.insert(id);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "vendor")]
+fn download(id: u64) -> Result<rbx_dom_weak::WeakDom, Problem> {
+    use std::io::Read;
+
+    let response = ureq::get(&format!("{ASSET_DELIVERY_URL}{id}"))
+        .call()
+        .map_err(|error| Problem::AssetDownload(id, error.to_string()))?;
+    let mut contents = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut contents)
+        .map_err(|error| Problem::AssetDownload(id, error.to_string()))?;
+
+    let format = pluginproxy_transpiler::RbxFileType::from_contents(&contents)
+        .ok_or_else(|| Problem::AssetDownload(id, String::from("the response isn't a Roblox model")))?;
+    pluginproxy_transpiler::decode_reader(contents.as_slice(), format)
+}
+
+/// Built without network access, so assets can't be downloaded
+#[cfg(not(feature = "vendor"))]
+fn download(id: u64) -> Result<rbx_dom_weak::WeakDom, Problem> {
+    log::warn!("Built without the vendor feature, can't download {ASSET_DELIVERY_URL}{id}");
+    Err(Problem::AssetDownload(id, String::from("built without the vendor feature")))
+}
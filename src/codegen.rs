@@ -0,0 +1,296 @@
+//! Builders of full_moon nodes for generated code, so rules don't have to assemble tokens and punctuation by hand
+//!
+//! Generated nodes have no trivia unless stated, print them with `to_string` or [`full_moon::print`]
+
+use full_moon::{
+    ast::{
+        punctuated::{Pair, Punctuated},
+        span::ContainedSpan,
+        Call, Expression, FunctionArgs, FunctionCall, Index, LocalAssignment, MethodCall, Prefix, Stmt, Suffix, Var,
+        VarExpression,
+    },
+    tokenizer::{StringLiteralQuoteType, Symbol, Token, TokenReference, TokenType},
+    ShortString,
+};
+
+use crate::dom::{
+    extension::{TokenRefExt, GLOBALS_MODULE_NAME, GLOBAL_VAR_NAME},
+    rbx_path::{lua_string, DotPath},
+};
+
+/// Creates a new identifier by returning an expression
+///
+/// # Arguments
+///
+/// `token_ref` - optional, closest token to newline, can be passed to preserve trivia (newline, whitespace)
+pub fn new_identifier_expression(identifier: &str, token_reference: Option<&TokenReference>) -> Expression {
+    let token_type = TokenType::Identifier {
+        identifier: ShortString::new(identifier),
+    };
+    let token_reference = match token_reference {
+        Some(token_reference) => token_reference.with_token(Token::new(token_type)),
+        None => TokenReference::new_type(token_type),
+    };
+
+    Expression::Symbol(token_reference)
+}
+
+/// Creates a new local assignment of a single name
+///
+/// Example: local name = expression
+pub fn new_local_assignment(name: &str, expression: Expression) -> LocalAssignment {
+    let mut name_list = Punctuated::new();
+    name_list.push(Pair::End(TokenReference::new_identifier(name)));
+
+    let mut expression_list = Punctuated::new();
+    expression_list.push(Pair::End(expression));
+
+    LocalAssignment::new(name_list)
+        .with_equal_token(Some(
+            TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Equal }).with_trivia(Some(" "), Some(" ")),
+        ))
+        .with_expressions(expression_list)
+}
+
+/// Creates a new local assignment of several names, one expression for each
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::codegen::{new_identifier_expression, new_multiple_local_assignment};
+///
+/// let expressions = vec![new_identifier_expression("1", None), new_identifier_expression("2", None)];
+/// assert_eq!(new_multiple_local_assignment(&["a", "b"], expressions).to_string(), "local a, b = 1, 2");
+/// ```
+pub fn new_multiple_local_assignment(names: &[&str], expressions: Vec<Expression>) -> LocalAssignment {
+    let comma = || TokenReference::symbol(", ").unwrap();
+    let punctuate = |index: usize, length: usize| index + 1 < length;
+
+    let name_list = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| match punctuate(index, names.len()) {
+            true => Pair::Punctuated(TokenReference::new_identifier(name), comma()),
+            false => Pair::End(TokenReference::new_identifier(name)),
+        })
+        .collect();
+    let length = expressions.len();
+    let expression_list = expressions
+        .into_iter()
+        .enumerate()
+        .map(|(index, expression)| match punctuate(index, length) {
+            true => Pair::Punctuated(expression, comma()),
+            false => Pair::End(expression),
+        })
+        .collect();
+
+    LocalAssignment::new(name_list)
+        .with_equal_token(Some(
+            TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Equal }).with_trivia(Some(" "), Some(" ")),
+        ))
+        .with_expressions(expression_list)
+}
+
+/// Creates a new local assignment that requires the main plugin source and gets its globals
+///
+/// Example: local _proxyGlobals = require(script.Parent.Parent).Globals
+pub fn new_global_require(depth: usize) -> LocalAssignment {
+    new_named_global_require(GLOBAL_VAR_NAME, depth)
+}
+
+/// Creates a new call of require with the path as the argument
+///
+/// # Arguments
+///
+/// * `path` - Expression leading to the module, e.g. script.Parent
+/// * `closing_parenthesis` - Closing parenthesis with its trivia
+pub fn new_require_call(path: &str, closing_parenthesis: &str) -> FunctionCall {
+    FunctionCall::new(Prefix::Name(TokenReference::new_identifier("require"))).with_suffixes(vec![Suffix::Call(
+        Call::AnonymousCall(FunctionArgs::Parentheses {
+            parentheses: ContainedSpan::new(
+                TokenReference::symbol("(").unwrap(),
+                TokenReference::symbol(closing_parenthesis).unwrap(),
+            ),
+            arguments: std::iter::once(Pair::End(new_identifier_expression(path, None))).collect(),
+        }),
+    )])
+}
+
+/// Same as [`new_global_require`], with a custom name for the globals variable
+pub fn new_named_global_require(globals_name: &str, depth: usize) -> LocalAssignment {
+    let require_func = new_require_call(&DotPath::new_ancestor_path(depth).to_string(), ")");
+
+    new_local_assignment(
+        globals_name,
+        Expression::Var(Var::Expression(Box::new(
+            VarExpression::new(Prefix::Expression(Box::new(Expression::FunctionCall(require_func)))).with_suffixes(vec![
+                Suffix::Index(Index::Dot {
+                    dot: TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Dot }),
+                    name: TokenReference::new_identifier("Globals").with_trivia(None, Some("\n")),
+                }),
+            ]),
+        ))),
+    )
+}
+
+/// Creates a statement that errors with the name of the missing capability and the script, when the expression is nil
+///
+/// Example: assert(plugin, "PluginProxy: the host didn't provide plugin, needed by " .. script:GetFullName())
+pub fn new_capability_assert(expression: &str, capability: &str) -> Stmt {
+    let message = lua_string(&format!("PluginProxy: the host didn't provide {capability}, needed by "));
+    let arguments = format!("{expression}, {message} .. script:GetFullName()");
+
+    Stmt::FunctionCall(
+        FunctionCall::new(Prefix::Name(TokenReference::new_identifier("assert"))).with_suffixes(vec![Suffix::Call(
+            Call::AnonymousCall(FunctionArgs::Parentheses {
+                parentheses: ContainedSpan::new(TokenReference::symbol("(").unwrap(), TokenReference::symbol(")\n").unwrap()),
+                arguments: std::iter::once(Pair::End(new_identifier_expression(&arguments, None))).collect(),
+            }),
+        )]),
+    )
+}
+
+/// Creates a new local assignment that requires the dedicated globals module, found by the name of the main script
+///
+/// Example: local _proxyGlobals = require(script:FindFirstAncestor("Main").Globals)
+pub fn new_globals_module_require(globals_name: &str, main_name: &str) -> LocalAssignment {
+    let path = format!("script:FindFirstAncestor({}).{GLOBALS_MODULE_NAME}", lua_string(main_name));
+    new_local_assignment(
+        globals_name,
+        Expression::FunctionCall(new_require_call(&path, ")\n")),
+    )
+}
+
+/// Creates a double quoted string literal, escaping the value
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::codegen::new_string_literal;
+///
+/// assert_eq!(new_string_literal("say \"hi\"").to_string(), r#""say \"hi\"""#);
+/// ```
+pub fn new_string_literal(value: &str) -> Expression {
+    let quoted = lua_string(value);
+    Expression::String(TokenReference::new_type(TokenType::StringLiteral {
+        literal: ShortString::new(&quoted[1..quoted.len() - 1]),
+        multi_line_depth: 0,
+        quote_type: StringLiteralQuoteType::Double,
+    }))
+}
+
+/// Creates a chain of dot indexes starting at a name, just the name if there are no fields
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::codegen::new_dot_chain;
+///
+/// assert_eq!(new_dot_chain("settings", &["Studio", "Theme"]).to_string(), "settings.Studio.Theme");
+/// ```
+pub fn new_dot_chain(root: &str, fields: &[&str]) -> Expression {
+    if fields.is_empty() {
+        return Expression::Var(Var::Name(TokenReference::new_identifier(root)));
+    }
+
+    let suffixes = fields
+        .iter()
+        .map(|field| {
+            Suffix::Index(Index::Dot {
+                dot: TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Dot }),
+                name: TokenReference::new_identifier(field),
+            })
+        })
+        .collect();
+    Expression::Var(Var::Expression(Box::new(
+        VarExpression::new(Prefix::Name(TokenReference::new_identifier(root))).with_suffixes(suffixes),
+    )))
+}
+
+/// Creates parenthesized call arguments separated by commas
+pub fn new_call_arguments(arguments: impl IntoIterator<Item = Expression>) -> FunctionArgs {
+    let mut arguments = arguments.into_iter().peekable();
+    let mut punctuated = Punctuated::new();
+    while let Some(argument) = arguments.next() {
+        punctuated.push(match arguments.peek() {
+            Some(_) => Pair::Punctuated(argument, TokenReference::symbol(", ").unwrap()),
+            None => Pair::End(argument),
+        });
+    }
+
+    FunctionArgs::Parentheses {
+        parentheses: ContainedSpan::new(TokenReference::symbol("(").unwrap(), TokenReference::symbol(")").unwrap()),
+        arguments: punctuated,
+    }
+}
+
+/// Creates a call of a function by name
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::codegen::{new_function_call, new_string_literal};
+///
+/// assert_eq!(new_function_call("warn", [new_string_literal("hi")]).to_string(), r#"warn("hi")"#);
+/// ```
+pub fn new_function_call(name: &str, arguments: impl IntoIterator<Item = Expression>) -> FunctionCall {
+    FunctionCall::new(Prefix::Name(TokenReference::new_identifier(name)))
+        .with_suffixes(vec![Suffix::Call(Call::AnonymousCall(new_call_arguments(arguments)))])
+}
+
+/// Creates a method call on an object. Names, indexes and calls are extended with the method,
+/// other expressions are wrapped in parentheses
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::codegen::{new_dot_chain, new_method_call, new_string_literal};
+///
+/// let call = new_method_call(new_dot_chain("game", &[]), "GetService", [new_string_literal("Selection")]);
+/// assert_eq!(call.to_string(), r#"game:GetService("Selection")"#);
+/// ```
+pub fn new_method_call(object: Expression, method: &str, arguments: impl IntoIterator<Item = Expression>) -> FunctionCall {
+    let (prefix, mut suffixes) = object_affixes(object);
+    suffixes.push(Suffix::Call(Call::MethodCall(MethodCall::new(
+        TokenReference::new_identifier(method),
+        new_call_arguments(arguments),
+    ))));
+    FunctionCall::new(prefix).with_suffixes(suffixes)
+}
+
+/// Creates a dot index of an object, which is extended or wrapped like the object of [`new_method_call`]
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::codegen::{new_dot_index, new_function_call};
+///
+/// let globals = new_dot_index(full_moon::ast::Expression::FunctionCall(new_function_call("require", [])), "Globals");
+/// assert_eq!(new_dot_index(globals, "plugin").to_string(), "require().Globals.plugin");
+/// ```
+pub fn new_dot_index(object: Expression, field: &str) -> Expression {
+    let (prefix, mut suffixes) = object_affixes(object);
+    suffixes.push(Suffix::Index(Index::Dot {
+        dot: TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Dot }),
+        name: TokenReference::new_identifier(field),
+    }));
+    Expression::Var(Var::Expression(Box::new(VarExpression::new(prefix).with_suffixes(suffixes))))
+}
+
+/// Prefix and suffixes that an object is continued from, expressions other than names, indexes and calls
+/// are put in parentheses
+fn object_affixes(object: Expression) -> (Prefix, Vec<Suffix>) {
+    match object {
+        Expression::Var(Var::Name(name)) => (Prefix::Name(name), Vec::new()),
+        Expression::Var(Var::Expression(var)) => (var.prefix().clone(), var.suffixes().cloned().collect()),
+        Expression::FunctionCall(call) => (call.prefix().clone(), call.suffixes().cloned().collect()),
+        expression @ Expression::Parentheses { .. } => (Prefix::Expression(Box::new(expression)), Vec::new()),
+        expression => (
+            Prefix::Expression(Box::new(Expression::Parentheses {
+                contained: ContainedSpan::new(TokenReference::symbol("(").unwrap(), TokenReference::symbol(")").unwrap()),
+                expression: Box::new(expression),
+            })),
+            Vec::new(),
+        ),
+    }
+}
@@ -1,16 +1,25 @@
+use std::collections::HashMap;
+
 use full_moon::{
     ast::*,
-    tokenizer::{StringLiteralQuoteType, Symbol, Token, TokenReference, TokenType},
+    tokenizer::{Token, TokenReference, TokenType},
     ShortString,
 };
-use punctuated::Pair;
-use punctuated::Punctuated;
-use rbx_dom_weak::{types::Ref, Instance, WeakDom};
-use span::ContainedSpan;
+use rbx_dom_weak::{
+    types::{Attributes, Ref, Variant},
+    Instance, InstanceBuilder, WeakDom,
+};
 
 use crate::dom::rbx_path::DotPath;
+/// Builders of generated code, kept here for code written before they moved to [`crate::codegen`]
+pub use crate::codegen::{
+    new_capability_assert, new_global_require, new_globals_module_require, new_identifier_expression, new_local_assignment,
+    new_multiple_local_assignment, new_named_global_require,
+};
 
 pub const GLOBAL_VAR_NAME: &str = "_proxyGlobals";
+/// Name of the module holding the plugin globals, when they're relocated out of the main script
+pub const GLOBALS_MODULE_NAME: &str = "Globals";
 
 /// Macro for producing string literal that indexes the global variable
 #[macro_export]
@@ -20,29 +29,103 @@ macro_rules! index_global {
     };
 }
 
-/// Macro for getting nth argument as a string (if arg is string), as achieving it may be tedious
-#[macro_export]
-macro_rules! nth_arg_string {
-    ($args: expr, $n: expr) => {
-        match $args {
-            FunctionArgs::Parentheses { arguments, .. } => arguments.iter().nth($n).and_then(|arg| {
-                if let Expression::String(token) = arg {
-                    token.identifier()
-                } else {
-                    None
-                }
-            }),
+/// An argument of a call, see [`nth_arg`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgValue<'a> {
+    /// A string literal, without its quotes
+    String(&'a str),
+    /// A number literal
+    Number(f64),
+    /// A variable, like `key` in `plugin:SetSetting(key, 5)`
+    Identifier(&'a str),
+    /// A table passed with `f{...}`
+    Table(&'a TableConstructor),
+    /// An interpolated string with expressions in it, like `` `Selec{suffix}` ``, which can't be resolved statically.
+    /// Ones without expressions are [`ArgValue::String`]
+    #[cfg(feature = "luau")]
+    Interpolated(&'a InterpolatedString),
+    /// Any other expression
+    Expression(&'a Expression),
+}
 
-            FunctionArgs::String(token) => {
-                if $n == 0 {
-                    token.identifier()
-                } else {
-                    None
-                }
-            }
+impl<'a> ArgValue<'a> {
+    /// The string, if the argument is a string literal
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            ArgValue::String(string) => Some(string),
             _ => None,
         }
-    };
+    }
+
+    /// The number, if the argument is a number literal
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            ArgValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a Luau number literal, including hexadecimal and binary ones and digit separators
+fn parse_number(literal: &str) -> Option<f64> {
+    let literal = literal.replace('_', "");
+    let integer = |digits: &str, radix| u64::from_str_radix(digits, radix).ok().map(|integer| integer as f64);
+    match literal.get(..2) {
+        Some("0x" | "0X") => integer(&literal[2..], 16),
+        Some("0b" | "0B") => integer(&literal[2..], 2),
+        _ => literal.parse().ok(),
+    }
+}
+
+/// Gets the nth argument of a call, counting from 0
+///
+/// # Example
+///
+/// ```rust
+/// use full_moon::ast::{Call, Stmt, Suffix};
+/// use pluginproxy_transpiler::dom::extension::{nth_arg, ArgValue};
+///
+/// let ast = full_moon::parse("plugin:SetSetting(key, 0x10)").unwrap();
+/// let Some(Stmt::FunctionCall(call)) = ast.nodes().stmts().next() else { unreachable!() };
+/// let Some(Suffix::Call(Call::MethodCall(method_call))) = call.suffixes().next() else { unreachable!() };
+///
+/// assert_eq!(nth_arg(method_call.args(), 0), Some(ArgValue::Identifier("key")));
+/// assert_eq!(nth_arg(method_call.args(), 1), Some(ArgValue::Number(16.0)));
+/// assert_eq!(nth_arg(method_call.args(), 2), None);
+///
+/// // interpolated strings are resolved when they have no expressions in them
+/// let ast = full_moon::parse("game:GetService(`Selection`, `Selec{suffix}`)").unwrap();
+/// let Some(Stmt::FunctionCall(call)) = ast.nodes().stmts().next() else { unreachable!() };
+/// let Some(Suffix::Call(Call::MethodCall(method_call))) = call.suffixes().next() else { unreachable!() };
+///
+/// assert_eq!(nth_arg(method_call.args(), 0), Some(ArgValue::String("Selection")));
+/// assert!(matches!(nth_arg(method_call.args(), 1), Some(ArgValue::Interpolated(_))));
+/// ```
+pub fn nth_arg(args: &FunctionArgs, n: usize) -> Option<ArgValue<'_>> {
+    match args {
+        FunctionArgs::Parentheses { arguments, .. } => arguments.iter().nth(n).map(|argument| match argument {
+            Expression::String(token) => token.identifier().map_or(ArgValue::Expression(argument), ArgValue::String),
+            Expression::Number(token) => {
+                token.literal().and_then(parse_number).map_or(ArgValue::Expression(argument), ArgValue::Number)
+            }
+            #[cfg(feature = "luau")]
+            Expression::InterpolatedString(string) if string.segments().next().is_none() => {
+                string.last_string().literal().map_or(ArgValue::Expression(argument), ArgValue::String)
+            }
+            #[cfg(feature = "luau")]
+            Expression::InterpolatedString(string) => ArgValue::Interpolated(string),
+            Expression::Var(Var::Name(name)) => name.identifier().map_or(ArgValue::Expression(argument), ArgValue::Identifier),
+            argument => ArgValue::Expression(argument),
+        }),
+        FunctionArgs::String(token) if n == 0 => token.identifier().map(ArgValue::String),
+        FunctionArgs::TableConstructor(table) if n == 0 => Some(ArgValue::Table(table)),
+        _ => None,
+    }
+}
+
+/// Gets the nth argument of a call if it's a string literal, see [`nth_arg`]
+pub fn nth_arg_string(args: &FunctionArgs, n: usize) -> Option<&str> {
+    nth_arg(args, n).and_then(|argument| argument.as_str())
 }
 
 pub enum SearchAction {
@@ -85,6 +168,17 @@ pub trait WeakDomExt {
     where
         F: FnMut(&Instance, &DotPath) -> ForEachAction;
 
+    /// Same as [`WeakDomExt::foreach_descendant`], but the operation receives the descendants mutably
+    ///
+    /// # Arguments
+    ///
+    /// * `parent` - Referent of the instance to iterate through
+    /// * `predicate` - function, receives Instance and its path, return ForEachAction - choose to either break or continue iteration
+    /// * `depth` - amount of levels to search, 0 for no limit
+    fn foreach_descendant_mut<F>(&mut self, parent: Ref, predicate: &mut F, depth: u8)
+    where
+        F: FnMut(&mut Instance, &DotPath) -> ForEachAction;
+
     /// Iterates through the entire descendant tree and returns a Vec of all Instance Refs that match
     ///
     /// # Arguments
@@ -106,6 +200,73 @@ pub trait WeakDomExt {
     fn find_first_child_class<F>(&self, parent: &Instance, class_predicate: F, depth: u8) -> Option<Ref>
     where
         F: Fn(&str) -> bool;
+
+    /// Gets the names of the instance and its ancestors (excluding the root) joined with dots, like `Instance:GetFullName()`
+    fn get_full_name(&self, referent: Ref) -> String;
+
+    /// Moves the children of the root of another dom and their descendants under `parent`.
+    /// Properties referencing instances that were moved along point to their new referents,
+    /// ones referencing anything else are cleared
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::dom::extension::WeakDomExt;
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut place = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let plugins = place.insert(place.root_ref(), InstanceBuilder::new("Folder").with_name("Plugins"));
+    ///
+    /// let mut plugin = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let main = plugin.insert(plugin.root_ref(), InstanceBuilder::new("Script").with_name("Main"));
+    /// plugin.insert(main, InstanceBuilder::new("ModuleScript").with_name("Util"));
+    ///
+    /// let grafted = place.graft(plugin, plugins);
+    /// assert_eq!(grafted.len(), 1);
+    /// assert_eq!(place.get_full_name(grafted[0]), "Plugins.Main");
+    /// assert_eq!(place.get_by_ref(grafted[0]).unwrap().children().len(), 1);
+    /// ```
+    ///
+    /// # Returns
+    /// Referents of the moved children, in their original order
+    fn graft(&mut self, other: WeakDom, parent: Ref) -> Vec<Ref>;
+
+    /// Copies an instance and its descendants into a new dom, as the only child of a DataModel root.
+    /// References are remapped like in [`WeakDomExt::graft`], the new dom is empty if the instance doesn't exist
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::dom::extension::WeakDomExt;
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut place = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let main = place.insert(place.root_ref(), InstanceBuilder::new("Script").with_name("Main"));
+    /// place.insert(main, InstanceBuilder::new("ModuleScript").with_name("Util"));
+    ///
+    /// let copy = place.clone_subtree(main);
+    /// let copied_main = copy.root().children()[0];
+    /// assert_eq!(copy.get_full_name(copied_main), "Main");
+    /// assert_ne!(copied_main, main);
+    /// ```
+    fn clone_subtree(&self, root: Ref) -> WeakDom;
+
+    /// Finds every instance in the dom with the attribute, with any value if `value` is `None`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::dom::extension::WeakDomExt;
+    /// use rbx_dom_weak::{types::{Attributes, Variant}, InstanceBuilder, WeakDom};
+    ///
+    /// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let attributes = Attributes::new().with("TranspilerVersion", String::from("0.1.0"));
+    /// let marker = tree.insert(tree.root_ref(), InstanceBuilder::new("Configuration").with_property("Attributes", attributes));
+    ///
+    /// assert_eq!(tree.find_by_attribute("TranspilerVersion", None), vec![marker]);
+    /// assert!(tree.find_by_attribute("TranspilerVersion", Some(&Variant::String(String::from("0.2.0")))).is_empty());
+    /// ```
+    fn find_by_attribute(&self, name: &str, value: Option<&Variant>) -> Vec<Ref>;
 }
 
 impl WeakDomExt for WeakDom {
@@ -133,6 +294,35 @@ impl WeakDomExt for WeakDom {
         }
     }
 
+    fn foreach_descendant_mut<F>(&mut self, parent: Ref, predicate: &mut F, depth: u8)
+    where
+        F: FnMut(&mut Instance, &DotPath) -> ForEachAction,
+    {
+        let mut queue = vec![(parent, DotPath::default())];
+
+        while let Some((current, mut path)) = queue.pop() {
+            let children = match self.get_by_ref(current) {
+                Some(current) => current.children().to_vec(),
+                None => continue,
+            };
+
+            for child_id in children {
+                let child = self.get_by_ref_mut(child_id).expect("child points to null ref?");
+
+                path.push(&child.name);
+                if let ForEachAction::Break = predicate(child, &path) {
+                    return;
+                }
+
+                if path.depth() < depth.into() || depth == 0 {
+                    queue.push((child_id, path.clone()));
+                }
+
+                path.pop();
+            }
+        }
+    }
+
     fn find_descendants<F>(&self, parent: &Instance, predicate: F, depth: u8) -> Vec<Ref>
     where
         F: Fn(&Instance) -> SearchAction,
@@ -179,6 +369,143 @@ impl WeakDomExt for WeakDom {
 
         result
     }
+
+    fn get_full_name(&self, referent: Ref) -> String {
+        let mut names = Vec::new();
+        let mut current = self.get_by_ref(referent);
+
+        while let Some(instance) = current {
+            if instance.referent() == self.root_ref() {
+                break;
+            }
+            names.push(instance.name.as_str());
+            current = self.get_by_ref(instance.parent());
+        }
+
+        names.reverse();
+        names.join(".")
+    }
+
+    fn graft(&mut self, other: WeakDom, parent: Ref) -> Vec<Ref> {
+        let mut referents = HashMap::new();
+        let grafted = other
+            .root()
+            .children()
+            .iter()
+            .filter_map(|child| copy_subtree(&other, *child, self, parent, &mut referents))
+            .collect();
+        remap_references(self, &referents);
+        grafted
+    }
+
+    fn find_by_attribute(&self, name: &str, value: Option<&Variant>) -> Vec<Ref> {
+        self.find_descendants(
+            self.root(),
+            |instance| {
+                let found = match instance.properties.get("Attributes") {
+                    Some(Variant::Attributes(attributes)) => match (attributes.get(name), value) {
+                        (Some(_), None) => true,
+                        (Some(attribute), Some(value)) => attribute == value,
+                        (None, _) => false,
+                    },
+                    _ => false,
+                };
+                SearchAction::Found(found)
+            },
+            0,
+        )
+    }
+
+    fn clone_subtree(&self, root: Ref) -> WeakDom {
+        let mut copy = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let mut referents = HashMap::new();
+        let copy_root = copy.root_ref();
+        copy_subtree(self, root, &mut copy, copy_root, &mut referents);
+        remap_references(&mut copy, &referents);
+        copy
+    }
+}
+
+/// Copies an instance and its descendants from one dom into another under `parent`,
+/// recording the new referent of every copied instance by its old one
+///
+/// # Returns
+/// Referent of the copy of `root`, `None` if it isn't in the source dom
+fn copy_subtree(source: &WeakDom, root: Ref, dest: &mut WeakDom, parent: Ref, referents: &mut HashMap<Ref, Ref>) -> Option<Ref> {
+    let mut queue = vec![(root, parent)];
+    while let Some((referent, parent)) = queue.pop() {
+        let Some(instance) = source.get_by_ref(referent) else {
+            continue;
+        };
+        let copy = dest.insert(
+            parent,
+            InstanceBuilder::new(instance.class.as_str())
+                .with_name(instance.name.as_str())
+                .with_properties(instance.properties.clone()),
+        );
+        referents.insert(referent, copy);
+        // reversed so children are popped, and inserted, in their original order
+        queue.extend(instance.children().iter().rev().map(|child| (*child, copy)));
+    }
+    referents.get(&root).copied()
+}
+
+/// Points properties of copied instances referencing other copied instances to the copies,
+/// references to instances that weren't copied are cleared as they don't exist in the dom
+fn remap_references(dom: &mut WeakDom, referents: &HashMap<Ref, Ref>) {
+    for copy in referents.values() {
+        let Some(instance) = dom.get_by_ref_mut(*copy) else {
+            continue;
+        };
+        for value in instance.properties.values_mut() {
+            if let Variant::Ref(target) = value {
+                if target.is_some() {
+                    *target = referents.get(target).copied().unwrap_or_else(Ref::none);
+                }
+            }
+        }
+    }
+}
+
+pub trait InstanceExt {
+    /// Gets the source of a script, `None` if it has no Source property or it isn't a string
+    fn source(&self) -> Option<&str>;
+    /// Same as [`InstanceExt::source`], but mutable
+    fn source_mut(&mut self) -> Option<&mut String>;
+    /// Sets the Source property, replacing any previous value
+    fn set_source(&mut self, source: String);
+    /// Sets an attribute, keeping the other attributes of the instance
+    fn set_attribute(&mut self, name: &str, value: Variant);
+}
+
+impl InstanceExt for Instance {
+    fn source(&self) -> Option<&str> {
+        match self.properties.get("Source") {
+            Some(Variant::String(source)) => Some(source),
+            _ => None,
+        }
+    }
+    fn source_mut(&mut self) -> Option<&mut String> {
+        match self.properties.get_mut("Source") {
+            Some(Variant::String(source)) => Some(source),
+            _ => None,
+        }
+    }
+    fn set_source(&mut self, source: String) {
+        self.properties.insert(String::from("Source"), Variant::String(source));
+    }
+    fn set_attribute(&mut self, name: &str, value: Variant) {
+        match self.properties.get_mut("Attributes") {
+            Some(Variant::Attributes(attributes)) => {
+                attributes.insert(name.to_owned(), value);
+            }
+            _ => {
+                let mut attributes = Attributes::new();
+                attributes.insert(name.to_owned(), value);
+                self.properties.insert(String::from("Attributes"), Variant::Attributes(attributes));
+            }
+        }
+    }
 }
 
 pub trait TokenRefExt {
@@ -187,6 +514,8 @@ pub trait TokenRefExt {
     fn with_trivia(&self, leading_whitespace: Option<&str>, trailing_whitespace: Option<&str>) -> TokenReference;
     /// Gets string from TokenReference if contains a identifier or string
     fn identifier(&self) -> Option<&str>;
+    /// Gets the text of a literal argument, a string, number or a part of an interpolated string, see [`nth_arg`]
+    fn literal(&self) -> Option<&str>;
 }
 
 /// Creates new trivia for TokenReference if a string is provided
@@ -222,6 +551,15 @@ impl TokenRefExt for TokenReference {
             _ => None,
         }
     }
+    fn literal(&self) -> Option<&str> {
+        match self.token_type() {
+            TokenType::StringLiteral { literal, .. } => Some(literal.as_str()),
+            TokenType::Number { text } => Some(text.as_str()),
+            #[cfg(feature = "luau")]
+            TokenType::InterpolatedString { literal, .. } => Some(literal.as_str()),
+            _ => None,
+        }
+    }
 }
 
 pub trait AffixExt {
@@ -285,63 +623,3 @@ impl HasAffixes for FunctionCall {
         self.with_suffixes(suffixes)
     }
 }
-
-/// Creates a new identifier by returning an expression
-///
-/// # Arguments
-///
-/// `token_ref` - optional, closest token to newline, can be passed to preserve trivia (newline, whitespace)
-pub fn new_identifier_expression(identifier: &str, token_reference: Option<&TokenReference>) -> Expression {
-    let token_type = TokenType::Identifier {
-        identifier: ShortString::new(identifier),
-    };
-    let token_reference = match token_reference {
-        Some(token_reference) => token_reference.with_token(Token::new(token_type)),
-        None => TokenReference::new_type(token_type),
-    };
-
-    Expression::Symbol(token_reference)
-}
-
-pub fn new_local_assignment(name: &str, expression: Expression) -> LocalAssignment {
-    let mut name_list = Punctuated::new();
-    name_list.push(Pair::End(TokenReference::new_identifier(name)));
-
-    let mut expression_list = Punctuated::new();
-    expression_list.push(Pair::End(expression));
-
-    LocalAssignment::new(name_list)
-        .with_equal_token(Some(
-            TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Equal }).with_trivia(Some(" "), Some(" ")),
-        ))
-        .with_expressions(expression_list)
-}
-
-/// Creates a new local assignment that requires the main plugin source and gets its globals
-///
-/// Example: local _proxyGlobals = require(script.Parent.Parent).Globals
-pub fn new_global_require(depth: usize) -> LocalAssignment {
-    let require_func =
-        FunctionCall::new(Prefix::Name(TokenReference::new_identifier("require"))).with_suffixes(vec![Suffix::Call(
-            Call::AnonymousCall(FunctionArgs::Parentheses {
-                parentheses: ContainedSpan::new(TokenReference::symbol("(").unwrap(), TokenReference::symbol(")").unwrap()),
-                arguments: std::iter::once(Pair::End(new_identifier_expression(
-                    &DotPath::new_ancestor_path(depth).to_string(),
-                    None,
-                )))
-                .collect(),
-            }),
-        )]);
-
-    new_local_assignment(
-        GLOBAL_VAR_NAME,
-        Expression::Var(Var::Expression(Box::new(
-            VarExpression::new(Prefix::Expression(Box::new(Expression::FunctionCall(require_func)))).with_suffixes(vec![
-                Suffix::Index(Index::Dot {
-                    dot: TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Dot }),
-                    name: TokenReference::new_identifier("Globals").with_trivia(None, Some("\n")),
-                }),
-            ]),
-        ))),
-    )
-}
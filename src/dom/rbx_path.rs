@@ -1,5 +1,60 @@
 use std::{fmt, path::PathBuf};
 
+use rbx_dom_weak::{types::Ref, WeakDom};
+
+/// Reserved words that can't be used as identifiers, `continue` of Luau is a valid identifier
+pub(crate) const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local", "nil", "not", "or",
+    "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Checks if the name can be used to index with a dot (script.Name), otherwise it needs brackets or FindFirstChild
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::dom::rbx_path::is_valid_identifier;
+///
+/// assert!(is_valid_identifier("Util_2"));
+/// assert!(!is_valid_identifier("My Module"));
+/// assert!(!is_valid_identifier("2D"));
+/// assert!(!is_valid_identifier("end"));
+/// ```
+pub fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+        && !LUA_KEYWORDS.contains(&name)
+}
+
+/// Quotes the string as a Luau string literal
+pub(crate) fn lua_string(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            char if char.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", char as u32)),
+            char => quoted.push(char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// How components that can't be indexed with a dot are written when rendering a [`DotPath`] as Lua
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexStyle {
+    /// `script.Parent["Weird Name"]`
+    Brackets,
+    /// `script.Parent:FindFirstChild("Weird Name")`, which doesn't collide with properties of the instance
+    FindFirstChild,
+}
+
 #[derive(Clone)]
 pub struct DotPath {
     root: String,
@@ -42,10 +97,58 @@ impl DotPath {
         }
     }
 
+    /// Creates the path that leads from one instance to another, going up through Parent and then down by name
+    ///
+    /// Returns `None` if either instance isn't in the tree
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::dom::rbx_path::DotPath;
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("ModuleScript").with_name("Main"));
+    /// let lib = tree.insert(main, InstanceBuilder::new("Folder").with_name("Lib"));
+    /// let util = tree.insert(lib, InstanceBuilder::new("ModuleScript").with_name("Util"));
+    /// let ui = tree.insert(main, InstanceBuilder::new("ModuleScript").with_name("UI"));
+    ///
+    /// assert_eq!(DotPath::relative_to(&tree, util, ui).unwrap().to_string(), "script.Parent.Parent.UI");
+    /// assert_eq!(DotPath::relative_to(&tree, main, util).unwrap().to_string(), "script.Lib.Util");
+    /// assert_eq!(DotPath::relative_to(&tree, util, util).unwrap().to_string(), "script");
+    /// ```
+    pub fn relative_to(tree: &WeakDom, from: Ref, to: Ref) -> Option<Self> {
+        let mut from_ancestors = Vec::new();
+        let mut current = tree.get_by_ref(from);
+        while let Some(instance) = current {
+            from_ancestors.push(instance.referent());
+            current = tree.get_by_ref(instance.parent());
+        }
+
+        let mut names = Vec::new();
+        let mut current = tree.get_by_ref(to)?;
+        let ups = loop {
+            if let Some(ups) = from_ancestors.iter().position(|ancestor| *ancestor == current.referent()) {
+                break ups;
+            }
+            names.push(current.name.clone());
+            current = tree.get_by_ref(current.parent())?;
+        };
+
+        let mut path = Self::new_ancestor_path(ups);
+        path.components.extend(names.into_iter().rev());
+        Some(path)
+    }
+
     pub fn depth(&self) -> usize {
         self.components.len()
     }
 
+    /// Names of the instances in the path after the root
+    pub fn components(&self) -> &[String] {
+        &self.components
+    }
+
     pub fn push(&mut self, component: &str) {
         self.components.push(component.to_string());
     }
@@ -66,6 +169,36 @@ impl DotPath {
         )
     }
 
+    /// Renders the path as a Lua expression, components that aren't valid identifiers are indexed in the given style
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::dom::rbx_path::{DotPath, IndexStyle};
+    ///
+    /// let mut path = DotPath::new_ancestor_path(1);
+    /// path.push("Weird Name");
+    /// path.push("Child");
+    /// assert_eq!(path.to_lua(IndexStyle::FindFirstChild), r#"script.Parent:FindFirstChild("Weird Name").Child"#);
+    /// assert_eq!(path.to_lua(IndexStyle::Brackets), r#"script.Parent["Weird Name"].Child"#);
+    /// ```
+    pub fn to_lua(&self, style: IndexStyle) -> String {
+        let mut lua = self.root.clone();
+        for component in &self.components {
+            if is_valid_identifier(component) {
+                lua.push('.');
+                lua.push_str(component);
+            } else {
+                let name = lua_string(component);
+                match style {
+                    IndexStyle::Brackets => lua.push_str(&format!("[{name}]")),
+                    IndexStyle::FindFirstChild => lua.push_str(&format!(":FindFirstChild({name})")),
+                }
+            }
+        }
+        lua
+    }
+
     /// Path string in the format of script/Parent/Parent
     pub fn path_string(&self) -> String {
         format!("{}/", self.join("/"))
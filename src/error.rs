@@ -1,29 +1,163 @@
-use std::io;
-use std::path::PathBuf;
+use std::{error::Error as _, fmt, io, path::PathBuf};
 use thiserror::Error;
 
+/// What was being done to a script when it failed, see [`Problem::Script`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Parsing,
+    Transpiling,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Parsing => "parsing",
+            Phase::Transpiling => "transpiling",
+        })
+    }
+}
+
+/// Inner errors are kept as the [`std::error::Error::source`] of a problem rather than in its message,
+/// use [`Problem::report`] to get the message with every cause
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Problem {
     #[error("Could not find the parent directory for file")]
     InvalidPath,
     #[error("User did not choose a file")]
     RFDCancel,
-    #[error("While attempting to {0}, {1}")]
-    IOError(&'static str, io::Error),
-    #[error("While attempting to decode the place file, at {0} rbx_binary didn't know what to do")]
-    BinaryDecodeError(rbx_binary::DecodeError),
-    #[error("While attempting to decode the place file, at {0} rbx_xml didn't know what to do")]
-    XMLDecodeError(rbx_xml::DecodeError),
-    #[error("While attempting to decode the place file, at {0} rbx_binary didn't know what to do")]
-    BinaryEncodeError(rbx_binary::EncodeError),
-    #[error("While attempting to decode the place file, at {0} rbx_xml didn't know what to do")]
-    XMLEncodeError(rbx_xml::EncodeError),
+    #[error("While attempting to {0}")]
+    IOError(&'static str, #[source] io::Error),
+    #[error("While attempting to decode the place file, rbx_binary didn't know what to do")]
+    BinaryDecodeError(#[source] rbx_binary::DecodeError),
+    #[error("While attempting to decode the place file, rbx_xml didn't know what to do")]
+    XMLDecodeError(#[source] rbx_xml::DecodeError),
+    #[error("While attempting to encode the place file, rbx_binary didn't know what to do")]
+    BinaryEncodeError(#[source] rbx_binary::EncodeError),
+    #[error("While attempting to encode the place file, rbx_xml didn't know what to do")]
+    XMLEncodeError(#[source] rbx_xml::EncodeError),
+    #[error("Couldn't save {}, {1}", .0.display())]
+    SaveAborted(PathBuf, &'static str, #[source] Box<Problem>),
     #[error("File '{}' does not have the correct rbx file extension", .0.file_name().and_then(|name| name.to_str()).unwrap_or("None"))]
     InvalidExtension(PathBuf),
     #[error("While searching through file, no source script was found")]
     NoMainSource,
+    #[error("Found multiple possible main scripts, choose one of: {}", .0.join(", "))]
+    AmbiguousMainSource(Vec<String>),
+    #[error("Could not find main script '{0}'")]
+    MainSourceNotFound(String),
+    #[error("Could not find an instance at '{0}'")]
+    InstanceNotFound(String),
+    #[error("Could not find the Roblox Studio plugins folder, it is only known on Windows and macOS")]
+    PluginsFolderNotFound,
+    #[error("User did not choose a main script")]
+    NoMainSourceSelected,
+    #[error("Can't insert the Globals module, the main script already has a child named '{0}'")]
+    GlobalsModuleConflict(String),
+    #[error("{1} has the name '{0}' of the main script, so modules can't find the Globals module by it, rename one of them")]
+    MainNameShadowed(String, String),
+    #[error("Asset {0} has no ModuleScript to vendor")]
+    AssetWithoutModule(u64),
+    #[error("Couldn't download asset {0}: {1}")]
+    AssetDownload(u64, String),
     #[error("Couldn't find source for script '{0}'")]
     NoScriptSource(String),
-    #[error("While transpiling, {0:?}")]
+    #[error("While attempting to write {0}")]
+    JSONError(&'static str, #[source] serde_json::Error),
+    #[error("{0} checks failed")]
+    ChecksFailed(usize),
+    #[error("Found {0} structural issues in the plugin")]
+    LintIssues(usize),
+    #[error("{0} scripts in the output differ from what the input transpiles to")]
+    OutputMismatch(usize),
+    #[error("Config file '{}' is invalid: {1}", .0.display())]
+    InvalidConfig(PathBuf, String),
+    #[error("Runtime descriptor '{}' is invalid: {1}", .0.display())]
+    InvalidRuntime(PathBuf, String),
+    #[error("{0} doesn't provide what the plugin needs: {}", .1.join(", "))]
+    RuntimeIncompatible(String, Vec<String>),
+    #[error("The plugin sends HTTP requests, allow them with allow_http = true in the config")]
+    HttpNotAllowed,
+    #[error("Profile '{0}' does not exist, available profiles: {}", .1.join(", "))]
+    UnknownProfile(String, Vec<String>),
+    #[error("Rule '{0}' is invalid: {1}")]
+    InvalidRule(String, String),
+    #[error("Couldn't parse the source: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
     TranspilerError(Vec<full_moon::Error>),
+    #[error("While {phase} {path}")]
+    Script {
+        /// Path of the script in the format of script.Parent.Child, `script` is the main script
+        path: String,
+        phase: Phase,
+        #[source]
+        source: Box<Problem>,
+        /// Code frames pointing at where it went wrong, see [`crate::frame`]
+        frame: Option<String>,
+    },
+    #[cfg(feature = "tokio")]
+    #[error("While waiting for a blocking task")]
+    TaskError(#[source] tokio::task::JoinError),
+}
+
+impl Problem {
+    /// Wraps a problem with the script it happened in, the phase is parsing for parse errors and transpiling otherwise
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::error::{Phase, Problem};
+    ///
+    /// let problem = Problem::script("script.Parent.Module", Problem::TranspilerError(Vec::new()));
+    /// assert!(matches!(problem, Problem::Script { phase: Phase::Parsing, .. }));
+    /// assert!(matches!(problem.root(), Problem::TranspilerError(_)));
+    /// assert!(problem.report().starts_with("While parsing script.Parent.Module: "));
+    /// ```
+    pub fn script(path: impl Into<String>, inner: Problem) -> Self {
+        let phase = match inner.root() {
+            Problem::TranspilerError(_) => Phase::Parsing,
+            _ => Phase::Transpiling,
+        };
+        Problem::Script {
+            path: path.into(),
+            phase,
+            source: Box::new(inner),
+            frame: None,
+        }
+    }
+
+    /// Adds code frames to a problem wrapped with [`Problem::script`], other problems are returned as they are
+    pub fn with_frame(mut self, code_frame: Option<String>) -> Self {
+        if let Problem::Script { frame, .. } = &mut self {
+            *frame = code_frame;
+        }
+        self
+    }
+
+    /// Code frames of the outermost script context, if it has any
+    pub fn frame(&self) -> Option<&str> {
+        match self {
+            Problem::Script { frame, .. } => frame.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The problem without the script context around it, for matching on what actually went wrong
+    pub fn root(&self) -> &Problem {
+        match self {
+            Problem::Script { source, .. } => source.root(),
+            problem => problem,
+        }
+    }
+
+    /// The message of the problem followed by every cause in its source chain, separated by colons
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut cause = self.source();
+        while let Some(error) = cause {
+            report.push_str(": ");
+            report.push_str(&error.to_string());
+            cause = error.source();
+        }
+        report
+    }
 }
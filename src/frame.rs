@@ -0,0 +1,82 @@
+//! Code frames that point at a span of a script's source, so errors and diagnostics show the offending code
+
+use full_moon::{node::Node, tokenizer::Position};
+use serde::Serialize;
+
+/// Span of a source on a single line, lines and columns start at 1 and the end column is exclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub start_column: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// Span between two positions, it's cut at the end of the first line when they're on different lines
+    pub fn from_positions(start: Position, end: Position) -> Self {
+        let end_column = if end.line() == start.line() { end.character() } else { usize::MAX };
+        Self {
+            line: start.line(),
+            start_column: start.character(),
+            end_column: end_column.max(start.character() + 1),
+        }
+    }
+
+    /// Span of the node, `None` for generated nodes
+    pub fn of(node: &impl Node) -> Option<Self> {
+        let (start, end) = node.range()?;
+        Some(Self::from_positions(start, end))
+    }
+}
+
+/// Renders the line of the span and the one before it, with the span underlined by carets and labeled
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::frame::{code_frame, Span};
+///
+/// let source = "local a = 1\nlocal b = = 2\n";
+/// let span = Span { line: 2, start_column: 11, end_column: 12 };
+/// assert_eq!(
+///     code_frame(source, "script.Module", span, "expected an expression"),
+///     "  --> script.Module:2:11\n   |\n 1 | local a = 1\n 2 | local b = = 2\n   |           ^ expected an expression"
+/// );
+/// ```
+pub fn code_frame(source: &str, path: &str, span: Span, label: &str) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let width = span.line.to_string().len();
+    let gutter = " ".repeat(width + 2);
+    let mut frame = format!("{}--> {path}:{}:{}\n{gutter}|", " ".repeat(width + 1), span.line, span.start_column);
+
+    let first = span.line.saturating_sub(1).max(1);
+    for number in first..=span.line {
+        let text = lines.get(number - 1).copied().unwrap_or_default();
+        frame.push_str(&format!("\n {number:>width$} | {text}"));
+    }
+
+    // tabs before the span are kept so the carets line up in terminals
+    let text = lines.get(span.line - 1).copied().unwrap_or_default();
+    let padding = text
+        .chars()
+        .take(span.start_column.saturating_sub(1))
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let line_end = text.chars().count() + 1;
+    let carets = span.end_column.min(line_end).saturating_sub(span.start_column).max(1);
+    frame.push_str(&format!("\n{gutter}| {padding}{} {label}", "^".repeat(carets)));
+
+    frame
+}
+
+/// Renders a code frame for every error of a failed parse
+pub fn parse_error_frames(source: &str, path: &str, errors: &[full_moon::Error]) -> String {
+    errors
+        .iter()
+        .map(|error| {
+            let (start, end) = error.range();
+            code_frame(source, path, Span::from_positions(start, end), &error.error_message())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
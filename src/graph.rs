@@ -0,0 +1,389 @@
+//! Which scripts of the plugin require each other, resolved from `require(script...)` expressions
+//!
+//! # Example
+//!
+//! ```ignore
+//! let graph = RequireGraph::build(transpiler.tree(), transpiler.source_script());
+//! for (script, required) in graph.edges() {
+//!     println!("{} requires {}", script.path, required.path);
+//! }
+//! println!("{}", graph.render(GraphFormat::Mermaid));
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use full_moon::{ast::*, visitors::Visitor};
+use rbx_dom_weak::{types::Ref, WeakDom};
+
+use crate::{
+    dom::{
+        extension::{nth_arg_string, AffixExt, ForEachAction, InstanceExt, TokenRefExt, WeakDomExt},
+        rbx_path::DotPath,
+    },
+    is_script_class,
+};
+
+/// A script in the graph, with the scripts it requires
+#[derive(Clone, Debug)]
+pub struct RequireNode {
+    pub referent: Ref,
+    /// Path of the script in the format of script.Parent.Child, `script` is the main script
+    pub path: String,
+    pub is_module: bool,
+    /// Indices of the required scripts in [`RequireGraph::nodes`]
+    pub requires: Vec<usize>,
+    /// Requires that couldn't be resolved to a script of the plugin, as written in the source
+    pub unresolved: Vec<String>,
+}
+
+/// Format for rendering a [`RequireGraph`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    Mermaid,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!("unknown graph format '{format}', expected dot or mermaid")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RequireGraph {
+    /// The main script is always the first node
+    nodes: Vec<RequireNode>,
+    indices: HashMap<Ref, usize>,
+}
+
+impl RequireGraph {
+    /// Builds the graph from the sources of the main script and all its descendant scripts
+    pub fn build(tree: &WeakDom, main: Ref) -> Self {
+        let mut graph = Self {
+            nodes: Vec::new(),
+            indices: HashMap::new(),
+        };
+
+        let Some(main_instance) = tree.get_by_ref(main) else {
+            return graph;
+        };
+        graph.add_node(main, String::from("script"), main_instance.class == "ModuleScript");
+        tree.foreach_descendant(
+            main_instance,
+            &mut |child, path| {
+                if is_script_class(&child.class) {
+                    graph.add_node(child.referent(), path.to_string(), child.class == "ModuleScript");
+                }
+                ForEachAction::Continue
+            },
+            0,
+        );
+
+        for index in 0..graph.nodes.len() {
+            let referent = graph.nodes[index].referent;
+            let Some(source) = tree.get_by_ref(referent).and_then(|script| script.source()) else {
+                continue;
+            };
+
+            let Ok(ast) = full_moon::parse(source) else {
+                graph.nodes[index].unresolved.push(String::from("<source failed to parse>"));
+                continue;
+            };
+
+            let mut collector = RequireCollector::default();
+            collector.visit_ast(&ast);
+            for require in collector.requires {
+                let required = require
+                    .as_ref()
+                    .ok()
+                    .and_then(|path| resolve(tree, referent, path))
+                    .and_then(|required| graph.indices.get(&required).copied());
+
+                match (required, require) {
+                    (Some(required), _) if graph.nodes[index].requires.contains(&required) => {}
+                    (Some(required), _) => graph.nodes[index].requires.push(required),
+                    (None, Ok(path)) => graph.nodes[index].unresolved.push(path.to_string()),
+                    (None, Err(expression)) => graph.nodes[index].unresolved.push(expression),
+                }
+            }
+        }
+
+        graph
+    }
+
+    fn add_node(&mut self, referent: Ref, path: String, is_module: bool) {
+        self.indices.insert(referent, self.nodes.len());
+        self.nodes.push(RequireNode {
+            referent,
+            path,
+            is_module,
+            requires: Vec::new(),
+            unresolved: Vec::new(),
+        });
+    }
+
+    /// All scripts in the graph, the main script first and its descendants in the order they're found
+    pub fn nodes(&self) -> &[RequireNode] {
+        &self.nodes
+    }
+
+    /// Gets the node of a script by its referent
+    pub fn get(&self, referent: Ref) -> Option<&RequireNode> {
+        self.indices.get(&referent).map(|index| &self.nodes[*index])
+    }
+
+    /// Gets the node of a script by its path, in the format of script.Parent.Child
+    pub fn find(&self, path: &str) -> Option<&RequireNode> {
+        self.nodes.iter().find(|node| node.path == path)
+    }
+
+    /// Scripts that are required by the script
+    pub fn requires<'a>(&'a self, node: &'a RequireNode) -> impl Iterator<Item = &'a RequireNode> {
+        node.requires.iter().map(|index| &self.nodes[*index])
+    }
+
+    /// Scripts that require the script
+    pub fn required_by<'a>(&'a self, node: &'a RequireNode) -> impl Iterator<Item = &'a RequireNode> {
+        let index = self.indices.get(&node.referent).copied();
+        self.nodes.iter().filter(move |other| index.is_some_and(|index| other.requires.contains(&index)))
+    }
+
+    /// Every require between scripts, as pairs of the requiring and the required script
+    pub fn edges(&self) -> impl Iterator<Item = (&RequireNode, &RequireNode)> {
+        self.nodes.iter().flat_map(|node| self.requires(node).map(move |required| (node, required)))
+    }
+
+    /// Finds scripts that end up requiring themselves, every cycle starts and ends with the same script
+    pub fn cycles(&self) -> Vec<Vec<&RequireNode>> {
+        let mut states = vec![VisitState::Unvisited; self.nodes.len()];
+        let mut cycles = Vec::new();
+        // the scripts being visited, with how many of their requires were followed, deep require chains
+        // can't overflow the call stack this way
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+
+        for start in 0..self.nodes.len() {
+            if states[start] != VisitState::Unvisited {
+                continue;
+            }
+            states[start] = VisitState::OnStack;
+            stack.push((start, 0));
+
+            while let Some((index, next)) = stack.last_mut() {
+                let Some(&required) = self.nodes[*index].requires.get(*next) else {
+                    states[*index] = VisitState::Done;
+                    stack.pop();
+                    continue;
+                };
+                *next += 1;
+
+                match states[required] {
+                    VisitState::Unvisited => {
+                        states[required] = VisitState::OnStack;
+                        stack.push((required, 0));
+                    }
+                    VisitState::OnStack => {
+                        let position = stack.iter().position(|(other, _)| *other == required).unwrap();
+                        let cycle = stack[position..].iter().map(|(other, _)| *other).chain(Some(required));
+                        cycles.push(cycle.map(|other| &self.nodes[other]).collect());
+                    }
+                    VisitState::Done => {}
+                }
+            }
+        }
+
+        cycles
+    }
+
+    /// Renders the graph, unresolved requires are shown as dashed edges to their expression
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let quote = |label: &str| format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut out = String::from("digraph requires {\n");
+
+        for node in &self.nodes {
+            let shape = if node.is_module { "box" } else { "ellipse" };
+            writeln!(out, "    {} [shape={shape}];", quote(&node.path)).unwrap();
+        }
+        for (node, required) in self.edges() {
+            writeln!(out, "    {} -> {};", quote(&node.path), quote(&required.path)).unwrap();
+        }
+        for node in &self.nodes {
+            for require in &node.unresolved {
+                writeln!(out, "    {} -> {} [style=dashed];", quote(&node.path), quote(require)).unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_mermaid(&self) -> String {
+        let quote = |label: &str| format!("\"{}\"", label.replace('"', "#quot;"));
+        let mut out = String::from("graph LR\n");
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let label = quote(&node.path);
+            if node.is_module {
+                writeln!(out, "    n{index}[{label}]").unwrap();
+            } else {
+                writeln!(out, "    n{index}([{label}])").unwrap();
+            }
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            for required in &node.requires {
+                writeln!(out, "    n{index} --> n{required}").unwrap();
+            }
+            for (unresolved, require) in node.unresolved.iter().enumerate() {
+                writeln!(out, "    n{index} -.-> u{index}_{unresolved}[{}]", quote(require)).unwrap();
+            }
+        }
+
+        out
+    }
+
+    /// Marks the scripts that run on their own or are required by one, by index.
+    /// The main script and descendant Scripts and LocalScripts run on their own
+    fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = (0..self.nodes.len()).filter(|index| *index == 0 || !self.nodes[*index].is_module).collect();
+
+        while let Some(index) = stack.pop() {
+            if !reachable[index] {
+                reachable[index] = true;
+                stack.extend(&self.nodes[index].requires);
+            }
+        }
+
+        reachable
+    }
+
+    /// Requires of reachable scripts that couldn't be resolved, as pairs of the script path and the require
+    pub(crate) fn reachable_unresolved(&self) -> Vec<(&str, &str)> {
+        let reachable = self.reachable();
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| reachable[*index])
+            .flat_map(|(_, node)| node.unresolved.iter().map(|require| (node.path.as_str(), require.as_str())))
+            .collect()
+    }
+
+    /// ModuleScripts that are never required from the main script, directly or indirectly,
+    /// excluding ones that contain a reachable script, as pairs of the referent and path
+    pub(crate) fn unreachable_modules(&self, tree: &WeakDom) -> Vec<(Ref, &str)> {
+        let reachable = self.reachable();
+
+        let mut protected = HashSet::new();
+        for node in self.nodes.iter().enumerate().filter(|(index, _)| reachable[*index]).map(|(_, node)| node) {
+            let mut current = tree.get_by_ref(node.referent);
+            while let Some(instance) = current {
+                if !protected.insert(instance.referent()) {
+                    break;
+                }
+                current = tree.get_by_ref(instance.parent());
+            }
+        }
+
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(index, node)| node.is_module && !reachable[*index] && !protected.contains(&node.referent))
+            .map(|(_, node)| (node.referent, node.path.as_str()))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VisitState {
+    Unvisited,
+    OnStack,
+    Done,
+}
+
+/// Collects the argument of every `require` call, as a path relative to the script if it is one
+#[derive(Default)]
+struct RequireCollector {
+    requires: Vec<Result<DotPath, String>>,
+}
+
+impl Visitor for RequireCollector {
+    fn visit_function_call(&mut self, node: &FunctionCall) {
+        if node.prefix().identifier() != Some("require") {
+            return;
+        }
+
+        if let Some(Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses { arguments, .. }))) = node.suffixes().next() {
+            if let Some(argument) = arguments.iter().next() {
+                self.requires.push(script_path(argument).ok_or_else(|| argument.to_string().trim().to_string()));
+            }
+        }
+    }
+}
+
+/// Gets the path of an expression like `script.Parent.Util` or `script:WaitForChild("Util")`
+fn script_path(expression: &Expression) -> Option<DotPath> {
+    match expression {
+        Expression::Var(Var::Name(name)) if name.identifier() == Some("script") => Some(DotPath::default()),
+        Expression::Var(Var::Expression(var)) => affixes_path(var.prefix(), var.suffixes()),
+        Expression::FunctionCall(call) => affixes_path(call.prefix(), call.suffixes()),
+        Expression::Parentheses { expression, .. } => script_path(expression),
+        _ => None,
+    }
+}
+
+pub(crate) fn affixes_path<'a>(prefix: &Prefix, suffixes: impl Iterator<Item = &'a Suffix>) -> Option<DotPath> {
+    if prefix.identifier() != Some("script") {
+        return None;
+    }
+
+    let mut path = DotPath::default();
+    for suffix in suffixes {
+        match suffix {
+            Suffix::Index(Index::Dot { name, .. }) => path.push(name.identifier()?),
+            Suffix::Index(Index::Brackets {
+                expression: Expression::String(name),
+                ..
+            }) => path.push(name.identifier()?),
+            Suffix::Call(Call::MethodCall(method_call))
+                if matches!(method_call.name().identifier(), Some("WaitForChild" | "FindFirstChild")) =>
+            {
+                path.push(nth_arg_string(method_call.args(), 0)?)
+            }
+            _ => return None,
+        }
+    }
+
+    Some(path)
+}
+
+/// Finds the instance a path leads to from the script, children take precedence over `Parent`
+pub(crate) fn resolve(tree: &WeakDom, script: Ref, path: &DotPath) -> Option<Ref> {
+    let mut current = tree.get_by_ref(script)?;
+
+    for name in path.components() {
+        let child = current.children().iter().find_map(|child| tree.get_by_ref(*child).filter(|child| child.name == *name));
+        current = match child {
+            Some(child) => child,
+            None if name == "Parent" => tree.get_by_ref(current.parent())?,
+            None => return None,
+        };
+    }
+
+    Some(current.referent())
+}
@@ -0,0 +1,172 @@
+//! Name and version of a plugin, read from a metadata module it contains, and its stable identity
+
+use full_moon::{
+    ast::{Expression, Field, LastStmt, Stmt, Var},
+    tokenizer::{StringLiteralQuoteType, TokenReference, TokenType},
+};
+use rbx_dom_weak::{types::Ref, WeakDom};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::dom::extension::{InstanceExt, TokenRefExt};
+
+/// Names of modules under the main script that can hold the name and version of the plugin, compared case-insensitively
+pub const METADATA_MODULE_NAMES: &[&str] = &["config", "configuration", "metadata", "package", "package.json", "plugininfo", "info", "version"];
+
+/// What a plugin says about itself, exposed next to `init` in the main script's wrapper so hosts can show what they loaded
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PluginInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+impl PluginInfo {
+    /// Reads the first metadata module under the main script that returns a table with a string `name` or `version`,
+    /// see [`METADATA_MODULE_NAMES`]
+    pub fn find(tree: &WeakDom, main: Ref) -> Option<Self> {
+        tree.get_by_ref(main)?
+            .children()
+            .iter()
+            .filter_map(|child| tree.get_by_ref(*child))
+            .filter(|child| child.class == "ModuleScript" && METADATA_MODULE_NAMES.contains(&child.name.to_lowercase().as_str()))
+            .find_map(|module| Self::from_source(module.source()?))
+    }
+
+    /// Reads the string `name` and `version` fields of the table returned by the source, keys are case-insensitive
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::info::PluginInfo;
+    ///
+    /// let info = PluginInfo::from_source(r#"local Config = { Name = "Builder", Version = "1.2.0" } return Config"#).unwrap();
+    /// assert_eq!(info.name.as_deref(), Some("Builder"));
+    /// assert_eq!(info.version.as_deref(), Some("1.2.0"));
+    ///
+    /// // escapes are resolved, the value is escaped once more where it's emitted
+    /// let info = PluginInfo::from_source(r#"return { name = "Bob's "Tools"" }"#).unwrap();
+    /// assert_eq!(info.name.as_deref(), Some(r#"Bob's "Tools""#));
+    /// ```
+    pub fn from_source(source: &str) -> Option<Self> {
+        let ast = full_moon::parse(source).ok()?;
+        let block = ast.nodes();
+        let Some(LastStmt::Return(returned)) = block.last_stmt() else {
+            return None;
+        };
+
+        let table = match returned.returns().iter().next()? {
+            Expression::TableConstructor(table) => table,
+            // `local Config = {...} return Config`
+            Expression::Var(Var::Name(name)) => block.stmts().find_map(|stmt| match stmt {
+                Stmt::LocalAssignment(assignment)
+                    if assignment.names().iter().next().and_then(|local| local.identifier()) == name.identifier() =>
+                {
+                    match assignment.expressions().iter().next()? {
+                        Expression::TableConstructor(table) => Some(table),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })?,
+            _ => return None,
+        };
+
+        let mut info = PluginInfo::default();
+        for field in table.fields() {
+            let Field::NameKey {
+                key,
+                value: Expression::String(value),
+                ..
+            } = field
+            else {
+                continue;
+            };
+            let value = string_value(value);
+            match key.identifier().map(str::to_lowercase).as_deref() {
+                Some("name") => info.name = value,
+                Some("version") => info.version = value,
+                _ => {}
+            }
+        }
+
+        (info.name.is_some() || info.version.is_some()).then_some(info)
+    }
+}
+
+/// Value of a string literal with its escapes resolved, so it's escaped once when emitted as Lua again
+fn string_value(token: &TokenReference) -> Option<String> {
+    let TokenType::StringLiteral { literal, quote_type, .. } = token.token_type() else {
+        return None;
+    };
+    if *quote_type == StringLiteralQuoteType::Brackets {
+        return Some(literal.to_string());
+    }
+
+    let mut value = String::with_capacity(literal.len());
+    let mut chars = literal.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            value.push(char);
+            continue;
+        }
+        match chars.next()? {
+            'n' | '\n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            'a' => value.push('\x07'),
+            'b' => value.push('\x08'),
+            'f' => value.push('\x0c'),
+            'v' => value.push('\x0b'),
+            'z' => {
+                while chars.next_if(|char| char.is_whitespace()).is_some() {}
+            }
+            'x' => {
+                let hex: String = (0..2).filter_map(|_| chars.next()).collect();
+                value.push(char::from(u8::from_str_radix(&hex, 16).ok()?));
+            }
+            'u' => {
+                chars.next_if_eq(&'{')?;
+                let hex: String = std::iter::from_fn(|| chars.next_if(|char| *char != '}')).collect();
+                chars.next_if_eq(&'}')?;
+                value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            digit if digit.is_ascii_digit() => {
+                let mut code = digit.to_digit(10)?;
+                for _ in 0..2 {
+                    match chars.next_if(char::is_ascii_digit) {
+                        Some(digit) => code = code * 10 + digit.to_digit(10)?,
+                        None => break,
+                    }
+                }
+                value.push(char::from(u8::try_from(code).ok()?));
+            }
+            // \\, \" and \' stand for themselves
+            escaped => value.push(escaped),
+        }
+    }
+    Some(value)
+}
+
+/// GUID identifying a plugin, derived from the SHA-256 of its original file so transpiling the same file again
+/// gives the same id, see [`crate::DomTranspiler::plugin_id`]
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::info::plugin_id_from_contents;
+///
+/// let id = plugin_id_from_contents(b"<roblox!");
+/// assert_eq!(id, plugin_id_from_contents(b"<roblox!"));
+/// assert_ne!(id, plugin_id_from_contents(b"<roblox "));
+/// assert_eq!(id.len(), 36);
+/// assert_eq!(&id[14..15], "8");
+/// ```
+pub fn plugin_id_from_contents(contents: &[u8]) -> String {
+    let mut bytes: [u8; 16] = Sha256::digest(contents)[..16].try_into().unwrap();
+    // version 8 and the RFC 4122 variant, the layout of a custom UUID
+    bytes[6] = (bytes[6] & 0x0f) | 0x80;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
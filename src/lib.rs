@@ -1,43 +1,97 @@
 use std::{
     borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fs::{self, File},
-    io::{BufReader, BufWriter},
+    io::{BufReader, BufWriter, Read, Write},
+    ops::Range,
     path::Path,
     path::PathBuf,
+    sync::Arc,
 };
 
 use full_moon::{
     ast::*,
     node::Node,
-    tokenizer::{Symbol, Token, TokenReference, TokenType},
-    visitors::VisitorMut,
+    tokenizer::{StringLiteralQuoteType, Symbol, Token, TokenReference, TokenType},
+    visitors::{Visitor, VisitorMut},
     ShortString,
 };
 use glob_match::glob_match;
-use log::info;
 use punctuated::Pair;
 use punctuated::Punctuated;
 use rbx_dom_weak::{
-    types::{Ref, Variant},
-    Instance, WeakDom,
+    types::{Attributes, Ref, Variant},
+    Instance, InstanceBuilder, WeakDom,
 };
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use span::ContainedSpan;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, debug_span, info, trace, trace_span, warn};
 
 mod trivia;
-use trivia::{FormatTriviaType, UpdateTrailingTrivia};
+use trivia::{strip_trivia, FormatTriviaType, UpdateLeadingTrivia, UpdateTrailingTrivia};
+
+pub mod audit;
+
+pub mod capabilities;
+use capabilities::{CapabilityEmbed, CapabilityManifest};
+
+pub mod census;
+use census::Census;
+
+pub mod codegen;
+use codegen::{new_dot_index, new_function_call, new_method_call, new_string_literal};
 
 pub mod dom;
 use dom::extension::*;
+use dom::rbx_path::{lua_string, DotPath, IndexStyle, LUA_KEYWORDS};
 
 pub mod error;
 use error::Problem;
 
+pub mod frame;
+use frame::{code_frame, parse_error_frames, Span};
+
+pub mod graph;
+use graph::RequireGraph;
+
+pub mod info;
+use info::PluginInfo;
+
+pub mod lint;
+
+mod local_scripts;
+
+pub mod report;
+use report::{
+    DanglingReference, Diagnostic, Manifest, Metrics, ReportFormat, Requirements, ScriptChecksum, ScriptFailure, ScriptMetrics,
+    ScriptSnapshot, SourceMap, TranspileReport,
+};
+
+pub mod rules;
+use rules::{Chain, ChainRewrite, Rule, RuleRegistry};
+
+pub mod runtime;
+
+pub mod vendor;
+use vendor::required_asset;
+
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "bench")]
+pub mod bench;
+
 #[derive(Default)]
 struct Requires {
     globals: bool,
     plugin: bool,
     enums: bool,
+    signals: bool,
 }
 
 impl Requires {
@@ -45,283 +99,3850 @@ impl Requires {
     pub fn globals(&self) -> bool {
         self.globals || (self.plugin || self.enums)
     }
-}
 
-#[derive(Default)]
-struct PluginProxyVisitor {
-    requires: Requires,
-}
+    fn merge(&mut self, requirements: Requirements) {
+        self.globals |= requirements.globals;
+        self.plugin |= requirements.plugin;
+        self.enums |= requirements.enums;
+        self.signals |= requirements.signals;
+    }
+}
+
+impl From<&Requires> for Requirements {
+    fn from(requires: &Requires) -> Self {
+        Self {
+            globals: requires.globals(),
+            plugin: requires.plugin,
+            enums: requires.enums,
+            signals: requires.signals,
+        }
+    }
+}
+
+struct PluginProxyVisitor {
+    /// Options of the script being visited, see [`PluginProxyVisitor::reset`]
+    options: TranspileOptions,
+    requires: Requires,
+    /// Amount of rewrites done by each rule
+    applied: BTreeMap<&'static str, usize>,
+    diagnostics: Vec<Diagnostic>,
+    /// Start lines of the original statements in visiting order, only recorded for source maps
+    statement_lines: Option<Vec<usize>>,
+    /// Amount of statements that were generated between the original ones
+    generated_statements: usize,
+    /// Amount of original statements before the generated ones, see [`TranspileOptions::place_after_requires`]
+    generated_position: usize,
+    /// What the locals in scope hold, by scope from the outermost one and by name, so using a local holding a service
+    /// is handled like using the service. Locals holding anything else are `None`, they shadow outer ones
+    locals: Vec<HashMap<String, Option<Held>>>,
+    /// Locals of the local assignments being visited, they're declared once their expressions were visited
+    pending_locals: Vec<Vec<(String, Option<Held>)>>,
+    /// Depth of the expression being visited within a thumbnail URL, so the parts of a URL and the URL itself
+    /// once it's an argument of `_proxyGlobals.assets:Thumbnail` aren't handled again
+    thumbnail_url_depth: usize,
+}
+
+/// Name of the service that the suffix gets, like PluginGuiService for `.PluginGuiService` or `:GetService("PluginGuiService")`
+pub(crate) fn service_name(suffix: &Suffix) -> Option<&str> {
+    match suffix {
+        Suffix::Call(Call::MethodCall(method_call))
+            if matches!(method_call.name().identifier(), Some("GetService" | "FindService")) =>
+        {
+            nth_arg_string(method_call.args(), 0)
+        }
+        suffix => suffix.identifier(),
+    }
+}
+
+/// Name of the service that the expression is, like PluginGuiService for `game.PluginGuiService`
+/// or `game:GetService("PluginGuiService")`
+fn game_service(expression: &Expression) -> Option<&str> {
+    let of_game = |prefix: &Prefix, suffixes: Vec<&Suffix>| match suffixes.as_slice() {
+        [suffix] if prefix.identifier() == Some("game") => service_name(suffix),
+        _ => None,
+    };
+
+    match expression {
+        Expression::Var(Var::Expression(var)) => of_game(var.prefix(), var.suffixes().collect()),
+        Expression::FunctionCall(call) => of_game(call.prefix(), call.suffixes().collect()),
+        Expression::Parentheses { expression, .. } => game_service(expression),
+        _ => None,
+    }
+}
+
+/// Whether the call creates a widget, like `plugin:CreateDockWidgetPluginGui(id, info)`
+fn is_widget_creation(call: &FunctionCall) -> bool {
+    let suffixes = call.suffixes().collect::<Vec<_>>();
+    let rest = match plugin_ancestor_call(suffixes.iter().copied()) {
+        Some(index) => &suffixes[index + 1..],
+        None if call.prefix().identifier() == Some("plugin") => &suffixes[..],
+        None => return false,
+    };
+    match rest {
+        [Suffix::Call(Call::MethodCall(method_call))] => {
+            matches!(method_call.name().identifier(), Some("CreateDockWidgetPluginGui" | "CreateDockWidgetPluginGuiAsync"))
+        }
+        _ => false,
+    }
+}
+
+/// What a local holds that the rules follow
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Held {
+    /// A service of `game`, like Selection
+    Service(String),
+    /// A widget from `plugin:CreateDockWidgetPluginGui`, whose parent is PluginGuiService
+    Widget,
+}
+
+/// Methods that insert assets, by the service or global they're called on, see [`rules::ASSET_INSERTION`]
+pub const ASSET_INSERTIONS: &[(&str, &str)] = &[
+    ("InsertService", "LoadAsset"),
+    ("InsertService", "LoadAssetVersion"),
+    ("InsertService", "LoadPackageAsset"),
+    ("StudioService", "PromptImportFile"),
+    ("StudioService", "PromptImportFiles"),
+    ("plugin", "ImportFbxAnimation"),
+    ("plugin", "ImportFbxRig"),
+    ("plugin", "PromptForExistingAssetId"),
+];
+
+/// Methods that load or preload asset content, by the service they're called on, besides every method of AssetService,
+/// see [`rules::THUMBNAILS`]
+pub const THUMBNAIL_CALLS: &[(&str, &str)] = &[("ContentProvider", "PreloadAsync")];
+
+/// Scheme of the URLs of Roblox's thumbnail pipeline, like `rbxthumb://type=Asset&id=123&w=150&h=150`
+pub const THUMBNAIL_URL_SCHEME: &str = "rbxthumb://";
+
+/// Whether an expression builds a thumbnail URL: a string starting with [`THUMBNAIL_URL_SCHEME`],
+/// a concatenation starting with one or an interpolated string starting with one
+fn is_thumbnail_url(expression: &Expression) -> bool {
+    match expression {
+        Expression::String(token) => {
+            matches!(token.token_type(), TokenType::StringLiteral { literal, .. } if literal.starts_with(THUMBNAIL_URL_SCHEME))
+        }
+        Expression::BinaryOperator { lhs, binop: BinOp::TwoDots(_), .. } => is_thumbnail_url(lhs),
+        #[cfg(feature = "luau")]
+        Expression::InterpolatedString(interpolated) => {
+            let first = interpolated.segments().next().map_or(interpolated.last_string(), |segment| &segment.literal);
+            let literal = match first.token_type() {
+                TokenType::InterpolatedString { literal, .. } => literal.as_str(),
+                _ => "",
+            };
+            literal.starts_with(THUMBNAIL_URL_SCHEME)
+        }
+        _ => false,
+    }
+}
+
+/// Index of the suffix calling `:FindFirstAncestorOfClass("Plugin")` or `:FindFirstAncestorWhichIsA("Plugin")`
+pub(crate) fn plugin_ancestor_call<'a>(mut suffixes: impl Iterator<Item = &'a Suffix>) -> Option<usize> {
+    suffixes.position(|suffix| match suffix {
+        Suffix::Call(Call::MethodCall(method_call)) => {
+            matches!(method_call.name().identifier(), Some("FindFirstAncestorOfClass" | "FindFirstAncestorWhichIsA"))
+                && nth_arg_string(method_call.args(), 0) == Some("Plugin")
+        }
+        _ => false,
+    })
+}
+
+/// Reads of the name or path of the plugin or the script, which differ once the plugin is proxied,
+/// see [`rules::TELEMETRY_KEY`]
+fn identity_read(expression: &Expression) -> Option<&'static str> {
+    let (prefix, suffixes) = match expression {
+        Expression::Var(Var::Expression(var)) => (var.prefix(), var.suffixes().collect::<Vec<_>>()),
+        Expression::FunctionCall(call) => (call.prefix(), call.suffixes().collect()),
+        _ => return None,
+    };
+    // script:FindFirstAncestorOfClass("Plugin").Name reads the plugin too
+    let (owner, rest) = match plugin_ancestor_call(suffixes.iter().copied()) {
+        Some(index) => ("plugin", &suffixes[index + 1..]),
+        None => (prefix.identifier()?, &suffixes[..]),
+    };
+    match (owner, rest) {
+        ("plugin", [name]) if name.identifier() == Some("Name") => Some("plugin.Name"),
+        ("script", [name]) if name.identifier() == Some("Name") => Some("script.Name"),
+        ("plugin" | "script", [Suffix::Call(Call::MethodCall(method_call))])
+            if method_call.name().identifier() == Some("GetFullName") =>
+        {
+            Some(if owner == "plugin" { "plugin:GetFullName()" } else { "script:GetFullName()" })
+        }
+        _ => None,
+    }
+}
+
+/// Reads of the name or path of the plugin or the script that an expression is or is built from,
+/// see [`identity_read`]
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::{rules, DomTranspiler};
+/// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+///
+/// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+/// let source = String::from(
+///     "local AnalyticsService = game:GetService(\"AnalyticsService\")\n\
+///     print(\"loaded \" .. script.Name)\n\
+///     plugin:SetSetting(\"open_\" .. script.Name .. \"_count\", 1)\n\
+///     AnalyticsService:LogCustomEvent(player, string.format(\"%s_opened\", plugin.Name))",
+/// );
+/// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("Script").with_name("Main").with_property("Source", source));
+///
+/// // only keys are reported, not every string mentioning the script
+/// let mut transpiler = DomTranspiler::with_source_script(tree, main).unwrap();
+/// transpiler.transpile_tree().unwrap();
+/// let diagnostics = transpiler.diagnostics();
+/// let keys: Vec<_> = diagnostics.iter().filter(|diagnostic| diagnostic.rule == rules::TELEMETRY_KEY).collect();
+/// assert_eq!(keys.iter().map(|diagnostic| diagnostic.line).collect::<Vec<_>>(), vec![Some(3), Some(4)]);
+/// ```
+fn identity_reads(expression: &Expression) -> Vec<(&Expression, &'static str)> {
+    if let Some(read) = identity_read(expression) {
+        return vec![(expression, read)];
+    }
+    let parts = match expression {
+        Expression::Parentheses { expression, .. } => vec![&**expression],
+        expression => string_parts(expression),
+    };
+    parts.into_iter().flat_map(identity_reads).collect()
+}
+
+/// Direct parts of a string built with `..`, `string.format` or an interpolated string
+fn string_parts(expression: &Expression) -> Vec<&Expression> {
+    match expression {
+        Expression::BinaryOperator { lhs, binop: BinOp::TwoDots(_), rhs } => vec![&**lhs, &**rhs],
+        Expression::FunctionCall(call) if call.prefix().identifier() == Some("string") => {
+            match call.suffixes().collect::<Vec<_>>().as_slice() {
+                [format, Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses { arguments, .. }))]
+                    if format.identifier() == Some("format") =>
+                {
+                    arguments.iter().skip(1).collect()
+                }
+                _ => Vec::new(),
+            }
+        }
+        #[cfg(feature = "luau")]
+        Expression::InterpolatedString(interpolated) => interpolated.segments().map(|segment| &segment.expression).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Why a service got with `game:GetService(name)` isn't proxied, if it isn't one of the allowed services,
+/// see [`TranspileOptions::allowed_services`]
+pub(crate) fn unlisted_service(allowed: &BTreeSet<String>, name: Option<&str>) -> Option<String> {
+    match name {
+        Some(name) if allowed.contains(name) => None,
+        Some(name) => Some(format!("gets {name}, which isn't an allowed service, so the real game provides it")),
+        None => Some(String::from("the service name is built at runtime, so it can't be checked against the allowed services")),
+    }
+}
+
+/// Methods that prompt purchases or check monetization policies, by the service they're called on,
+/// see [`rules::MONETIZATION`]
+pub const MONETIZATION_CALLS: &[(&str, &str)] = &[
+    ("MarketplaceService", "PromptPurchase"),
+    ("MarketplaceService", "PromptProductPurchase"),
+    ("MarketplaceService", "PromptGamePassPurchase"),
+    ("MarketplaceService", "PromptBundlePurchase"),
+    ("MarketplaceService", "PromptPremiumPurchase"),
+    ("MarketplaceService", "PromptSubscriptionPurchase"),
+    ("PolicyService", "GetPolicyInfoForPlayerAsync"),
+];
+
+/// Methods whose argument is a key of stored or reported data, like analytics event names and plugin settings,
+/// by the service they're called on or `plugin` and the position of the key, see [`rules::TELEMETRY_KEY`]
+pub const KEY_SINKS: &[(&str, &str, usize)] = &[
+    ("plugin", "SetSetting", 0),
+    ("plugin", "GetSetting", 0),
+    ("AnalyticsService", "LogCustomEvent", 1),
+    ("AnalyticsService", "LogFunnelStepEvent", 1),
+    ("AnalyticsService", "LogOnboardingFunnelStepEvent", 2),
+    ("AnalyticsService", "LogProgressionEvent", 1),
+    ("AnalyticsService", "LogProgressionStartEvent", 1),
+    ("AnalyticsService", "LogProgressionCompleteEvent", 1),
+    ("AnalyticsService", "LogProgressionFailEvent", 1),
+];
+
+/// Methods of RunService that plugins branch on to decide whether to activate, see [`rules::RUN_CONTEXT`]
+pub const RUN_CONTEXT_METHODS: &[&str] = &["IsEdit", "IsStudio", "IsRunMode", "IsRunning"];
+
+/// Whether a suffix gets a change signal, like `:GetPropertyChangedSignal("Theme")` or `.Changed`
+pub(crate) fn acquires_signal<'a>(mut suffixes: impl Iterator<Item = &'a Suffix>) -> bool {
+    suffixes.any(|suffix| match suffix {
+        Suffix::Call(Call::MethodCall(method_call)) => matches!(
+            method_call.name().identifier(),
+            Some("GetPropertyChangedSignal" | "GetAttributeChangedSignal")
+        ),
+        suffix => matches!(suffix.identifier(), Some("Changed" | "AttributeChanged")),
+    })
+}
+
+/// Key of a plugin setting prefixed with `namespace/`, merged into the literal when the key is a plain string
+/// so `"config"` becomes `"MyPlugin/config"`, otherwise concatenated like `"MyPlugin/" .. key`
+fn namespaced_key(namespace: &str, key: Expression) -> Expression {
+    let prefix = format!("{namespace}/");
+    if let Expression::String(token) = &key {
+        if let TokenType::StringLiteral { literal, quote_type, .. } = token.token_type() {
+            let plain = |text: &str| !text.contains(['\\', '"', '\'', '\n']);
+            if *quote_type != StringLiteralQuoteType::Brackets && plain(&prefix) && !literal.as_str().contains('\\') {
+                return Expression::String(token.with_token(Token::new(TokenType::StringLiteral {
+                    literal: ShortString::new(format!("{prefix}{literal}")),
+                    multi_line_depth: 0,
+                    quote_type: *quote_type,
+                })));
+            }
+        }
+    }
+
+    let key = match key {
+        Expression::Var(_) | Expression::String(_) | Expression::FunctionCall(_) | Expression::Parentheses { .. } => key,
+        key => Expression::Parentheses {
+            contained: ContainedSpan::new(TokenReference::symbol("(").unwrap(), TokenReference::symbol(")").unwrap()),
+            expression: Box::new(key),
+        },
+    };
+    Expression::BinaryOperator {
+        lhs: Box::new(new_string_literal(&prefix)),
+        binop: BinOp::TwoDots(TokenReference::symbol(" .. ").unwrap()),
+        rhs: Box::new(key),
+    }
+}
+
+impl PluginProxyVisitor {
+    fn new(options: &TranspileOptions) -> Self {
+        Self {
+            options: options.clone(),
+            requires: Requires::default(),
+            applied: BTreeMap::new(),
+            diagnostics: Vec::new(),
+            statement_lines: None,
+            generated_statements: 0,
+            generated_position: 0,
+            locals: Vec::new(),
+            pending_locals: Vec::new(),
+            thumbnail_url_depth: 0,
+        }
+    }
+
+    /// Clears what was found in the previous script and takes the options of the next one, so the visitor can be reused
+    /// for it. The rules see the options of the script they're visiting, like its depth and the path rules of it
+    fn reset(&mut self, options: &TranspileOptions) {
+        self.options.clone_from(options);
+        self.requires = Requires::default();
+        self.applied.clear();
+        self.diagnostics.clear();
+        self.generated_statements = 0;
+        self.generated_position = 0;
+        self.locals.clear();
+        self.pending_locals.clear();
+        if let Some(lines) = &mut self.statement_lines {
+            lines.clear();
+        }
+    }
+
+    fn record_line(&mut self, node: &impl Node) {
+        if let Some(lines) = &mut self.statement_lines {
+            lines.push(start_line(node));
+        }
+    }
+
+    fn enabled(&self, rule: &str) -> bool {
+        !self.options.disabled_rules.contains(rule)
+    }
+
+    /// Identifier that indexes a field of the plugin globals, e.g. _proxyGlobals.plugin
+    fn index_global(&self, field: &str) -> String {
+        format!("{}.{field}", self.options.globals_name)
+    }
+
+    fn apply(&mut self, rule: &'static str, node: &impl Node) {
+        *self.applied.entry(rule).or_default() += 1;
+        match node.start_position() {
+            Some(position) => trace!("{rule} rewrote line {}", position.line()),
+            None => trace!("{rule} rewrote generated code"),
+        }
+    }
+
+    /// Reports something found by a rule at the node, the path of the script is filled in later
+    fn diagnose(&mut self, rule: &'static str, node: &impl Node, message: impl Into<String>) {
+        if self.enabled(rule) {
+            self.diagnostics.push(Diagnostic {
+                rule,
+                path: String::new(),
+                line: node.start_position().map(|position| position.line()),
+                span: Span::of(node),
+                message: message.into(),
+                frame: None,
+            });
+        }
+    }
+
+    /// Routes an expression building a thumbnail URL through `_proxyGlobals.assets:Thumbnail(url)`
+    /// with [`TranspileOptions::proxy_thumbnails`], otherwise reports it
+    fn thumbnail_url(&mut self, node: Expression) -> Expression {
+        self.thumbnail_url_depth = 1;
+        if !self.options.proxy_thumbnails || !self.enabled(rules::THUMBNAILS) {
+            self.diagnose(
+                rules::THUMBNAILS,
+                &node,
+                "builds an rbxthumb:// URL, which proxy hosts may serve from their own pipeline, see --proxy-thumbnails",
+            );
+            return node;
+        }
+
+        self.requires.globals = true;
+        self.apply(rules::THUMBNAILS, &node);
+        let leading = leading_trivia(&node);
+        let trailing = node.tokens().last().map(|token| token.trailing_trivia().cloned().collect()).unwrap_or_default();
+        let assets = Expression::Var(Var::Name(TokenReference::new_identifier(&self.index_global("assets"))));
+        let call = new_method_call(assets, "Thumbnail", [strip_trivia(&node)]);
+        Expression::FunctionCall(call)
+            .update_leading_trivia(FormatTriviaType::Replace(leading))
+            .update_trailing_trivia(FormatTriviaType::Replace(trailing))
+    }
+
+    /// Replaces a statement calling a monetization API with an empty `do end` block that keeps its trivia and says
+    /// what was stripped, see [`TranspileOptions::strip_monetization`]
+    fn strip_monetization_call(&mut self, node: Stmt) -> Stmt {
+        let Stmt::FunctionCall(call) = &node else {
+            return node;
+        };
+        let Some(api) = rules::monetization_call(&self.chain(call), &self.options) else {
+            return node;
+        };
+        if !self.options.strip_monetization || !self.enabled(rules::MONETIZATION) {
+            return node;
+        }
+
+        self.apply(rules::MONETIZATION, &node);
+        let trailing = node.tokens().last().map(|token| token.trailing_trivia().cloned().collect()).unwrap_or_default();
+        let comment = vec![
+            Token::new(TokenType::spaces(1)),
+            Token::new(TokenType::MultiLineComment {
+                blocks: 0,
+                comment: ShortString::new(format!(" stripped {api} ")),
+            }),
+            Token::new(TokenType::spaces(1)),
+        ];
+        let do_token = TokenReference::new(leading_trivia(&node), Token::new(TokenType::Symbol { symbol: Symbol::Do }), comment);
+        let end_token = TokenReference::new(Vec::new(), Token::new(TokenType::Symbol { symbol: Symbol::End }), trailing);
+        Stmt::Do(Do::new().with_do_token(do_token).with_end_token(end_token))
+    }
+
+    /// Prefixes the key of `plugin:GetSetting(key)` and `plugin:SetSetting(key, value)` with the namespace,
+    /// see [`TranspileOptions::settings_namespace`]
+    fn namespace_setting_key(&mut self, node: FunctionCall) -> FunctionCall {
+        let Some(namespace) = &self.options.settings_namespace else {
+            return node;
+        };
+        if !self.enabled(rules::SETTINGS_NAMESPACE) || node.prefix().identifier() != Some("plugin") {
+            return node;
+        }
+        let mut suffixes = node.suffixes().cloned().collect::<Vec<_>>();
+        let Some(Suffix::Call(Call::MethodCall(method_call))) = suffixes.first() else {
+            return node;
+        };
+        if !matches!(method_call.name().identifier(), Some("GetSetting" | "SetSetting")) {
+            return node;
+        }
+
+        let args = match method_call.args() {
+            FunctionArgs::Parentheses { parentheses, arguments } if !arguments.is_empty() => {
+                let mut arguments = arguments.clone();
+                if let Some(key) = arguments.iter_mut().next() {
+                    let original = std::mem::replace(key, Expression::Symbol(TokenReference::symbol("nil").unwrap()));
+                    *key = namespaced_key(namespace, original);
+                }
+                FunctionArgs::Parentheses {
+                    parentheses: parentheses.clone(),
+                    arguments,
+                }
+            }
+            // plugin:GetSetting"key"
+            FunctionArgs::String(key) => {
+                let mut arguments = Punctuated::new();
+                arguments.push(Pair::End(namespaced_key(namespace, Expression::String(key.clone()))));
+                FunctionArgs::Parentheses {
+                    parentheses: ContainedSpan::new(TokenReference::symbol("(").unwrap(), TokenReference::symbol(")").unwrap()),
+                    arguments,
+                }
+            }
+            _ => return node,
+        };
+        suffixes[0] = Suffix::Call(Call::MethodCall(method_call.clone().with_args(args)));
+        self.apply(rules::SETTINGS_NAMESPACE, &node);
+        node.with_suffixes(suffixes)
+    }
+
+    /// Reports the key argument of a call like `plugin:SetSetting(key, value)` built from the name or path
+    /// of the plugin or the script, see [`rules::TELEMETRY_KEY`]
+    fn diagnose_identity_key(&mut self, node: &FunctionCall) {
+        let Some((index, position)) = rules::key_sink(&self.chain(node), &self.options) else {
+            return;
+        };
+        let Some(Suffix::Call(Call::MethodCall(method_call))) = node.suffixes().nth(index) else {
+            return;
+        };
+        let FunctionArgs::Parentheses { arguments, .. } = method_call.args() else {
+            return;
+        };
+        let Some(key) = arguments.iter().nth(position) else {
+            return;
+        };
+        for (part, read) in identity_reads(key) {
+            self.diagnose(
+                rules::TELEMETRY_KEY,
+                part,
+                format!("builds a key from {read}, like an analytics key, which differs once the plugin is proxied"),
+            );
+        }
+    }
+
+    /// Reports an assignment target that is a property of `settings()`, it's still rewritten like reads are
+    fn diagnose_settings_write(&mut self, variable: &Var) {
+        if let Var::Expression(var) = variable {
+            let chain = self.chain(&**var);
+            let options = &self.options;
+            let settings = options.rules.get(rules::SETTINGS).filter(|rule| self.enabled(rule.name()));
+            if settings.is_some_and(|rule| rule.rewrite(&chain, options).is_some()) {
+                self.diagnose(
+                    rules::SETTINGS_WRITE,
+                    variable,
+                    "writes a Studio setting, proxy hosts may block setting writes while allowing reads",
+                );
+            }
+        }
+    }
+
+    /// Runs the rules that rewrite the prefix of a node, for reads as well as assignment targets
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::DomTranspiler;
+    ///
+    /// let ast = DomTranspiler::transpile_source("settings().Studio.Theme = theme\nlabel[Enum.UITheme.Dark] = true", 1).unwrap();
+    /// let source = full_moon::print(&ast);
+    /// assert!(source.contains("_proxyGlobals.settings().Studio.Theme = theme"));
+    /// assert!(source.contains("label[Enums.UITheme.Dark] = true"));
+    ///
+    /// let source = "local key = \"open_\" .. script:FindFirstAncestorOfClass(\"Plugin\").Name";
+    /// let ast = DomTranspiler::transpile_source(source, 1).unwrap();
+    /// assert!(full_moon::print(&ast).contains("local key = \"open_\" .. plugin.Name"));
+    ///
+    /// let source = "local colors = {\n\tEnum.StudioStyleGuideColor.MainText,\n}\n\
+    ///     if color == Enum.StudioStyleGuideColor.MainText then\n\
+    ///     \t-- the modifier's number\n\tprint(Enum.StudioStyleGuideModifier.Selected.Value)\nend";
+    /// let ast = DomTranspiler::transpile_source(source, 1).unwrap();
+    /// let source = full_moon::print(&ast);
+    /// assert!(source.contains("{\n\tEnums.StudioStyleGuideColor.MainText,\n}"));
+    /// assert!(source.contains("if color == Enums.StudioStyleGuideColor.MainText then"));
+    /// assert!(source.contains("\t-- the modifier's number\n\tprint(Enums.StudioStyleGuideModifier.Selected.Value)"));
+    ///
+    /// let source = "task.spawn(function()\n\tlocal theme = settings().Studio.Theme\nend)\n\
+    ///     Signal.new():Connect(function(style) return style == Enum.UITheme.Dark end)\n\
+    ///     print(string.format(\"%s\", settings().Studio.Theme))\n\
+    ///     local changed = (settings()).Studio:GetPropertyChangedSignal(\"Theme\")\n\
+    ///     local studio = ((settings())).Studio";
+    /// let ast = DomTranspiler::transpile_source(source, 1).unwrap();
+    /// let source = full_moon::print(&ast);
+    /// assert!(source.contains("\tlocal theme = _proxyGlobals.settings().Studio.Theme\nend)"));
+    /// assert!(source.contains("return style == Enums.UITheme.Dark end)"));
+    /// assert!(source.contains("print(string.format(\"%s\", _proxyGlobals.settings().Studio.Theme))"));
+    /// assert!(source.contains("local changed = _proxyGlobals.settings().Studio:GetPropertyChangedSignal(\"Theme\")"));
+    /// assert!(source.contains("local studio = _proxyGlobals.settings().Studio"));
+    ///
+    /// let source = "(settings()).Studio.Theme = theme\n\
+    ///     local size = (script:FindFirstAncestorOfClass(\"Plugin\")):GetSetting(\"size\")";
+    /// let ast = DomTranspiler::transpile_source(source, 1).unwrap();
+    /// let source = full_moon::print(&ast);
+    /// assert!(source.contains("_proxyGlobals.settings().Studio.Theme = theme"));
+    /// assert!(source.contains("local size = plugin:GetSetting(\"size\")"));
+    /// ```
+    pub fn process_common<T: HasAffixes + Node>(&mut self, node: T) -> T {
+        // rules swap the prefix for a token without trivia, the indentation and comments before it have to stay
+        let leading = leading_trivia(node.prefix());
+        let node = self.dispatch_rules(node);
+        match node.prefix() {
+            Prefix::Name(name) if !leading.is_empty() && name.leading_trivia().next().is_none() => {
+                let name = name.update_leading_trivia(FormatTriviaType::Replace(leading));
+                node.with_prefix(Prefix::Name(name))
+            }
+            _ => node,
+        }
+    }
+
+    /// What the local in scope with the name holds
+    fn held(&self, name: &str) -> Option<&Held> {
+        self.locals.iter().rev().find_map(|scope| scope.get(name)).and_then(Option::as_ref)
+    }
+
+    /// What an expression holds, following the locals holding services and widgets
+    fn holds(&self, expression: &Expression) -> Option<Held> {
+        if let Some(service) = game_service(expression) {
+            return Some(Held::Service(service.to_owned()));
+        }
+        match expression {
+            Expression::Var(Var::Name(name)) => self.held(name.identifier()?).cloned(),
+            // widget.Parent
+            Expression::Var(Var::Expression(var)) => match var.suffixes().collect::<Vec<_>>().as_slice() {
+                [parent] if parent.identifier() == Some("Parent") => {
+                    let name = var.prefix().identifier()?;
+                    (self.held(name) == Some(&Held::Widget)).then(|| Held::Service(String::from("PluginGuiService")))
+                }
+                _ => None,
+            },
+            Expression::FunctionCall(call) if is_widget_creation(call) => Some(Held::Widget),
+            Expression::Parentheses { expression, .. } => self.holds(expression),
+            _ => None,
+        }
+    }
+
+    /// Declares a local in the innermost scope
+    fn declare(&mut self, name: &str, held: Option<Held>) {
+        if let Some(scope) = self.locals.last_mut() {
+            scope.insert(name.to_owned(), held);
+        }
+    }
+
+    /// The proxied PluginGuiService in place of an expression holding it, keeping the trivia of the expression
+    fn proxied_plugin_gui_service(&mut self, expression: &Expression) -> Expression {
+        self.requires.globals = true;
+        self.apply(rules::PLUGIN_GUI_SERVICE, expression);
+        let leading = leading_trivia(expression);
+        let trailing = expression.tokens().last().map(|token| token.trailing_trivia().cloned().collect()).unwrap_or_default();
+        let game = Expression::Var(Var::Name(TokenReference::new_identifier(&self.index_global("game"))));
+        new_dot_index(game, "PluginGuiService")
+            .update_leading_trivia(FormatTriviaType::Replace(leading))
+            .update_trailing_trivia(FormatTriviaType::Replace(trailing))
+    }
+
+    /// Chain of a node, with the service held by the local it starts at
+    fn chain<'n, T: HasAffixes>(&'n self, node: &'n T) -> Chain<'n> {
+        let mut chain = Chain::of(node);
+        chain.local_service = match chain.name().and_then(|name| self.held(name)) {
+            Some(Held::Service(service)) => Some(service.as_str()),
+            _ => None,
+        };
+        chain
+    }
+
+    /// Tries the rules of the registry in order, the first enabled one matching the chain rewrites it.
+    /// Enabled rules after it that match the chain too are suppressed, see [`rules::RULE_CONFLICT`]
+    fn dispatch_rules<T: HasAffixes + Node>(&mut self, node: T) -> T {
+        let options = &self.options;
+        let mut diagnostics = Vec::new();
+        let mut matched: Option<(&'static str, ChainRewrite)> = None;
+        let mut suffixes = Vec::new();
+        {
+            let chain = self.chain(&node);
+            for rule in options.rules.iter().filter(|rule| self.enabled(rule.name())) {
+                if let Some(message) = rule.diagnostic(&chain, options) {
+                    diagnostics.push((rule.name(), message));
+                }
+                let Some(rewrite) = rule.rewrite(&chain, options) else {
+                    continue;
+                };
+                match &matched {
+                    // the built-in rules are registered from the most specific to the most general
+                    Some((winner, _)) if rules::ALL.contains(winner) && rules::ALL.contains(&rule.name()) => {}
+                    Some((winner, _)) => diagnostics.push((
+                        rules::RULE_CONFLICT,
+                        format!("Rule '{}' matched too but was suppressed by '{winner}', which is tried first", rule.name()),
+                    )),
+                    None => {
+                        // the chain may continue a chain in parentheses, so the suffixes are taken from it
+                        suffixes = chain.suffixes.iter().skip(rewrite.keep_from).map(|suffix| (*suffix).clone()).collect();
+                        matched = Some((rule.name(), rewrite));
+                    }
+                }
+            }
+        }
+        for (rule, message) in diagnostics {
+            self.diagnose(rule, &node, message);
+        }
+
+        let Some((rule, rewrite)) = matched else {
+            return node;
+        };
+        self.requires.merge(rewrite.requirements);
+        self.apply(rule, &node);
+        let prefix = Prefix::Name(TokenReference::new_identifier(&rewrite.prefix));
+        node.with_prefix(prefix).with_suffixes(suffixes)
+    }
+}
+
+impl VisitorMut for PluginProxyVisitor {
+    fn visit_stmt(&mut self, node: Stmt) -> Stmt {
+        self.record_line(&node);
+        self.strip_monetization_call(node)
+    }
+
+    fn visit_last_stmt(&mut self, node: LastStmt) -> LastStmt {
+        self.record_line(&node);
+        node
+    }
+
+    fn visit_var_expression(&mut self, node: VarExpression) -> VarExpression {
+        self.process_common(node)
+    }
+
+    fn visit_block(&mut self, node: Block) -> Block {
+        self.locals.push(HashMap::new());
+        node
+    }
+
+    fn visit_block_end(&mut self, node: Block) -> Block {
+        self.locals.pop();
+        node
+    }
+
+    // parameters shadow the locals outside the function, the block of the body is a scope of its own
+    fn visit_function_body(&mut self, node: FunctionBody) -> FunctionBody {
+        let parameters = node.parameters().iter().filter_map(|parameter| match parameter {
+            Parameter::Name(name) => name.identifier(),
+            _ => None,
+        });
+        self.locals.push(parameters.map(|name| (name.to_owned(), None)).collect());
+        node
+    }
+
+    fn visit_function_body_end(&mut self, node: FunctionBody) -> FunctionBody {
+        self.locals.pop();
+        node
+    }
+
+    fn visit_numeric_for(&mut self, node: NumericFor) -> NumericFor {
+        let index = node.index_variable().identifier().map(|name| (name.to_owned(), None));
+        self.locals.push(index.into_iter().collect());
+        node
+    }
+
+    fn visit_numeric_for_end(&mut self, node: NumericFor) -> NumericFor {
+        self.locals.pop();
+        node
+    }
+
+    fn visit_generic_for(&mut self, node: GenericFor) -> GenericFor {
+        let names = node.names().iter().filter_map(|name| name.identifier());
+        self.locals.push(names.map(|name| (name.to_owned(), None)).collect());
+        node
+    }
+
+    fn visit_generic_for_end(&mut self, node: GenericFor) -> GenericFor {
+        self.locals.pop();
+        node
+    }
+
+    fn visit_local_function(&mut self, node: LocalFunction) -> LocalFunction {
+        if let Some(name) = node.name().identifier() {
+            self.declare(name, None);
+        }
+        node
+    }
+
+    // locals and assignments are seen before their expressions are rewritten, the names of locals are declared
+    // after them since the expressions still see the locals they shadow
+    fn visit_local_assignment(&mut self, node: LocalAssignment) -> LocalAssignment {
+        let mut expressions = node.expressions().iter();
+        let locals = node
+            .names()
+            .iter()
+            .map(|name| (name, expressions.next().and_then(|expression| self.holds(expression))))
+            .filter_map(|(name, held)| Some((name.identifier()?.to_owned(), held)))
+            .collect();
+        self.pending_locals.push(locals);
+        node
+    }
+
+    fn visit_local_assignment_end(&mut self, node: LocalAssignment) -> LocalAssignment {
+        for (name, held) in self.pending_locals.pop().unwrap_or_default() {
+            self.declare(&name, held);
+        }
+        node
+    }
+
+    fn visit_assignment(&mut self, node: Assignment) -> Assignment {
+        for variable in node.variables() {
+            self.diagnose_settings_write(variable);
+        }
+        let mut expressions = node.expressions().clone();
+        for (variable, expression) in node.variables().iter().zip(expressions.iter_mut()) {
+            let is_parent = match variable {
+                Var::Expression(var) => var.suffixes().last().and_then(|suffix| suffix.identifier()) == Some("Parent"),
+                _ => false,
+            };
+            if !is_parent || self.holds(expression) != Some(Held::Service(String::from("PluginGuiService"))) {
+                continue;
+            }
+            self.diagnose(
+                rules::PLUGIN_GUI_PARENT,
+                variable,
+                "only widgets show in PluginGuiService, parent the UI to a widget from plugin:CreateDockWidgetPluginGui",
+            );
+            // the local may hold the service of the real game, like one from game:FindService("PluginGuiService"),
+            // the UI is parented to the host's container either way
+            if matches!(expression, Expression::Var(Var::Name(_))) && self.enabled(rules::PLUGIN_GUI_SERVICE) {
+                *expression = self.proxied_plugin_gui_service(expression);
+            }
+        }
+        node.with_expressions(expressions)
+    }
+
+    #[cfg(feature = "luau")]
+    fn visit_compound_assignment(&mut self, node: CompoundAssignment) -> CompoundAssignment {
+        self.diagnose_settings_write(node.lhs());
+        node
+    }
+
+    fn visit_function_call(&mut self, node: FunctionCall) -> FunctionCall {
+        if let Some(id) = required_asset(&node) {
+            self.diagnose(
+                rules::ASSET_REQUIRE,
+                &node,
+                format!("requires marketplace asset {id}, which proxy hosts can't load, see --vendor-asset-requires"),
+            );
+        }
+        self.diagnose_identity_key(&node);
+        // the key is namespaced once the prefix is `plugin`, also when an ancestor lookup was rewritten to it
+        let node = self.process_common(node);
+        self.namespace_setting_key(node)
+    }
+
+    // Using visit_expression for functions so one can be replaced with just an identifier
+    fn visit_expression(&mut self, node: Expression) -> Expression {
+        if self.thumbnail_url_depth > 0 {
+            self.thumbnail_url_depth += 1;
+        } else if is_thumbnail_url(&node) {
+            return self.thumbnail_url(node);
+        }
+
+        // replace script:FindFirstAncestorOfClass('Plugin') with plugin global
+        if let Expression::FunctionCall(function_call) = &node {
+            for (index, suf) in function_call.suffixes().enumerate() {
+                // find a MethodCall in suffixes that searches for "Plugin"
+                if let Suffix::Call(Call::MethodCall(method_call)) = suf {
+                    if let Some(name) = method_call.name().identifier() {
+                        // preserve trivia by grabbing last token from parentheses or string
+                        let token_ref = {
+                            match method_call.args() {
+                                FunctionArgs::Parentheses { parentheses, .. } => parentheses.tokens().1,
+                                FunctionArgs::String(token_ref) => token_ref,
+                                _ => &TokenReference::new(Vec::new(), Token::new(TokenType::Eof), Vec::new()),
+                            }
+                        };
+
+                        match name {
+                            "FindFirstAncestorOfClass" | "FindFirstAncestorWhichIsA" if self.enabled(rules::PLUGIN_ANCESTOR) => {
+                                // chained calls like :GetSetting(key) are kept by process_common
+                                let is_last = index + 1 == function_call.suffixes().count();
+                                if is_last && nth_arg_string(method_call.args(), 0).is_some_and(|a| matches!(a, "Plugin")) {
+                                    self.requires.plugin = true;
+                                    self.apply(rules::PLUGIN_ANCESTOR, &node);
+                                    return new_identifier_expression("plugin", Some(token_ref));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        node
+    }
+
+    fn visit_expression_end(&mut self, node: Expression) -> Expression {
+        self.thumbnail_url_depth = self.thumbnail_url_depth.saturating_sub(1);
+        node
+    }
+}
+
+fn indent_string(s: String, indent: &str) -> String {
+    let mut result = String::with_capacity(s.len() + s.lines().count() * indent.len());
+    let mut is_first_line = true;
+    for line in s.lines() {
+        if !is_first_line {
+            result.push('\n');
+        }
+        if !line.is_empty() {
+            result.push_str(indent);
+        }
+        result.push_str(line);
+        is_first_line = false;
+    }
+    result
+}
+
+/// Line a node starts at, 0 for nodes without tokens
+fn start_line(node: &impl Node) -> usize {
+    node.start_position().map_or(0, |position| position.line())
+}
+
+/// Collects the start line of every statement, in the same order as [`PluginProxyVisitor`] visits them
+#[derive(Default)]
+struct StatementLines(Vec<usize>);
+
+impl Visitor for StatementLines {
+    fn visit_stmt(&mut self, node: &Stmt) {
+        self.0.push(start_line(node));
+    }
+
+    fn visit_last_stmt(&mut self, node: &LastStmt) {
+        self.0.push(start_line(node));
+    }
+}
+
+/// Maps the line of every original statement to its line in the transpiled source, by matching the statements in order
+/// from the `first` one, skipping the `generated` ones. Rules only rewrite expressions, so the statements match up
+/// unless something went wrong
+fn map_lines(
+    original_lines: &[usize],
+    transpiled: &str,
+    first: usize,
+    generated: Range<usize>,
+) -> Option<BTreeMap<usize, usize>> {
+    let mut collector = StatementLines::default();
+    collector.visit_ast(&full_moon::parse(transpiled).ok()?);
+
+    let mut statements = collector.0;
+    if generated.end > statements.len() {
+        return None;
+    }
+    statements.drain(generated);
+    // generated code like the reload hooks of the main script can follow the original statements
+    let transpiled_lines = statements.get(first..first + original_lines.len())?;
+
+    let mut lines = BTreeMap::new();
+    for (original, transpiled) in original_lines.iter().zip(transpiled_lines) {
+        lines.entry(*original).or_insert(*transpiled);
+    }
+    Some(lines)
+}
+
+/// Length of a line that makes a script look minified when it holds nearly all of the source, see [`is_minified`]
+pub const MINIFIED_LINE_LENGTH: usize = 2000;
+
+/// Identifiers minifiers and obfuscators generate, like `a`, `_0x1f3a` or `IlIlIl`
+fn is_meaningless_identifier(identifier: &str) -> bool {
+    identifier.len() <= 2
+        || identifier.starts_with("_0x")
+        || (identifier.len() >= 4 && identifier.chars().all(|c| matches!(c, 'I' | 'l' | '1' | '_')))
+}
+
+/// Whether a source looks minified or obfuscated: a line of at least [`MINIFIED_LINE_LENGTH`] holding nearly all
+/// of it, or mostly meaningless identifiers. It's a heuristic, flagged scripts skip the cosmetic options
+/// and are reported for review, see [`rules::MINIFIED`]
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::is_minified;
+///
+/// assert!(!is_minified("local toolbar = plugin:CreateToolbar(\"Tools\")\nprint(toolbar)"));
+/// let minified = (0..200).map(|i| format!("local a{i}=b{i}(c,d)")).collect::<Vec<_>>().join(" ");
+/// assert!(is_minified(&minified));
+/// ```
+pub fn is_minified(source: &str) -> bool {
+    let longest = source.lines().map(str::len).max().unwrap_or(0);
+    if longest >= MINIFIED_LINE_LENGTH && longest * 10 >= source.len() * 9 {
+        return true;
+    }
+
+    let identifiers = source
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && !LUA_KEYWORDS.contains(word))
+        .collect::<HashSet<_>>();
+    let meaningless = identifiers.iter().filter(|identifier| is_meaningless_identifier(identifier)).count();
+    identifiers.len() >= 40 && meaningless * 10 >= identifiers.len() * 6
+}
+
+/// Default wording of the comment marking generated code, see [`TranspileOptions::generated_comment`]
+pub const GENERATED_COMMENT: &str = "Autogenerated with PluginProxy Transpiler";
+
+/// Line of the comment marking generated code, empty without one
+fn generated_comment_line(comment: Option<&str>) -> String {
+    comment.map(|comment| format!("-- {}\n", single_line(comment))).unwrap_or_default()
+}
+
+/// Wording of a comment with its line breaks replaced with spaces, so it can't end the comment early
+pub(crate) fn single_line(comment: &str) -> String {
+    comment.replace(['\r', '\n'], " ")
+}
+
+/// Indentation of generated code, see [`TranspileOptions::indent`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Indent {
+    #[default]
+    Tab,
+    Spaces(usize),
+}
+
+impl Indent {
+    /// One level of the indentation
+    pub fn unit(self) -> String {
+        match self {
+            Indent::Tab => String::from("\t"),
+            Indent::Spaces(spaces) => " ".repeat(spaces),
+        }
+    }
+}
+
+impl std::str::FromStr for Indent {
+    type Err = String;
+
+    fn from_str(indent: &str) -> Result<Self, Self::Err> {
+        match indent.to_ascii_lowercase().as_str() {
+            "tab" | "tabs" => Ok(Indent::Tab),
+            spaces => spaces
+                .parse()
+                .map(Indent::Spaces)
+                .map_err(|_| format!("unknown indentation '{indent}', expected tab or a number of spaces")),
+        }
+    }
+}
+
+/// Either `"tab"` or a number of spaces, as a number or a string
+impl<'de> Deserialize<'de> for Indent {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Spaces(usize),
+            Name(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Spaces(spaces) => Ok(Indent::Spaces(spaces)),
+            Raw::Name(name) => name.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Lines that the wrapper of the main script adds before its code, plus one for every directive,
+/// see [`TranspileOptions::preserve_lines`]
+pub const MAIN_LINE_OFFSET: usize = 1;
+
+/// Luau directives that can be emitted at the top of the wrapped main script, see [`TranspileOptions::directives`]
+pub const DIRECTIVES: &[&str] = &["strict", "nonstrict", "nocheck", "nolint", "native", "optimize"];
+
+/// Prefixes of common comments at the top of scripts to keep above the generated code, Luau directives and selene,
+/// StyLua and provenance comments, see [`TranspileOptions::header_comments`]. None are kept unless they're set
+pub const HEADER_COMMENTS: &[&str] = &["!", "selene:", "stylua:", "upstream:"];
+
+fn leading_trivia(node: &impl Node) -> Vec<Token> {
+    node.tokens().next().map(|token| token.leading_trivia().cloned().collect()).unwrap_or_default()
+}
+
+/// Amount of leading trivia tokens that are header comments and the whitespace around them, 0 without header comments
+fn header_length(trivia: &[Token], prefixes: &[String]) -> usize {
+    let mut length = 0;
+    let mut found = false;
+    for (index, token) in trivia.iter().enumerate() {
+        match token.token_type() {
+            TokenType::SingleLineComment { comment }
+                if prefixes.iter().any(|prefix| comment.trim_start().starts_with(prefix.as_str())) =>
+            {
+                found = true;
+                length = index + 1;
+            }
+            TokenType::Whitespace { .. } if length == index => length = index + 1,
+            TokenType::Whitespace { .. } => {}
+            _ => break,
+        }
+    }
+
+    if found {
+        length
+    } else {
+        0
+    }
+}
+
+/// Moves the header comments at the top of the block in front of the first generated statement,
+/// so they stay the first lines, see [`TranspileOptions::header_comments`]
+fn move_header_comments(block: &mut Block, generated: &mut [(Stmt, Option<TokenReference>)], prefixes: &[String]) {
+    let Some((first_generated, _)) = generated.first_mut() else {
+        return;
+    };
+    let trivia = match (block.stmts().next(), block.last_stmt()) {
+        (Some(statement), _) => leading_trivia(statement),
+        (None, Some(last_statement)) => leading_trivia(last_statement),
+        (None, None) => return,
+    };
+    let length = header_length(&trivia, prefixes);
+    if length == 0 {
+        return;
+    }
+
+    let (header, rest) = trivia.split_at(length);
+    let generated_trivia = header.iter().cloned().chain(leading_trivia(first_generated)).collect();
+    *first_generated = first_generated.update_leading_trivia(FormatTriviaType::Replace(generated_trivia));
+
+    let rest = FormatTriviaType::Replace(rest.to_vec());
+    let mut statements = block.stmts_with_semicolon().cloned().collect::<Vec<_>>();
+    *block = match statements.first_mut() {
+        Some((statement, _)) => {
+            *statement = statement.update_leading_trivia(rest);
+            block.clone().with_stmts(statements)
+        }
+        None => {
+            let last_statement = block
+                .last_stmt_with_semicolon()
+                .map(|(last, semicolon)| (last.update_leading_trivia(rest), semicolon.clone()));
+            block.clone().with_last_stmt(last_statement)
+        }
+    };
+}
+
+/// Amount of `local X = require(...)` statements at the start of the block, stopping before the first one that uses
+/// one of the `generated` names or the globals, or that has a function in it, so each of them is a single statement
+fn leading_requires(block: &Block, generated: &[String], globals_name: &str) -> usize {
+    let mut names = generated.iter().map(String::as_str).collect::<Vec<_>>();
+    names.push(globals_name);
+    let is_require = |expression: &Expression| match expression {
+        Expression::FunctionCall(call) => call.prefix().identifier() == Some("require"),
+        _ => false,
+    };
+
+    block
+        .stmts()
+        .take_while(|statement| {
+            let Stmt::LocalAssignment(local) = statement else {
+                return false;
+            };
+            !local.expressions().is_empty()
+                && local.expressions().iter().all(is_require)
+                && !uses_names(local, &names)
+                && !local.tokens().any(|token| {
+                    matches!(
+                        token.token_type(),
+                        TokenType::Symbol {
+                            symbol: Symbol::Function
+                        }
+                    )
+                })
+        })
+        .count()
+}
+
+/// Whether the node uses any of the names, including generated identifiers that index them like `_proxyGlobals.game`
+fn uses_names(node: &impl Node, names: &[&str]) -> bool {
+    node.tokens().any(|token| {
+        token.identifier().is_some_and(|identifier| {
+            names.iter().any(|name| {
+                identifier
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+        })
+    })
+}
+
+/// Index of the script's own top-level `local name = ...` statement that makes generating a declaration of the name
+/// redundant, `None` if there's none or the name is used before it
+fn existing_declaration(block: &Block, name: &str, redundant: impl Fn(&Expression) -> bool) -> Option<usize> {
+    for (index, statement) in block.stmts().enumerate() {
+        if let Stmt::LocalAssignment(local) = statement {
+            let declares = local.names().len() == 1
+                && local.expressions().len() == 1
+                && local.names().iter().next().and_then(|declared| declared.identifier()) == Some(name);
+            if declares && local.expressions().iter().next().is_some_and(&redundant) {
+                return Some(index);
+            }
+        }
+        if uses_names(statement, &[name]) {
+            return None;
+        }
+    }
+    None
+}
+
+/// Merges the generated locals into one statement in front of the other generated statements.
+/// Its expressions are evaluated before any of the names exist, so the ones indexing the globals variable
+/// index the expression that gets the globals instead
+fn combine_locals(statements: &mut Vec<(Stmt, Option<TokenReference>)>, globals_name: &str) {
+    let locals = statements.iter().filter(|(statement, _)| matches!(statement, Stmt::LocalAssignment(_))).count();
+    if locals < 2 {
+        return;
+    }
+
+    let globals_prefix = format!("{globals_name}.");
+    let mut globals_value: Option<Expression> = None;
+    let mut names = Vec::with_capacity(locals);
+    let mut values = Vec::with_capacity(locals);
+    statements.retain(|(statement, _)| {
+        let Stmt::LocalAssignment(local) = statement else {
+            return true;
+        };
+        for (name, expression) in local.names().iter().zip(local.expressions()) {
+            let name = name.token().to_string();
+            // fields of the globals are generated as one identifier token, like `_proxyGlobals.plugin`
+            let field = match expression {
+                Expression::Symbol(token) => token.identifier().and_then(|identifier| identifier.strip_prefix(&globals_prefix)),
+                _ => None,
+            };
+            let value = match (&globals_value, field) {
+                (Some(globals), Some(field)) => new_dot_index(globals.clone(), field),
+                _ => strip_trivia(expression),
+            };
+            if name == globals_name {
+                globals_value = Some(value.clone());
+            }
+            names.push(name);
+            values.push(value);
+        }
+        false
+    });
+
+    if let Some(last) = values.pop() {
+        let newline = Token::new(TokenType::Whitespace {
+            characters: ShortString::new("\n"),
+        });
+        values.push(last.update_trailing_trivia(FormatTriviaType::Replace(vec![newline])));
+    }
+    let expressions = values;
+    let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+    statements.insert(0, (Stmt::LocalAssignment(new_multiple_local_assignment(&names, expressions)), None));
+}
+
+/// Puts the statements on the first line separated by semicolons, so the statements after them keep their lines
+fn join_on_first_line(statements: &mut [(Stmt, Option<TokenReference>)], comment: Option<&str>) {
+    let whitespace = || {
+        Token::new(TokenType::Whitespace {
+            characters: ShortString::new(" "),
+        })
+    };
+    let last = statements.len() - 1;
+
+    for (index, (statement, semicolon)) in statements.iter_mut().enumerate() {
+        let mut trailing_trivia = vec![whitespace()];
+        if let Some(comment) = comment.filter(|_| index == last).map(single_line) {
+            // enough equal signs that the wording can't close the comment
+            let blocks = (0..).find(|blocks| !comment.contains(&format!("]{}]", "=".repeat(*blocks)))).unwrap_or(0);
+            trailing_trivia.push(Token::new(TokenType::MultiLineComment {
+                blocks,
+                comment: ShortString::new(format!(" {comment} ")),
+            }));
+            trailing_trivia.push(whitespace());
+        }
+
+        *statement = statement.update_trailing_trivia(FormatTriviaType::Replace(Vec::new()));
+        *semicolon = Some(TokenReference::new(
+            Vec::new(),
+            Token::new(TokenType::Symbol {
+                symbol: Symbol::Semicolon,
+            }),
+            trailing_trivia,
+        ));
+    }
+}
+
+/// Lua of the `unload` field of the wrapper, replaces the modules with clones so requiring them runs them again
+fn unload_hook(options: &TranspileOptions) -> String {
+    let mut hook = String::from("function()");
+    if options.unload_cleanup {
+        hook.push_str(CLEANUP_UNLOAD);
+    }
+    if options.memoize_init {
+        hook.push_str("\n\trawset(require(script), \"_exports\", nil)");
+    }
+    if let Some(reload_modules) = &options.reload_modules {
+        hook.push_str(&format!(
+            "\n\tfor _, module in {{ {} }} do\n\t\tlocal fresh = module:Clone()\n\t\tfresh.Parent = module.Parent\n\t\tmodule:Destroy()\n\tend",
+            reload_modules.join(", ")
+        ));
+    }
+    hook.push_str("\nend");
+    hook
+}
+
+/// Lua of the Janitor-like `cleanup` field of the wrapper. Tasks are functions, connections, instances
+/// or objects with a `Destroy` method, and `Connect` and `Once` stand in for `plugin.Unloading`
+const CLEANUP_TABLE: &str = "{\n\ttasks = {},\n\tAdd = function(self, task)\n\t\ttable.insert(self.tasks, task)\n\t\treturn task\n\tend,\n\t\
+    Connect = function(self, handler)\n\t\treturn self:Once(handler)\n\tend,\n\t\
+    Once = function(self, handler)\n\t\ttable.insert(self.tasks, handler)\n\t\treturn {\n\t\t\t\
+    Disconnect = function()\n\t\t\t\tlocal index = table.find(self.tasks, handler)\n\t\t\t\tif index then\n\t\t\t\t\t\
+    table.remove(self.tasks, index)\n\t\t\t\tend\n\t\t\tend,\n\t\t}\n\tend,\n}";
+
+/// Lua that runs the tasks of the cleanup in reverse order, at the start of the `unload` hook
+const CLEANUP_UNLOAD: &str = "\n\tlocal tasks = require(script).cleanup.tasks\n\tfor index = #tasks, 1, -1 do\n\t\t\
+    local task = table.remove(tasks, index)\n\t\tif type(task) == \"function\" then\n\t\t\ttask()\n\t\t\
+    elseif typeof(task) == \"RBXScriptConnection\" then\n\t\t\ttask:Disconnect()\n\t\t\
+    elseif typeof(task) == \"Instance\" or type(task) == \"table\" and task.Destroy then\n\t\t\ttask:Destroy()\n\t\t\
+    end\n\tend";
+
+/// Lua of the metatable of the wrapper, when [`TranspileOptions::memoize_init`] is enabled. `init` runs `_init` once
+/// and keeps what it returned for later calls, until `unload` forgets it
+const MEMOIZED_INIT: &str = "{\n\t__index = function(wrapper, key)\n\t\tif key == \"init\" then\n\t\t\treturn function(...)\n\t\t\t\t\
+    local exports = rawget(wrapper, \"_exports\")\n\t\t\t\tif not exports then\n\t\t\t\t\t\
+    exports = table.pack(wrapper._init(...))\n\t\t\t\t\trawset(wrapper, \"_exports\", exports)\n\t\t\t\tend\n\t\t\t\t\
+    return table.unpack(exports, 1, exports.n)\n\t\t\tend\n\t\tend\n\t\treturn nil\n\tend,\n}";
+
+/// Lua expression of the cleanup in the main script's wrapper, see [`TranspileOptions::unload_cleanup`]
+pub(crate) fn cleanup_expression(options: &TranspileOptions) -> String {
+    let main = match (&options.globals_module, options.path_depth) {
+        (_, 0) => String::from("script"),
+        (Some(main_name), _) => format!("script:FindFirstAncestor({})", lua_string(main_name)),
+        (None, depth) => DotPath::new_ancestor_path(depth).to_string(),
+    };
+    format!("require({main}).cleanup")
+}
+
+/// Lua of the `reload` field of the wrapper, the main module requires itself to get the cached wrapper
+fn reload_hook(globals_name: &str) -> String {
+    format!(
+        "function({globals_name})\n\tlocal wrapper = require(script)\n\twrapper.unload()\n\treturn wrapper.init({globals_name})\nend"
+    )
+}
+
+/// Wraps main plugin source with: return { init = function(_proxyGlobals) ... end }
+fn wrap_main_source(ast: Ast, options: &TranspileOptions) -> String {
+    let globals_name = &options.globals_name;
+    let indent = options.indent.unit();
+    let code_block = indent_string(full_moon::print(&ast), &indent);
+    // the snippets are written with tabs
+    let styled = |snippet: &str| snippet.replace('\t', &indent);
+
+    let init_func = FunctionBody::new()
+        .with_parameters(std::iter::once(Pair::End(Parameter::Name(TokenReference::new_identifier(globals_name)))).collect())
+        .with_parameters_parentheses(ContainedSpan::new(
+            TokenReference::symbol("(").unwrap(),
+            TokenReference::symbol(")\n").unwrap(),
+        ))
+        .with_block(Block::new().with_last_stmt(Some((
+            LastStmt::Return(Return::new().with_token(TokenReference::new_type(TokenType::Identifier {
+                identifier: ShortString::new(code_block),
+            }))),
+            None,
+        ))));
+
+    let name_key = |key: &str, value: Expression| Field::NameKey {
+        key: TokenReference::new_identifier(key),
+        equal: TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Equal }).with_trivia(Some(" "), Some(" ")),
+        value,
+    };
+    let init_field = name_key(
+        if options.memoize_init { "_init" } else { "init" },
+        Expression::Function(Box::new((
+            TokenReference::new_type(TokenType::Symbol {
+                symbol: Symbol::Function,
+            }),
+            init_func,
+        ))),
+    );
+
+    let mut fields = vec![init_field];
+    if options.unload_cleanup {
+        fields.push(name_key("cleanup", new_identifier_expression(&styled(CLEANUP_TABLE), None)));
+    }
+    if options.unload_cleanup || options.reload_modules.is_some() {
+        let unload = styled(&unload_hook(options));
+        fields.push(name_key("unload", new_identifier_expression(&unload, None)));
+    }
+    if options.reload_modules.is_some() {
+        fields.push(name_key("reload", new_identifier_expression(&styled(&reload_hook(globals_name)), None)));
+    }
+    if let Some(plugin_id) = &options.plugin_id {
+        fields.push(name_key("id", new_identifier_expression(&lua_string(plugin_id), None)));
+    }
+    if let Some(info) = &options.plugin_info {
+        for (key, value) in [("name", &info.name), ("version", &info.version)] {
+            if let Some(value) = value {
+                fields.push(name_key(key, new_identifier_expression(&lua_string(value), None)));
+            }
+        }
+    }
+
+    let last = fields.len() - 1;
+    let fields: Punctuated<Field> = fields
+        .into_iter()
+        .enumerate()
+        .map(|(index, field)| {
+            if index == last {
+                Pair::End(field)
+            } else {
+                Pair::Punctuated(field, TokenReference::symbol(",\n").unwrap())
+            }
+        })
+        .collect();
+
+    let wrapper = Expression::TableConstructor(TableConstructor::new().with_fields(fields));
+    let wrapper = match options.memoize_init {
+        true => Expression::FunctionCall(new_function_call(
+            "setmetatable",
+            [wrapper, new_identifier_expression(&styled(MEMOIZED_INIT), None)],
+        )),
+        false => wrapper,
+    };
+    let mut returns = Punctuated::new();
+    returns.push(Pair::End(wrapper));
+
+    let mut wrapped: String = options.directives.iter().map(|directive| format!("--!{directive}\n")).collect();
+    wrapped.push_str(&full_moon::print(
+        &ast.with_nodes(Block::new().with_last_stmt(Some((LastStmt::Return(Return::new().with_returns(returns)), None)))),
+    ));
+    wrapped
+}
+
+/// Options that affect how a single script is transpiled
+#[derive(Clone, Debug)]
+pub struct TranspileOptions {
+    /// The depth of the script in the dom tree, used for requiring the plugin globals.
+    /// Depth 0 is the main script, which gets wrapped
+    pub path_depth: usize,
+    /// Names of the rules that won't rewrite anything, see [`rules`]. The [`rules::OPT_IN`] rules by default
+    pub disabled_rules: BTreeSet<String>,
+    /// Name of the variable holding the plugin globals, `_proxyGlobals` by default
+    pub globals_name: String,
+    /// Name of the main script, if the globals are required from a dedicated `Globals` module under it
+    /// instead of from the main script by depth
+    pub globals_module: Option<String>,
+    /// Keeps every original statement on its original line, so runtime errors can be mapped back to the original source.
+    /// The requires are put on the first line, and the main script's wrapper moves it down by [`MAIN_LINE_OFFSET`]
+    /// and the amount of directives
+    pub preserve_lines: bool,
+    /// Asserts that the plugin globals and every capability used from them exist, erroring with the name
+    /// of the missing capability and the script instead of a generic "attempt to index nil"
+    pub debug_runtime: bool,
+    /// Lua expressions of the modules that the `unload` hook of the main script's wrapper replaces with fresh clones,
+    /// clearing their require cache. `None` doesn't emit the `unload` and `reload` hooks
+    pub reload_modules: Option<Vec<String>>,
+    /// Luau directives emitted at the top of the wrapped main script without the `--!`, like `strict` or `optimize 2`.
+    /// Directives of the original source end up inside the wrapper, where they have no effect
+    pub directives: Vec<String>,
+    /// Name and version of the plugin, exposed as `name` and `version` next to `init` in the main script's wrapper
+    pub plugin_info: Option<PluginInfo>,
+    /// Stable identity of the plugin, exposed as `id` next to `init` in the main script's wrapper so hosts can keep
+    /// settings, widgets and toolbars across re-transpiles, see [`DomTranspiler::plugin_id`]
+    pub plugin_id: Option<String>,
+    /// Emits the generated locals as one `local _proxyGlobals, plugin, Enums = ...` statement on a single line,
+    /// so the original code moves down less. Capability asserts of [`TranspileOptions::debug_runtime`] come after it
+    pub combine_locals: bool,
+    /// Puts the generated statements after the script's leading block of `local X = require(...)` statements instead of
+    /// at the very top, keeping require groupings intact. Requires using the generated locals stay after them,
+    /// and [`TranspileOptions::preserve_lines`] keeps them on the first line
+    pub place_after_requires: bool,
+    /// Emits a Janitor-like `cleanup` table and an `unload` function that runs its tasks in the main script's wrapper,
+    /// giving hosts a standard teardown entry point. `plugin.Unloading` connections are made on the cleanup,
+    /// since the proxied plugin doesn't unload with the host
+    pub unload_cleanup: bool,
+    /// Makes `init` of the main script's wrapper run the plugin once and return the same exports on later calls,
+    /// for hosts that may call it more than once, like with several docks. The function is kept as `_init` and `init`
+    /// is served by the wrapper's metatable, `unload` forgets the exports so the plugin can run again
+    pub memoize_init: bool,
+    /// Routes calls that insert assets, like `InsertService:LoadAsset` and `plugin:ImportFbxRig`, through
+    /// `_proxyGlobals.assets` so hosts can sandbox them, otherwise they're only reported, see [`ASSET_INSERTIONS`]
+    pub proxy_assets: bool,
+    /// Routes `RunService:IsEdit()`, `IsStudio()`, `IsRunMode()` and `IsRunning()` through `_proxyGlobals.runContext`,
+    /// so hosts control which mode the plugin believes Studio is in, see [`RUN_CONTEXT_METHODS`]
+    pub proxy_run_context: bool,
+    /// Routes reads of what's below CoreGui, like `game.CoreGui.SomePanel`, through `_proxyGlobals.coreGui`,
+    /// the virtual container hosts usually expose instead of the real CoreGui, otherwise they're only reported
+    pub proxy_coregui: bool,
+    /// Routes AssetService calls and `ContentProvider:PreloadAsync` through `_proxyGlobals.assets`, and wraps
+    /// expressions building `rbxthumb://` URLs in `_proxyGlobals.assets:Thumbnail(url)`, so hosts can substitute
+    /// their own thumbnail pipeline, otherwise they're only reported, see [`THUMBNAIL_CALLS`]
+    pub proxy_thumbnails: bool,
+    /// Replaces statements that call monetization APIs, like `MarketplaceService:PromptPurchase(player, id)`,
+    /// with an empty `do end` block for hosts that forbid purchase prompts, see [`MONETIZATION_CALLS`].
+    /// Calls whose result is used are only reported
+    pub strip_monetization: bool,
+    /// Services that `game:GetService` is proxied for, for hosts whose game facade only provides some,
+    /// other services are got from the real game and reported, see [`rules::UNLISTED_SERVICE`]. All when `None`
+    pub allowed_services: Option<BTreeSet<String>>,
+    /// Indentation of the code the transpiler generates and of the code it wraps, like the main script in `init`
+    pub indent: Indent,
+    /// Wording of the comment marking generated code, no comment when `None`. Defaults to [`GENERATED_COMMENT`]
+    pub generated_comment: Option<String>,
+    /// Rules that rewrite chains like `settings().Studio.Theme`, the built-in ones and those added with
+    /// [`DomTranspiler::add_rule`]
+    pub rules: RuleRegistry,
+    /// Prefixes of comments at the very top of a script that stay its first lines, above the generated code,
+    /// matched after the `--` and spaces. Empty by default, [`HEADER_COMMENTS`] has the common ones
+    pub header_comments: Vec<String>,
+    /// Namespace that keys of `plugin:GetSetting` and `plugin:SetSetting` are prefixed with as `namespace/key`,
+    /// so proxied plugins storing the same key don't collide in the host's settings store
+    pub settings_namespace: Option<String>,
+}
+
+impl Default for TranspileOptions {
+    fn default() -> Self {
+        Self {
+            path_depth: 0,
+            disabled_rules: rules::OPT_IN.iter().map(|rule| rule.to_string()).collect(),
+            globals_name: String::from(GLOBAL_VAR_NAME),
+            globals_module: None,
+            preserve_lines: false,
+            debug_runtime: false,
+            reload_modules: None,
+            directives: Vec::new(),
+            plugin_info: None,
+            plugin_id: None,
+            combine_locals: false,
+            place_after_requires: false,
+            unload_cleanup: false,
+            memoize_init: false,
+            proxy_assets: false,
+            proxy_run_context: false,
+            proxy_coregui: false,
+            proxy_thumbnails: false,
+            strip_monetization: false,
+            allowed_services: None,
+            indent: Indent::Tab,
+            generated_comment: Some(String::from(GENERATED_COMMENT)),
+            rules: RuleRegistry::builtin(),
+            header_comments: Vec::new(),
+            settings_namespace: None,
+        }
+    }
+}
+
+/// Outcome of transpiling a script in the tree
+struct ProcessedScript {
+    /// Empty when freed early in low-memory mode, see [`DomTranspiler::low_memory`]
+    original: String,
+    bytes_before: usize,
+    /// Lines the original statements were moved down by, when lines are preserved
+    line_offset: Option<usize>,
+    /// Original lines mapped to transpiled lines, when recording source maps
+    line_map: Option<BTreeMap<usize, usize>>,
+    /// Kept untouched because it couldn't be parsed, see [`DomTranspiler::passthrough_unparsed`]
+    passed_through: bool,
+    /// Looks minified or obfuscated, see [`is_minified`]
+    minified: bool,
+    requirements: Requirements,
+    /// Amount of rewrites done by each rule
+    applied: BTreeMap<&'static str, usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Where the original source of modified scripts is kept, see [`DomTranspiler::embed_original`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbedOriginal {
+    /// A string attribute on the script
+    Attribute,
+    /// A StringValue child of the script, it's a child instead of a sibling so it stays with the main script
+    /// and doesn't show up when code iterates through a folder of modules
+    StringValue,
+}
+
+impl std::str::FromStr for EmbedOriginal {
+    type Err = String;
+
+    fn from_str(embed: &str) -> Result<Self, Self::Err> {
+        match embed.to_ascii_lowercase().as_str() {
+            "attribute" => Ok(EmbedOriginal::Attribute),
+            "string-value" | "stringvalue" => Ok(EmbedOriginal::StringValue),
+            _ => Err(format!("unknown place for the original source '{embed}', expected attribute or string-value")),
+        }
+    }
+}
+
+/// Namespace of the keys of plugin settings, see [`DomTranspiler::settings_namespace`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SettingsNamespace {
+    /// The name from the plugin's metadata module, or else the name of the top-level instance containing the main script
+    PluginName,
+    Custom(String),
+}
+
+/// Glob patterns of TestEZ and Jest-Lua test modules and runners, see [`DomTranspiler::test_modules`]
+pub const TEST_PATTERNS: &[&str] = &[
+    "**/*.spec/**",
+    "**/*.test/**",
+    "**/__tests__/**",
+    "**/[Tt]ests/**",
+    "**/TestEZ/**",
+    "**/[Jj]est/**",
+    "**/JestGlobals/**",
+];
+
+/// What happens to test modules, see [`DomTranspiler::test_modules`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestModules {
+    /// Transpiled like any other module
+    #[default]
+    Keep,
+    /// Kept in the output untouched
+    Skip,
+    /// Deleted with their descendants before transpiling
+    Strip,
+}
+
+impl std::str::FromStr for TestModules {
+    type Err = String;
+
+    fn from_str(test_modules: &str) -> Result<Self, Self::Err> {
+        match test_modules.to_ascii_lowercase().as_str() {
+            "keep" => Ok(TestModules::Keep),
+            "skip" => Ok(TestModules::Skip),
+            "strip" => Ok(TestModules::Strip),
+            _ => Err(format!("unknown handling of test modules '{test_modules}', expected keep, skip or strip")),
+        }
+    }
+}
+
+/// What to do with an instance after the save hook has seen it, see [`DomTranspiler::on_save`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeepOrDrop {
+    Keep,
+    /// Deletes the instance with its descendants from the output
+    Drop,
+}
+
+type SaveHook = Box<dyn FnMut(&mut Instance) -> KeepOrDrop + Send>;
+
+/// Rule toggle for the scripts whose path matches a glob pattern, see [`DomTranspiler::path_rule`]
+struct PathRule {
+    pattern: String,
+    rule: String,
+    enabled: bool,
+}
+
+/// Applies the toggles of the path rules matching the script path on top of the disabled rules
+fn apply_path_rules(path_rules: &[PathRule], path: &str, disabled_rules: &mut BTreeSet<String>) {
+    for path_rule in path_rules.iter().filter(|path_rule| glob_match(&path_rule.pattern, path)) {
+        if path_rule.enabled {
+            disabled_rules.remove(&path_rule.rule);
+        } else {
+            disabled_rules.insert(path_rule.rule.clone());
+        }
+    }
+}
+
+pub struct DomTranspiler {
+    tree: WeakDom,
+    source_script: Ref,
+    options: TranspileOptions,
+    exclude_libs: bool,
+    exclude_paths: Vec<String>,
+    strip_paths: Vec<String>,
+    test_modules: TestModules,
+    test_paths: Vec<String>,
+    path_rules: Vec<PathRule>,
+    prune_unused: bool,
+    local_scripts_as_modules: bool,
+    main_only: bool,
+    globals_module: bool,
+    /// Top-level scripts run by a synthesized loader, see [`DomTranspiler::with_loader`]
+    entry_scripts: Vec<Ref>,
+    save_hook: Option<SaveHook>,
+    save_full_tree: bool,
+    plugin_info: Option<PluginInfo>,
+    plugin_id: Option<String>,
+    settings_namespace: Option<SettingsNamespace>,
+    hot_reload: bool,
+    low_memory: bool,
+    embed_original: Option<EmbedOriginal>,
+    record_metadata: bool,
+    /// Hash of the original sources of the transpiled scripts, for the metadata
+    source_hash: Sha256,
+    record_require_graph: bool,
+    require_graph: Option<RequireGraph>,
+    record_snapshots: bool,
+    snapshots: Vec<ScriptSnapshot>,
+    record_source_maps: bool,
+    source_maps: Vec<SourceMap>,
+    record_capabilities: bool,
+    embed_capabilities: Option<CapabilityEmbed>,
+    capabilities: Option<CapabilityManifest>,
+    record_manifest: bool,
+    manifest: Manifest,
+    metrics: Metrics,
+    allow_failures: bool,
+    keep_directives: bool,
+    passthrough_unparsed: bool,
+    code_frames: bool,
+    failures: Vec<ScriptFailure>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+pub(crate) fn is_script_class(class: &str) -> bool {
+    matches!(class, "ModuleScript" | "Script" | "LocalScript")
+}
+
+/// Name of the main script synthesized by [`DomTranspiler::with_loader`]
+pub const LOADER_NAME: &str = "PluginProxyLoader";
+
+/// Directives on the first lines of a source without the leading `--!`, like `strict` for `--!strict`
+fn leading_directives(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("--!"))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.trim_start_matches("--!").to_string())
+        .collect()
+}
+
+/// Text in the source of a script that couldn't be parsed, which suggests plugin API usage that wasn't rewritten
+const UNPARSED_API_PATTERNS: &[&str] = &[
+    "GetService",
+    "settings(",
+    "FindFirstAncestorOfClass",
+    "FindFirstAncestorWhichIsA",
+    "StudioStyleGuide",
+    "UITheme",
+];
+
+/// Name of the Configuration inserted by [`DomTranspiler::record_metadata`]
+pub const METADATA_NAME: &str = "PluginProxyMetadata";
+/// Name of the attribute or ModuleScript holding the capabilities of the plugin, see [`DomTranspiler::embed_capabilities`]
+pub const CAPABILITIES_NAME: &str = "PluginProxyCapabilities";
+/// Name of the attribute of the main script holding the id of the plugin, see [`DomTranspiler::plugin_id`]
+pub const PLUGIN_ID_NAME: &str = "PluginProxyId";
+/// Name of the attribute or StringValue holding the original source of a script, see [`DomTranspiler::embed_original`]
+pub const ORIGINAL_SOURCE_NAME: &str = "PluginProxyOriginalSource";
+
+/// Counts the ModuleScripts below the instance, without building their paths
+fn count_modules(tree: &WeakDom, parent: Ref) -> usize {
+    let mut stack = vec![parent];
+    let mut count = 0;
+
+    while let Some(referent) = stack.pop() {
+        if let Some(instance) = tree.get_by_ref(referent) {
+            if referent != parent && instance.class == "ModuleScript" {
+                count += 1;
+            }
+            stack.extend(instance.children());
+        }
+    }
+
+    count
+}
+
+/// Levels below the root that are searched for the main script, before searching the whole tree
+pub const MAIN_SEARCH_DEPTH: u8 = 2;
+
+/// Finds every script that could be the main plugin script, in the order they're searched
+///
+/// Candidates are scripts near the root that aren't nested in another script,
+/// more than one usually means the file contains multiple plugins
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::main_script_candidates;
+/// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+///
+/// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+/// let folder = tree.insert(tree.root_ref(), InstanceBuilder::new("Folder"));
+/// let main = tree.insert(folder, InstanceBuilder::new("Script").with_name("Main"));
+/// let util = tree.insert(folder, InstanceBuilder::new("ModuleScript").with_name("Util"));
+/// assert_eq!(main_script_candidates(&tree), vec![main]);
+///
+/// tree.destroy(main);
+/// assert_eq!(main_script_candidates(&tree), vec![util]);
+/// ```
+pub fn main_script_candidates(tree: &WeakDom) -> Vec<Ref> {
+    main_script_candidates_within(tree, MAIN_SEARCH_DEPTH)
+}
+
+/// Same as [`main_script_candidates`], but searching `depth` levels below the root
+///
+/// If no script is found within the depth, the whole tree is searched with a warning,
+/// keeping only the candidates closest to the root. Depth 0 searches the whole tree
+pub fn main_script_candidates_within(tree: &WeakDom, depth: u8) -> Vec<Ref> {
+    let candidates = find_main_script_candidates(tree, depth);
+    if !candidates.is_empty() || depth == 0 {
+        return candidates.into_iter().map(|(referent, _)| referent).collect();
+    }
+
+    let candidates = find_main_script_candidates(tree, 0);
+    let Some(closest) = candidates.iter().map(|(_, depth)| *depth).min() else {
+        return Vec::new();
+    };
+    warn!("No script was found within {depth} levels of the root, using the scripts {closest} levels deep");
+
+    candidates
+        .into_iter()
+        .filter(|(_, depth)| *depth == closest)
+        .map(|(referent, _)| referent)
+        .collect()
+}
+
+/// Finds scripts that aren't nested in another script with their depth. ModuleScripts are only candidates
+/// when there's no Script or LocalScript, so the modules next to the main script don't make it ambiguous
+fn find_main_script_candidates(tree: &WeakDom, depth: u8) -> Vec<(Ref, usize)> {
+    let mut candidates = Vec::new();
+    let mut modules = Vec::new();
+    tree.foreach_descendant(
+        tree.root(),
+        &mut |instance, path| {
+            let nested = tree.get_by_ref(instance.parent()).is_some_and(|parent| is_script_class(&parent.class));
+            if is_script_class(&instance.class) && !nested {
+                match instance.class.as_str() {
+                    "ModuleScript" => modules.push((instance.referent(), path.depth())),
+                    _ => candidates.push((instance.referent(), path.depth())),
+                }
+            }
+            ForEachAction::Continue
+        },
+        depth,
+    );
+
+    if candidates.is_empty() {
+        modules
+    } else {
+        candidates
+    }
+}
+
+impl DomTranspiler {
+    pub fn new(tree: WeakDom) -> Result<Self, Problem> {
+        Self::with_search_depth(tree, MAIN_SEARCH_DEPTH)
+    }
+
+    /// Creates a transpiler for the first main script found within `depth` levels of the root,
+    /// see [`main_script_candidates_within`]
+    pub fn with_search_depth(tree: WeakDom, depth: u8) -> Result<Self, Problem> {
+        let source_script = *main_script_candidates_within(&tree, depth).first().ok_or(Problem::NoMainSource)?;
+
+        Self::with_source_script(tree, source_script)
+    }
+
+    /// Creates a transpiler with an explicitly chosen main plugin script, see [`main_script_candidates`]
+    pub fn with_source_script(tree: WeakDom, source_script: Ref) -> Result<Self, Problem> {
+        match tree.get_by_ref(source_script) {
+            Some(script) if is_script_class(&script.class) => {}
+            _ => return Err(Problem::NoMainSource),
+        }
+
+        Ok(Self {
+            tree,
+            source_script,
+            options: TranspileOptions::default(),
+            exclude_libs: true,
+            exclude_paths: Vec::new(),
+            strip_paths: Vec::new(),
+            test_modules: TestModules::Keep,
+            test_paths: Vec::new(),
+            path_rules: Vec::new(),
+            prune_unused: false,
+            local_scripts_as_modules: false,
+            main_only: false,
+            globals_module: false,
+            entry_scripts: Vec::new(),
+            save_hook: None,
+            save_full_tree: false,
+            plugin_info: None,
+            plugin_id: None,
+            settings_namespace: None,
+            hot_reload: false,
+            low_memory: false,
+            embed_original: None,
+            record_metadata: false,
+            source_hash: Sha256::new(),
+            record_require_graph: false,
+            require_graph: None,
+            record_snapshots: false,
+            snapshots: Vec::new(),
+            record_source_maps: false,
+            source_maps: Vec::new(),
+            record_capabilities: false,
+            embed_capabilities: None,
+            capabilities: None,
+            record_manifest: false,
+            manifest: Manifest::default(),
+            metrics: Metrics::default(),
+            allow_failures: false,
+            keep_directives: false,
+            passthrough_unparsed: false,
+            code_frames: false,
+            failures: Vec::new(),
+            diagnostics: Vec::new(),
+        })
+    }
+
+    /// Creates a transpiler for plugins with several top-level Scripts instead of one main script
+    ///
+    /// A `PluginProxyLoader` ModuleScript is inserted next to the scripts and everything next to it is moved into it.
+    /// The loader becomes the main script, its `init` requires and runs every original Script and LocalScript in order,
+    /// which are wrapped the same way as a main script
+    pub fn with_loader(tree: WeakDom) -> Result<Self, Problem> {
+        Self::with_loader_and_search_depth(tree, MAIN_SEARCH_DEPTH)
+    }
+
+    /// Same as [`DomTranspiler::with_loader`], with the scripts found within `depth` levels of the root,
+    /// see [`main_script_candidates_within`]
+    pub fn with_loader_and_search_depth(mut tree: WeakDom, depth: u8) -> Result<Self, Problem> {
+        let first = *main_script_candidates_within(&tree, depth).first().ok_or(Problem::NoMainSource)?;
+        let container = tree.get_by_ref(first).unwrap().parent();
+        let children = tree.get_by_ref(container).unwrap().children().to_vec();
+
+        let entry_scripts: Vec<Ref> = children
+            .iter()
+            .copied()
+            .filter(|child| tree.get_by_ref(*child).is_some_and(|child| matches!(child.class.as_str(), "Script" | "LocalScript")))
+            .collect();
+        if entry_scripts.is_empty() {
+            return Err(Problem::NoMainSource);
+        }
+
+        let loader = tree.insert(
+            container,
+            InstanceBuilder::new("ModuleScript")
+                .with_name(LOADER_NAME)
+                .with_property("Source", String::new()),
+        );
+        for child in children {
+            tree.transfer_within(child, loader);
+        }
+        info!("Synthesized a loader for {} top-level scripts", entry_scripts.len());
+
+        let mut transpiler = Self::with_source_script(tree, loader)?;
+        transpiler.entry_scripts = entry_scripts;
+        Ok(transpiler)
+    }
+
+    /// The dom tree being transpiled
+    pub fn tree(&self) -> &WeakDom {
+        &self.tree
+    }
+
+    /// Referent of the main plugin script, the root of the saved output
+    pub fn source_script(&self) -> Ref {
+        self.source_script
+    }
+
+    /// Ids of the assets that the main script and the scripts below it require like `require(1234567)`,
+    /// which proxy hosts can't load. Download them and pass them to [`DomTranspiler::vendor_asset`]
+    pub fn asset_requires(&self) -> BTreeSet<u64> {
+        vendor::asset_requires(&self.tree, self.source_script)
+    }
+
+    /// Inserts the module of a downloaded asset into the plugin and makes the requires of the asset require it,
+    /// see [`vendor`]. Call before transpiling, so the vendored module is transpiled too
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::{dom::extension::InstanceExt, DomTranspiler};
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let source = String::from("local lib = require(1234)");
+    /// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("Script").with_name("Main").with_property("Source", source));
+    /// let mut asset = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// asset.insert(asset.root_ref(), InstanceBuilder::new("ModuleScript").with_name("MainModule"));
+    ///
+    /// let mut transpiler = DomTranspiler::with_source_script(tree, main).unwrap();
+    /// assert_eq!(transpiler.asset_requires().into_iter().collect::<Vec<_>>(), vec![1234]);
+    /// assert_eq!(transpiler.vendor_asset(1234, asset).unwrap(), 1);
+    /// let main = transpiler.tree().get_by_ref(main).unwrap();
+    /// assert_eq!(main.source(), Some("local lib = require(script._Vendored.Asset1234)"));
+    /// ```
+    ///
+    /// # Returns
+    /// Amount of rewritten requires
+    pub fn vendor_asset(&mut self, id: u64, asset: WeakDom) -> Result<usize, Problem> {
+        let count = vendor::vendor(&mut self.tree, self.source_script, id, asset)?;
+        info!("Vendored asset {id}, rewrote {count} requires");
+        Ok(count)
+    }
+
+    /// Controls the exclusion of standard libraries that typically don't need plugin access.
+    ///
+    /// * **Default: true** (libraries are excluded)
+    /// * Set to `false` to include all libraries, even standard ones.
+    ///
+    /// Use `false` if your plugin relies on a module that shares a name with
+    /// a standard library (like React or Fusion) and needs plugin-specific methods.
+    ///
+    /// # Affected libraries
+    ///
+    /// (also any descendants of the path to these libraries)
+    /// * React/Roact, jsdotlua descendants
+    /// * Fusion
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn exclude_libs(&mut self, exclude_libs: bool) -> &mut Self {
+        self.exclude_libs = exclude_libs;
+        self
+    }
+
+    /// Excludes scripts with paths matching any of the glob patterns from being transpiled
+    ///
+    /// Paths are in the format of script/Parent/Child/, where `script` is the main script
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn exclude_paths<I, S>(&mut self, patterns: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude_paths.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Deletes instances with paths matching any of the glob patterns, with their descendants, before transpiling
+    ///
+    /// Paths are in the format of script/Parent/Child/, where `script` is the main script.
+    /// Useful for test or story modules that shouldn't be distributed, like `**/Tests/**`
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn strip_paths<I, S>(&mut self, patterns: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.strip_paths.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Controls what happens to TestEZ and Jest-Lua test modules and runners, found by [`TEST_PATTERNS`]
+    /// and [`DomTranspiler::test_paths`]. Separate from [`DomTranspiler::exclude_libs`]
+    ///
+    /// * **Default: [`TestModules::Keep`]**
+    ///
+    /// Skipped ones count as skipped scripts and stripped ones as stripped instances in the [`Metrics`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn test_modules(&mut self, test_modules: TestModules) -> &mut Self {
+        self.test_modules = test_modules;
+        self
+    }
+
+    /// Adds glob patterns of test modules to [`TEST_PATTERNS`], for test folders with other names
+    ///
+    /// Paths are in the format of script/Parent/Child/, where `script` is the main script
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn test_paths<I, S>(&mut self, patterns: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.test_paths.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Controls removing ModuleScripts that are never required from the main script before transpiling
+    ///
+    /// * **Default: false**
+    ///
+    /// Scripts and LocalScripts are kept, as they run on their own. Only requires of paths relative to the script
+    /// can be followed (like `require(script.Parent.Util)`), if a used script has any other require nothing is removed
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn prune_unused(&mut self, prune_unused: bool) -> &mut Self {
+        self.prune_unused = prune_unused;
+        self
+    }
+
+    /// Controls converting the LocalScripts below the main script into ModuleScripts that run on demand,
+    /// since LocalScripts can't run inside proxy hosts
+    ///
+    /// * **Default: false**
+    ///
+    /// Each becomes a module returning `{ run = function() ... end }`, and statements that enable one like
+    /// `script.Template.Runner.Disabled = false` or `.Enabled = true` are rewritten into `require(script.Template.Runner).run()`.
+    /// Entry scripts of [`DomTranspiler::with_loader`] are kept
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn local_scripts_as_modules(&mut self, local_scripts_as_modules: bool) -> &mut Self {
+        self.local_scripts_as_modules = local_scripts_as_modules;
+        self
+    }
+
+    /// Controls transpiling only the main script, and the entry scripts of [`DomTranspiler::with_loader`],
+    /// leaving the modules below it untouched. For plugins that only use the plugin API in the entry point
+    ///
+    /// * **Default: false**
+    ///
+    /// The untouched modules are counted as skipped
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn main_only(&mut self, main_only: bool) -> &mut Self {
+        self.main_only = main_only;
+        self
+    }
+
+    /// Controls building the [`RequireGraph`] of the plugin while transpiling
+    ///
+    /// * **Default: false**
+    ///
+    /// The graph is built from the original sources, after pruning, and can be accessed with [`DomTranspiler::require_graph`].
+    /// It's also built when the [`rules::REQUIRE_CYCLE`] rule is enabled
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn record_require_graph(&mut self, record_require_graph: bool) -> &mut Self {
+        self.record_require_graph = record_require_graph;
+        self
+    }
+
+    /// Name and version read from a metadata module of the plugin by the last [`DomTranspiler::transpile_tree`],
+    /// see [`PluginInfo::find`]
+    pub fn plugin_info(&self) -> Option<&PluginInfo> {
+        self.plugin_info.as_ref()
+    }
+
+    /// Require graph built by the last [`DomTranspiler::transpile_tree`], see [`DomTranspiler::record_require_graph`]
+    pub fn require_graph(&self) -> Option<&RequireGraph> {
+        self.require_graph.as_ref()
+    }
+
+    /// Sets the name of the variable holding the plugin globals in the generated code
+    ///
+    /// * **Default: `_proxyGlobals`**
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn globals_name(&mut self, globals_name: &str) -> &mut Self {
+        self.options.globals_name = globals_name.to_string();
+        self
+    }
+
+    /// Controls inserting a dedicated `Globals` ModuleScript under the main script, which descendant scripts require
+    /// by the name of the main script instead of requiring the main script by their depth
+    ///
+    /// * **Default: false**
+    ///
+    /// This keeps descendant scripts working if they're moved to another depth,
+    /// transpiling fails if the main script already has a child named `Globals`
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn globals_module(&mut self, globals_module: bool) -> &mut Self {
+        self.globals_module = globals_module;
+        self
+    }
+
+    /// Enables or disables a rule, all rules but the [`rules::OPT_IN`] ones are enabled by default
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - Name of the rule, see [`rules`]
+    /// * `enabled` - Whether the rule should rewrite code
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn rule(&mut self, rule: &str, enabled: bool) -> &mut Self {
+        if enabled {
+            self.options.disabled_rules.remove(rule);
+        } else {
+            self.options.disabled_rules.insert(rule.to_string());
+        }
+        self
+    }
+
+    /// Enables or disables a rule only for the scripts whose path matches a glob pattern, like disabling
+    /// [`rules::GET_SERVICE`] under `**/Vendor/**`. Overrides what [`DomTranspiler::rule`] set for every script,
+    /// the last matching override of a rule wins
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Glob pattern of script paths in the format of script/Parent/Child/, the main script is script/
+    /// * `rule` - Name of the rule, see [`rules`]
+    /// * `enabled` - Whether the rule should rewrite code in the matching scripts
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn path_rule(&mut self, pattern: &str, rule: &str, enabled: bool) -> &mut Self {
+        self.path_rules.push(PathRule {
+            pattern: pattern.to_string(),
+            rule: rule.to_string(),
+            enabled,
+        });
+        self
+    }
+
+    /// Sets a function that is called for every instance in the output just before it's encoded
+    /// by any of the save functions, parents before their children
+    ///
+    /// The function can edit the instance, like removing secrets from StringValues, or drop it from the output.
+    /// The main script and its ancestors can't be dropped. Changes stay in the dom after saving
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::{DomTranspiler, KeepOrDrop, RbxFileType};
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("Script").with_name("Main"));
+    /// tree.insert(main, InstanceBuilder::new("StringValue").with_name("ApiKey"));
+    /// tree.insert(main, InstanceBuilder::new("StringValue").with_name("Theme"));
+    /// let mut transpiler = DomTranspiler::with_source_script(tree, main).unwrap();
+    /// transpiler.on_save(|instance| match instance.name.as_str() {
+    ///     "ApiKey" => KeepOrDrop::Drop,
+    ///     _ => KeepOrDrop::Keep,
+    /// });
+    ///
+    /// let mut output = Vec::new();
+    /// transpiler.save_to_writer(&mut output, RbxFileType::Binary).unwrap();
+    /// let saved = rbx_binary::from_reader(output.as_slice()).unwrap();
+    /// let main = saved.get_by_ref(saved.root().children()[0]).unwrap();
+    /// let names: Vec<_> = main.children().iter().map(|child| saved.get_by_ref(*child).unwrap().name.as_str()).collect();
+    /// assert_eq!(names, ["Theme"]);
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn on_save<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: FnMut(&mut Instance) -> KeepOrDrop + Send + 'static,
+    {
+        self.save_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Controls inserting a `PluginProxyMetadata` Configuration under the main script, with attributes describing
+    /// how the output was produced, so proxy hosts can check it at runtime
+    ///
+    /// * **Default: false**
+    ///
+    /// # Attributes
+    ///
+    /// * `TranspilerVersion` - Version of the transpiler
+    /// * `TranspiledAt` - Unix timestamp in seconds
+    /// * `SourceHash` - SHA-256 of the paths and original sources of the transpiled scripts
+    /// * `GlobalsName`, `GlobalsModule`, `ExcludeLibs`, `DisabledRules` - Options that affect the output
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn record_metadata(&mut self, record_metadata: bool) -> &mut Self {
+        self.record_metadata = record_metadata;
+        self
+    }
+
+    /// Sets the stable identity of the plugin, embedded as `id` in the main script's wrapper and as the
+    /// [`PLUGIN_ID_NAME`] attribute of the main script, so proxy hosts can track settings, widgets and toolbars
+    /// across re-transpiles of the same plugin. [`info::plugin_id_from_contents`] derives one from the original file
+    ///
+    /// * **Default: None**
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::{dom::extension::InstanceExt, DomTranspiler, PLUGIN_ID_NAME};
+    /// use rbx_dom_weak::{types::Variant, InstanceBuilder, WeakDom};
+    ///
+    /// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let source = String::from("print(plugin)");
+    /// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("Script").with_name("Main").with_property("Source", source));
+    /// let mut transpiler = DomTranspiler::with_source_script(tree, main).unwrap();
+    /// transpiler.plugin_id(Some("1b4e28ba-2fa1-11d2-883f-0016d3cca427")).transpile_tree().unwrap();
+    ///
+    /// let main = transpiler.tree().get_by_ref(main).unwrap();
+    /// assert!(main.source().unwrap().contains(r#"id = "1b4e28ba-2fa1-11d2-883f-0016d3cca427""#));
+    /// let Some(Variant::Attributes(attributes)) = main.properties.get("Attributes") else { unreachable!() };
+    /// assert!(attributes.get(PLUGIN_ID_NAME).is_some());
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn plugin_id<S: Into<String>>(&mut self, plugin_id: Option<S>) -> &mut Self {
+        self.plugin_id = plugin_id.map(Into::into);
+        self
+    }
+
+    /// Controls reducing memory usage for large inputs like places
+    ///
+    /// * **Default: false**
+    ///
+    /// Clears the properties of instances that aren't saved before transpiling, and frees the original sources
+    /// as soon as the transpiled ones are written back unless they're needed for snapshots, the manifest or the metadata
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn low_memory(&mut self, low_memory: bool) -> &mut Self {
+        self.low_memory = low_memory;
+        self
+    }
+
+    /// Adds Luau directives to emit at the top of the wrapped main script, like `strict` or `optimize 2`,
+    /// see [`TranspileOptions::directives`]. A leading `--!` is removed
+    ///
+    /// Unknown directives are kept with a warning, see [`DIRECTIVES`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn directives<I, S>(&mut self, directives: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for directive in directives {
+            let directive: String = directive.into();
+            let directive = directive.trim().trim_start_matches("--!").to_string();
+            let name = directive.split_whitespace().next().unwrap_or_default();
+            if !DIRECTIVES.contains(&name) {
+                warn!("Unknown directive '{directive}', known directives are: {}", DIRECTIVES.join(", "));
+            }
+            self.options.directives.push(directive);
+        }
+        self
+    }
+
+    /// Controls keeping the directives on the first lines of the main script, like `--!strict`,
+    /// which would otherwise end up inside `init` where Luau ignores them
+    ///
+    /// * **Default: false**
+    ///
+    /// Kept directives are emitted after the ones added with [`DomTranspiler::directives`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn keep_directives(&mut self, keep_directives: bool) -> &mut Self {
+        self.keep_directives = keep_directives;
+        self
+    }
+
+    /// Controls asserting in the generated code that the host provides every capability, see [`TranspileOptions::debug_runtime`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, debug_runtime: true, ..Default::default() };
+    /// let source = r#"local p = script:FindFirstAncestorOfClass("Plugin")"#;
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("assert(plugin, "));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn debug_runtime(&mut self, debug_runtime: bool) -> &mut Self {
+        self.options.debug_runtime = debug_runtime;
+        self
+    }
+
+    /// Controls keeping every original statement on its original line, see [`TranspileOptions::preserve_lines`]
+    ///
+    /// * **Default: false**
+    ///
+    /// The line offset of every script is recorded in its [`ScriptMetrics`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, preserve_lines: true, ..Default::default() };
+    /// let source = "local theme = settings().Studio.Theme\nprint(theme)";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// let first = transpiled.lines().next().unwrap();
+    /// assert!(first.starts_with("local _proxyGlobals = require(script.Parent).Globals;"));
+    /// assert!(first.ends_with("local theme = _proxyGlobals.settings().Studio.Theme"));
+    /// assert_eq!(transpiled.lines().nth(1), Some("print(theme)"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn preserve_lines(&mut self, preserve_lines: bool) -> &mut Self {
+        self.options.preserve_lines = preserve_lines;
+        self
+    }
+
+    /// Controls emitting the generated locals as one statement, see [`TranspileOptions::combine_locals`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, combine_locals: true, ..Default::default() };
+    /// let source = "print(script:FindFirstAncestorOfClass(\"Plugin\"), Enum.UITheme.Dark)";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.starts_with(
+    ///     "local _proxyGlobals, plugin, Enums = require(script.Parent).Globals, require(script.Parent).Globals.plugin, \
+    ///      require(script.Parent).Globals.Enums\n-- Autogenerated"
+    /// ));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn combine_locals(&mut self, combine_locals: bool) -> &mut Self {
+        self.options.combine_locals = combine_locals;
+        self
+    }
+
+    /// Controls putting the generated statements after the leading requires of scripts,
+    /// see [`TranspileOptions::place_after_requires`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, place_after_requires: true, ..Default::default() };
+    /// let source = "local Roact = require(script.Parent.Roact)\nlocal theme = settings().Studio.Theme";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert_eq!(transpiled.lines().nth(1), Some("local _proxyGlobals = require(script.Parent).Globals"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn place_after_requires(&mut self, place_after_requires: bool) -> &mut Self {
+        self.options.place_after_requires = place_after_requires;
+        self
+    }
+
+    /// Controls emitting a cleanup that `unload` runs in the main script's wrapper, see [`TranspileOptions::unload_cleanup`]
+    ///
+    /// * **Default: false**
+    ///
+    /// Scripts can add their own tasks with `require(main).cleanup:Add(task)`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 2, unload_cleanup: true, ..Default::default() };
+    /// let transpiled = DomTranspiler::transpile_script("plugin.Unloading:Connect(save)", &options).unwrap();
+    /// assert_eq!(transpiled, "require(script.Parent.Parent).cleanup:Connect(save)");
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn unload_cleanup(&mut self, unload_cleanup: bool) -> &mut Self {
+        self.options.unload_cleanup = unload_cleanup;
+        self
+    }
+
+    /// Controls running the plugin only once no matter how often `init` is called, see [`TranspileOptions::memoize_init`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { memoize_init: true, ..Default::default() };
+    /// let transpiled = DomTranspiler::transpile_script("return 1", &options).unwrap();
+    /// assert!(transpiled.starts_with("return setmetatable({"));
+    /// assert!(transpiled.contains("_init = function(_proxyGlobals)"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn memoize_init(&mut self, memoize_init: bool) -> &mut Self {
+        self.options.memoize_init = memoize_init;
+        self
+    }
+
+    /// Controls routing calls that insert assets through `_proxyGlobals.assets`, see [`TranspileOptions::proxy_assets`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, proxy_assets: true, ..Default::default() };
+    /// let source = "local InsertService = game:GetService(\"InsertService\")\nlocal model = InsertService:LoadAsset(123)";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("local model = _proxyGlobals.assets:LoadAsset(123)"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn proxy_assets(&mut self, proxy_assets: bool) -> &mut Self {
+        self.options.proxy_assets = proxy_assets;
+        self
+    }
+
+    /// Sets the namespace that keys of `plugin:GetSetting` and `plugin:SetSetting` are prefixed with,
+    /// see [`TranspileOptions::settings_namespace`]
+    ///
+    /// * **Default: None** (keys are kept)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, settings_namespace: Some(String::from("Builder")), ..Default::default() };
+    /// let source = "local config = plugin:GetSetting(\"config\")\nplugin:SetSetting(key, true)";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("plugin:GetSetting(\"Builder/config\")"));
+    /// assert!(transpiled.contains("plugin:SetSetting(\"Builder/\" .. key, true)"));
+    ///
+    /// let source = "local open = (script:FindFirstAncestorOfClass(\"Plugin\")):GetSetting(\"open\")";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("local open = plugin:GetSetting(\"Builder/open\")"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn settings_namespace(&mut self, settings_namespace: Option<SettingsNamespace>) -> &mut Self {
+        self.settings_namespace = settings_namespace;
+        self
+    }
+
+    /// Controls routing the RunService checks of the mode Studio is in through `_proxyGlobals.runContext`,
+    /// see [`TranspileOptions::proxy_run_context`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, proxy_run_context: true, ..Default::default() };
+    /// let source = "local RunService = game:GetService(\"RunService\")\nif not RunService:IsEdit() then return end";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("if not _proxyGlobals.runContext:IsEdit() then"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn proxy_run_context(&mut self, proxy_run_context: bool) -> &mut Self {
+        self.options.proxy_run_context = proxy_run_context;
+        self
+    }
+
+    /// Controls routing reads of what's below CoreGui through `_proxyGlobals.coreGui`,
+    /// see [`TranspileOptions::proxy_coregui`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, proxy_coregui: true, ..Default::default() };
+    /// let source = "local panel = game.CoreGui:FindFirstChild(\"SomePanel\")\nlocal bar = game.CoreGui.TopBar";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("local panel = _proxyGlobals.coreGui:FindFirstChild(\"SomePanel\")"));
+    /// assert!(transpiled.contains("local bar = _proxyGlobals.coreGui.TopBar"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn proxy_coregui(&mut self, proxy_coregui: bool) -> &mut Self {
+        self.options.proxy_coregui = proxy_coregui;
+        self
+    }
+
+    /// Controls routing AssetService calls, `ContentProvider:PreloadAsync` and `rbxthumb://` URLs through
+    /// `_proxyGlobals.assets`, see [`TranspileOptions::proxy_thumbnails`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, proxy_thumbnails: true, ..Default::default() };
+    /// let source = "icon.Image = \"rbxthumb://type=Asset&id=\" .. id .. \"&w=150&h=150\"";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("_proxyGlobals.assets:Thumbnail(\"rbxthumb://type=Asset&id=\" .. id .. \"&w=150&h=150\")"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn proxy_thumbnails(&mut self, proxy_thumbnails: bool) -> &mut Self {
+        self.options.proxy_thumbnails = proxy_thumbnails;
+        self
+    }
+
+    /// Controls stripping statements that prompt purchases or check monetization policies,
+    /// see [`TranspileOptions::strip_monetization`]
+    ///
+    /// * **Default: false**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, strip_monetization: true, ..Default::default() };
+    /// let source = "local Marketplace = game:GetService(\"MarketplaceService\")\nMarketplace:PromptPurchase(player, 123)";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("do --[[ stripped MarketplaceService:PromptPurchase ]] end"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn strip_monetization(&mut self, strip_monetization: bool) -> &mut Self {
+        self.options.strip_monetization = strip_monetization;
+        self
+    }
+
+    /// Sets the only services that `game:GetService` is proxied for, see [`TranspileOptions::allowed_services`]
+    ///
+    /// * **Default: None**, every service is proxied
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let allowed_services = Some(["Selection".to_string()].into());
+    /// let options = TranspileOptions { path_depth: 1, allowed_services, ..Default::default() };
+    /// let source = "local Selection = game:GetService(\"Selection\")\nlocal Http = game:GetService(\"HttpService\")";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("_proxyGlobals.game:GetService(\"Selection\")"));
+    /// assert!(transpiled.contains("local Http = game:GetService(\"HttpService\")"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn allowed_services<I, S>(&mut self, services: Option<I>) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.allowed_services = services.map(|services| services.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Registers a rule that rewrites chains, tried after the built-in ones, see [`rules::Rule`]. It's toggled by its name
+    /// like the built-in rules, and a rule with the name of a built-in one replaces it
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn add_rule<R: Rule + 'static>(&mut self, rule: R) -> &mut Self {
+        self.add_shared_rule(Arc::new(rule))
+    }
+
+    /// Same as [`DomTranspiler::add_rule`], for a rule shared with other transpilers, like one compiled once from
+    /// a [`rules::RuleDefinition`] for every input
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn add_shared_rule(&mut self, rule: Arc<dyn Rule>) -> &mut Self {
+        self.options.rules.register(rule);
+        self
+    }
+
+    /// Sets the indentation of generated code and of the code it wraps, for teams with formatting gates
+    ///
+    /// * **Default: [`Indent::Tab`]**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, Indent, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { indent: Indent::Spaces(4), ..Default::default() };
+    /// let transpiled = DomTranspiler::transpile_script("print(plugin)", &options).unwrap();
+    /// assert!(transpiled.contains("\n    print(plugin)"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn indent(&mut self, indent: Indent) -> &mut Self {
+        self.options.indent = indent;
+        self
+    }
+
+    /// Sets the wording of the comment marking generated code, `None` leaves the comment out. Line breaks are replaced
+    /// with spaces where it's emitted, so the comment stays on one line
+    ///
+    /// * **Default: [`GENERATED_COMMENT`]**
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let source = "local theme = settings().Studio.Theme";
+    /// let options = TranspileOptions { path_depth: 1, generated_comment: None, ..Default::default() };
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(!transpiled.contains("Autogenerated"));
+    ///
+    /// let comment = Some(String::from("Generated\nby hand"));
+    /// let options = TranspileOptions { path_depth: 1, generated_comment: comment, ..Default::default() };
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("Generated by hand"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn generated_comment<S: Into<String>>(&mut self, comment: Option<S>) -> &mut Self {
+        self.options.generated_comment = comment.map(Into::into);
+        self
+    }
+
+    /// Sets the prefixes of comments at the top of scripts that stay their first lines, above the generated code,
+    /// so tools reading them like Luau, selene and StyLua still find them. A leading `--` is removed.
+    /// The main script is wrapped, so this applies to the modules below it
+    ///
+    /// * **Default: empty** (comments stay below the generated code, [`HEADER_COMMENTS`] has the common prefixes)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions, HEADER_COMMENTS};
+    ///
+    /// let header_comments = HEADER_COMMENTS.iter().map(|prefix| prefix.to_string()).collect();
+    /// let options = TranspileOptions { path_depth: 1, header_comments, ..Default::default() };
+    /// let source = "--!strict\n-- selene: allow(unused_variable)\nlocal theme = settings().Studio.Theme\n";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.starts_with("--!strict\n-- selene: allow(unused_variable)\nlocal _proxyGlobals = require(script.Parent)"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn header_comments<I, S>(&mut self, prefixes: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.header_comments = prefixes
+            .into_iter()
+            .map(|prefix| {
+                let prefix: String = prefix.into();
+                prefix.strip_prefix("--").map(str::trim_start).unwrap_or(&prefix).to_owned()
+            })
+            .collect();
+        self
+    }
+
+    /// Controls emitting `unload` and `reload` hooks next to `init` in the main script's wrapper,
+    /// so proxy hosts can hot-swap plugin versions without restarting Studio
+    ///
+    /// * **Default: false**
+    ///
+    /// `unload` replaces the modules under the main script with fresh clones, so requiring them runs them again,
+    /// after running the cleanup of [`DomTranspiler::unload_cleanup`],
+    /// and `reload(globals)` unloads and runs `init` again
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn hot_reload(&mut self, hot_reload: bool) -> &mut Self {
+        self.hot_reload = hot_reload;
+        self
+    }
+
+    /// Controls keeping the original source of every modified script in the output, for debugging inside Studio
+    ///
+    /// * **Default: None**
+    ///
+    /// The source is kept in an attribute or a StringValue child of the script, both named [`ORIGINAL_SOURCE_NAME`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn embed_original(&mut self, embed_original: Option<EmbedOriginal>) -> &mut Self {
+        self.embed_original = embed_original;
+        self
+    }
+
+    /// Controls saving the whole dom instead of only the main script with its descendants
+    ///
+    /// * **Default: false**
+    ///
+    /// Use this if instances in the plugin reference instances outside of it, see [`DomTranspiler::dangling_references`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn save_full_tree(&mut self, save_full_tree: bool) -> &mut Self {
+        self.save_full_tree = save_full_tree;
+        self
+    }
+
+    /// Finds Ref properties in the main script and its descendants that point outside of them,
+    /// these become empty in the output unless the full tree is saved
+    pub fn dangling_references(&self) -> Vec<DanglingReference> {
+        let main = self.tree.get_by_ref(self.source_script).unwrap();
+        let mut instances = vec![(main, String::from("script"))];
+        self.tree.foreach_descendant(
+            main,
+            &mut |child, path| {
+                instances.push((child, path.to_string()));
+                ForEachAction::Continue
+            },
+            0,
+        );
+
+        let saved: HashSet<Ref> = instances.iter().map(|(instance, _)| instance.referent()).collect();
+        let mut dangling = Vec::new();
+        for (instance, path) in &instances {
+            for (property, value) in &instance.properties {
+                if let Variant::Ref(target) = value {
+                    if target.is_some() && !saved.contains(target) {
+                        dangling.push(DanglingReference {
+                            path: path.clone(),
+                            property: property.clone(),
+                            target: self.tree.get_full_name(*target),
+                        });
+                    }
+                }
+            }
+        }
+
+        dangling
+    }
+
+    /// Controls recording the original and transpiled source of every modified script while transpiling
+    ///
+    /// * **Default: false**
+    ///
+    /// Recorded snapshots can be accessed with [`DomTranspiler::snapshots`] or written with [`DomTranspiler::write_snapshots`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn record_snapshots(&mut self, record_snapshots: bool) -> &mut Self {
+        self.record_snapshots = record_snapshots;
+        self
+    }
+
+    /// Snapshots of the scripts modified by the last [`DomTranspiler::transpile_tree`],
+    /// empty unless enabled with [`DomTranspiler::record_snapshots`]
+    pub fn snapshots(&self) -> &[ScriptSnapshot] {
+        &self.snapshots
+    }
+
+    /// Controls recording SHA-256 checksums of the original and transpiled source of every transpiled script
+    ///
+    /// * **Default: false**
+    ///
+    /// The manifest can be accessed with [`DomTranspiler::manifest`] or written with [`DomTranspiler::write_manifest`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn record_manifest(&mut self, record_manifest: bool) -> &mut Self {
+        self.record_manifest = record_manifest;
+        self
+    }
+
+    /// Checksums of the scripts transpiled by the last [`DomTranspiler::transpile_tree`],
+    /// empty unless enabled with [`DomTranspiler::record_manifest`]
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Writes the manifest as JSON to a file path
+    pub fn write_manifest(&self, file_path: &Path) -> Result<(), Problem> {
+        let output = BufWriter::new(File::create(file_path).map_err(|error| Problem::IOError("create the manifest", error))?);
+        serde_json::to_writer_pretty(output, &self.manifest).map_err(|error| Problem::JSONError("the manifest", error))
+    }
+
+    /// Controls whether descendant scripts that fail to transpile abort the whole transpile
+    ///
+    /// * **Default: false** (the first failure is returned as an error)
+    /// * Set to `true` to keep the original source of failed scripts and continue,
+    /// the failures can be accessed with [`DomTranspiler::failures`] or written with [`DomTranspiler::write_failures`]
+    ///
+    /// The main script always has to transpile, as it needs to be wrapped
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn allow_failures(&mut self, allow_failures: bool) -> &mut Self {
+        self.allow_failures = allow_failures;
+        self
+    }
+
+    /// Controls keeping descendant scripts that can't be parsed, like ones with exotic syntax or minified code, untouched
+    ///
+    /// * **Default: false** (a parse failure is handled like other failures, see [`DomTranspiler::allow_failures`])
+    ///
+    /// Scripts that were passed through are marked in their [`ScriptMetrics`] and get a [`rules::UNPARSED`] diagnostic
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn passthrough_unparsed(&mut self, passthrough_unparsed: bool) -> &mut Self {
+        self.passthrough_unparsed = passthrough_unparsed;
+        self
+    }
+
+    /// Controls rendering a code frame of the original source for every diagnostic that points at code
+    ///
+    /// * **Default: false**
+    ///
+    /// The frames are in [`Diagnostic::frame`], errors of scripts that can't be parsed always have them
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn code_frames(&mut self, code_frames: bool) -> &mut Self {
+        self.code_frames = code_frames;
+        self
+    }
+
+    /// Scripts that failed to transpile in the last [`DomTranspiler::transpile_tree`], see [`DomTranspiler::allow_failures`]
+    pub fn failures(&self) -> &[ScriptFailure] {
+        &self.failures
+    }
+
+    /// Writes the failed scripts as a JSON array to a file path
+    pub fn write_failures(&self, file_path: &Path) -> Result<(), Problem> {
+        let output = BufWriter::new(File::create(file_path).map_err(|error| Problem::IOError("create the failure manifest", error))?);
+        serde_json::to_writer_pretty(output, &self.failures).map_err(|error| Problem::JSONError("the failure manifest", error))
+    }
+
+    /// Diagnostics reported by the rules in the last [`DomTranspiler::transpile_tree`],
+    /// see [`report::group_diagnostics`] for grouping repeated ones
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Counts of what the last [`DomTranspiler::transpile_tree`] did, per script and in total
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Controls recording which line of the transpiled source every original line ended up on
+    ///
+    /// * **Default: false**
+    ///
+    /// Source maps let proxy hosts translate stack traces, they can be accessed with [`DomTranspiler::source_maps`]
+    /// or written with [`DomTranspiler::write_source_maps`]. Transpiling is slower, as every script is parsed again
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn record_source_maps(&mut self, record_source_maps: bool) -> &mut Self {
+        self.record_source_maps = record_source_maps;
+        self
+    }
+
+    /// Source maps of the scripts transpiled by the last [`DomTranspiler::transpile_tree`],
+    /// empty unless enabled with [`DomTranspiler::record_source_maps`]
+    pub fn source_maps(&self) -> &[SourceMap] {
+        &self.source_maps
+    }
+
+    /// Writes the recorded source maps as a JSON array to a file path
+    pub fn write_source_maps(&self, file_path: &Path) -> Result<(), Problem> {
+        let output =
+            BufWriter::new(File::create(file_path).map_err(|error| Problem::IOError("create the source map file", error))?);
+        serde_json::to_writer_pretty(output, &self.source_maps).map_err(|error| Problem::JSONError("the source map file", error))
+    }
+
+    /// Controls deriving the capabilities the plugin needs from the host, like `settings` or `toolbar`,
+    /// from the APIs it references, see [`capabilities`]
+    ///
+    /// * **Default: false**
+    ///
+    /// The manifest can be accessed with [`DomTranspiler::capabilities`] or written with [`DomTranspiler::write_capabilities`]
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn record_capabilities(&mut self, record_capabilities: bool) -> &mut Self {
+        self.record_capabilities = record_capabilities;
+        self
+    }
+
+    /// Controls embedding the capability manifest in the output, as an attribute or a module under the main script
+    /// named [`CAPABILITIES_NAME`]. Embedding also records the manifest
+    ///
+    /// * **Default: None**
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn embed_capabilities(&mut self, embed_capabilities: Option<CapabilityEmbed>) -> &mut Self {
+        self.embed_capabilities = embed_capabilities;
+        self
+    }
+
+    /// Capabilities derived by the last [`DomTranspiler::transpile_tree`],
+    /// `None` unless enabled with [`DomTranspiler::record_capabilities`]
+    pub fn capabilities(&self) -> Option<&CapabilityManifest> {
+        self.capabilities.as_ref()
+    }
+
+    /// Report of the last [`DomTranspiler::transpile_tree`] for reviewing it, with the scripts, the rules that fired,
+    /// the diagnostics, the failures and the capabilities if they were recorded
+    pub fn report(&self) -> TranspileReport<'_> {
+        TranspileReport {
+            version: env!("CARGO_PKG_VERSION"),
+            plugin: self.plugin_info.as_ref(),
+            metrics: &self.metrics,
+            diagnostics: &self.diagnostics,
+            failures: &self.failures,
+            capabilities: self.capabilities.as_ref(),
+        }
+    }
+
+    /// Writes the report rendered in a format to a file path, see [`DomTranspiler::report`]
+    pub fn write_report(&self, file_path: &Path, format: ReportFormat) -> Result<(), Problem> {
+        fs::write(file_path, self.report().render(format)).map_err(|error| Problem::IOError("write the report file", error))
+    }
+
+    /// Writes the capability manifest as JSON to a file path, it's empty if capabilities weren't recorded
+    pub fn write_capabilities(&self, file_path: &Path) -> Result<(), Problem> {
+        let output =
+            BufWriter::new(File::create(file_path).map_err(|error| Problem::IOError("create the capabilities file", error))?);
+        serde_json::to_writer_pretty(output, &self.capabilities)
+            .map_err(|error| Problem::JSONError("the capabilities file", error))
+    }
+
+    /// Writes the recorded snapshots as a JSON array to a file path
+    pub fn write_snapshots(&self, file_path: &Path) -> Result<(), Problem> {
+        let output = BufWriter::new(File::create(file_path).map_err(|error| Problem::IOError("create the snapshot file", error))?);
+        serde_json::to_writer_pretty(output, &self.snapshots).map_err(|error| Problem::JSONError("the snapshot file", error))
+    }
+
+    /// Check if path is of a test module or runner, see [`DomTranspiler::test_modules`]
+    fn is_test(test_paths: &[String], p: &str) -> bool {
+        TEST_PATTERNS.iter().any(|pattern| glob_match(pattern, p)) || test_paths.iter().any(|pattern| glob_match(pattern, p))
+    }
+
+    /// Check if path could be a library that does not require plugin access
+    fn is_excluded(exclude_libs: bool, exclude_paths: &[String], p: &str) -> bool {
+        (exclude_libs
+            && (glob_match("**/[Rr][eo]act*/**", p) || glob_match("**/*jsdotlua*/**", p) || glob_match("**/Fusion/**", p)))
+            || exclude_paths.iter().any(|pattern| glob_match(pattern, p))
+    }
+
+    /// Saves the edited dom to a file path
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to save the dom to.<br>
+    /// Extension must be: <br>
+    /// `.rbxm`, `.rbxl` (**binary**) or <br>
+    /// `.rbxmx`, `.rbxlx` (**xml**)
+    ///
+    /// The dom is encoded into a temporary file next to the path that replaces it once it's complete,
+    /// so when saving fails there's no partial file and an existing file at the path is left untouched
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::{error::Problem, DomTranspiler};
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    /// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("Script").with_name("Main"));
+    /// let mut transpiler = DomTranspiler::with_source_script(tree, main).unwrap();
+    ///
+    /// let output = std::env::temp_dir().join("pluginproxy-missing-directory").join("out.rbxm");
+    /// let error = transpiler.save_to_file(&output).unwrap_err();
+    /// assert!(matches!(error, Problem::SaveAborted(..)));
+    /// assert!(error.report().contains("no partial file was left behind"));
+    /// ```
+    pub fn save_to_file(&mut self, file_path: &Path) -> Result<(), Problem> {
+        self.save_to_file_with_format(file_path, RbxFileType::from_path(file_path)?)
+    }
+
+    /// Same as [`DomTranspiler::save_to_file`], but with an explicit format, ignoring the extension of the path
+    pub fn save_to_file_with_format(&mut self, file_path: &Path, extension: RbxFileType) -> Result<(), Problem> {
+        let _span = debug_span!("encode", file = %file_path.display()).entered();
+        let existed = file_path.exists();
+        let mut temp_path = file_path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+
+        let saved = self.save_to_temp_file(&temp_path, extension).and_then(|_| {
+            fs::rename(&temp_path, file_path).map_err(|error| Problem::IOError("replace the output file", error))
+        });
+        if let Err(error) = saved {
+            fs::remove_file(&temp_path).ok();
+            let preserved = match existed {
+                true => "the existing file was left untouched",
+                false => "no partial file was left behind",
+            };
+            return Err(Problem::SaveAborted(file_path.to_path_buf(), preserved, Box::new(error)));
+        }
+        Ok(())
+    }
+
+    /// Encodes the dom into the temporary file of [`DomTranspiler::save_to_file_with_format`],
+    /// making sure everything reached the disk
+    fn save_to_temp_file(&mut self, temp_path: &Path, format: RbxFileType) -> Result<(), Problem> {
+        let file = File::create(temp_path).map_err(|error| Problem::IOError("create the output file", error))?;
+        let mut output = BufWriter::new(file);
+        self.save_to_writer(&mut output, format)?;
+        let file = output
+            .into_inner()
+            .map_err(|error| Problem::IOError("write the output file", error.into_error()))?;
+        file.sync_all().map_err(|error| Problem::IOError("write the output file", error))
+    }
+
+    /// Saves the edited dom to any writer, such as stdout for piping the output into another tool
+    ///
+    /// The save hook is called on the output first, see [`DomTranspiler::on_save`]
+    pub fn save_to_writer<W: Write>(&mut self, output: W, format: RbxFileType) -> Result<(), Problem> {
+        let now = Instant::now();
+
+        self.run_save_hook();
+        let roots = if self.save_full_tree {
+            self.tree.root().children().to_vec()
+        } else {
+            let dangling = self.dangling_references();
+            for reference in &dangling {
+                debug!("{}.{} points to {} outside of the output", reference.path, reference.property, reference.target);
+            }
+            if !dangling.is_empty() {
+                warn!("{} properties point to instances outside of the output and will be empty, save the full tree to keep them", dangling.len());
+            }
+            vec![self.source_script]
+        };
+
+        match format {
+            RbxFileType::XML => rbx_xml::to_writer_default(output, &self.tree, &roots).map_err(Problem::XMLEncodeError),
+            RbxFileType::Binary => rbx_binary::to_writer(output, &self.tree, &roots).map_err(Problem::BinaryEncodeError),
+        }?;
+
+        info!("Encoded in {:.2?}", now.elapsed());
+        Ok(())
+    }
+
+    /// Calls the save hook on every instance in the output, which is the whole tree when
+    /// [`DomTranspiler::save_full_tree`] is on, see [`DomTranspiler::on_save`]
+    fn run_save_hook(&mut self) {
+        let Some(hook) = self.save_hook.as_mut() else {
+            return;
+        };
+
+        let roots = match self.save_full_tree {
+            true => self.tree.root().children().to_vec(),
+            false => vec![self.source_script],
+        };
+        let mut referents = Vec::new();
+        for root in roots {
+            referents.push(root);
+            self.tree.foreach_descendant(
+                self.tree.get_by_ref(root).unwrap(),
+                &mut |child, _| {
+                    referents.push(child.referent());
+                    ForEachAction::Continue
+                },
+                0,
+            );
+        }
+
+        let mut kept = vec![self.source_script];
+        let mut current = self.tree.get_by_ref(self.source_script).unwrap();
+        while let Some(parent) = self.tree.get_by_ref(current.parent()) {
+            kept.push(parent.referent());
+            current = parent;
+        }
+
+        let mut dropped = 0;
+        for referent in referents {
+            // descendants of a dropped instance are already gone
+            let Some(instance) = self.tree.get_by_ref_mut(referent) else {
+                continue;
+            };
+
+            match hook(instance) {
+                KeepOrDrop::Drop if kept.contains(&referent) => warn!("Save hook can't drop the main script or its ancestors"),
+                KeepOrDrop::Drop => {
+                    self.tree.destroy(referent);
+                    dropped += 1;
+                }
+                KeepOrDrop::Keep => {}
+            }
+        }
+
+        if dropped > 0 {
+            info!("Save hook dropped {dropped} instances");
+        }
+    }
+
+    /// Transpiles the entire dom tree, which can then be saved to a file
+    ///
+    /// # Returns
+    /// `Result<&mut Self, Problem>` for method chaining and error handling
+    pub fn transpile_tree(&mut self) -> Result<&mut Self, Problem> {
+        self.transpile_tree_with_progress(|_, _| {})
+    }
+
+    /// Same as [`DomTranspiler::transpile_tree`], but reports progress after every transpiled script
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - function, receives the amount of processed scripts and the total amount,
+    /// excluded scripts count as processed once they're skipped
+    pub fn transpile_tree_with_progress<F>(&mut self, mut progress: F) -> Result<&mut Self, Problem>
+    where
+        F: FnMut(usize, usize),
+    {
+        let _span = debug_span!("transpile_tree").entered();
+        let now = Instant::now();
+        self.snapshots.clear();
+        self.source_maps.clear();
+        self.capabilities = None;
+        self.manifest = Manifest::default();
+        self.source_hash = Sha256::new();
+        self.metrics = Metrics::default();
+        self.failures.clear();
+        self.diagnostics.clear();
+
+        if self.globals_module {
+            let main = self.tree.get_by_ref(self.source_script).unwrap();
+            let conflict = main
+                .children()
+                .iter()
+                .filter_map(|child| self.tree.get_by_ref(*child))
+                .any(|child| child.name == GLOBALS_MODULE_NAME);
+            if conflict {
+                return Err(Problem::GlobalsModuleConflict(String::from(GLOBALS_MODULE_NAME)));
+            }
+
+            // modules find the main script with FindFirstAncestor, which would stop at a descendant with its name
+            let mut shadowing = None;
+            self.tree.foreach_descendant(
+                main,
+                &mut |descendant, _| match descendant.name == main.name {
+                    true => {
+                        shadowing = Some(descendant.referent());
+                        ForEachAction::Break
+                    }
+                    false => ForEachAction::Continue,
+                },
+                0,
+            );
+            if let Some(shadowing) = shadowing {
+                return Err(Problem::MainNameShadowed(main.name.clone(), self.tree.get_full_name(shadowing)));
+            }
+        }
+
+        let markers = self.tree.find_by_attribute("TranspilerVersion", None);
+        if let Some(marker) = markers.iter().filter_map(|marker| self.tree.get_by_ref(*marker)).find(|marker| {
+            marker.name == METADATA_NAME && marker.parent() == self.source_script
+        }) {
+            let version = match marker.properties.get("Attributes") {
+                Some(Variant::Attributes(attributes)) => match attributes.get("TranspilerVersion") {
+                    Some(Variant::String(version)) => version.as_str(),
+                    _ => "unknown",
+                },
+                _ => "unknown",
+            };
+            warn!("The plugin was already transpiled by version {version}, transpiling it again wraps it twice");
+        }
+
+        self.plugin_info = PluginInfo::find(&self.tree, self.source_script);
+        self.options.settings_namespace = match &self.settings_namespace {
+            Some(SettingsNamespace::Custom(namespace)) => Some(namespace.clone()),
+            Some(SettingsNamespace::PluginName) => Some(self.plugin_name()),
+            None => None,
+        };
+        if let Some(info) = &self.plugin_info {
+            info!(
+                "Plugin: {} {}",
+                info.name.as_deref().unwrap_or("(unnamed)"),
+                info.version.as_deref().unwrap_or_default()
+            );
+        }
+        if self.low_memory {
+            self.drop_unsaved_properties();
+        }
+        if !self.strip_paths.is_empty() || self.test_modules == TestModules::Strip {
+            self.strip();
+        }
+        if self.prune_unused {
+            self.prune();
+        }
+        if self.local_scripts_as_modules {
+            let indent = self.options.indent.unit();
+            let converted = local_scripts::convert(&mut self.tree, self.source_script, &self.entry_scripts, &indent);
+            let runs = local_scripts::rewrite_runs(&mut self.tree, self.source_script, &converted);
+            info!("Converted {} LocalScripts into modules, {runs} statements that enable them now run them", converted.len());
+        }
+        let check_cycles = !self.options.disabled_rules.contains(rules::REQUIRE_CYCLE);
+        self.require_graph = (self.record_require_graph || check_cycles).then(|| RequireGraph::build(&self.tree, self.source_script));
+        if let Some(graph) = self.require_graph.as_ref().filter(|_| check_cycles) {
+            for cycle in graph.cycles() {
+                let path = cycle.iter().map(|node| node.path.as_str()).collect::<Vec<_>>().join(" -> ");
+                self.diagnostics.push(Diagnostic {
+                    rule: rules::REQUIRE_CYCLE,
+                    path: cycle[0].path.clone(),
+                    line: None,
+                    span: None,
+                    message: format!("require cycle {path}, it may break once the main script is wrapped in init"),
+                    frame: None,
+                });
+            }
+        }
+
+        // the census is of the original sources, rules rewrite some of the APIs it looks for
+        let census = (self.record_capabilities || self.embed_capabilities.is_some())
+            .then(|| Census::collect(&self.tree, self.source_script));
+
+        let progress_total = count_modules(&self.tree, self.source_script) + self.entry_scripts.len() + 1;
+        let base_options = self.script_options(0);
+        let (exclude_libs, exclude_paths, allow_failures) = (self.exclude_libs, &self.exclude_paths, self.allow_failures);
+        let skip_tests = self.test_modules == TestModules::Skip;
+        let test_paths = &self.test_paths;
+        let main_only = self.main_only;
+        let path_rules = &self.path_rules;
+        let free_originals = self.low_memory && !self.keeps_originals();
+        let passthrough_unparsed = self.passthrough_unparsed;
+        let diagnose_unparsed = !self.options.disabled_rules.contains(rules::UNPARSED);
+
+        let mut visitor = PluginProxyVisitor::new(&base_options);
+        visitor.statement_lines = self.record_source_maps.then(Vec::new);
+        let mut done = 0;
+        let mut processed_scripts = Vec::new();
+        let mut failures = Vec::new();
+        let mut error = None;
+        self.tree.foreach_descendant_mut(
+            self.source_script,
+            &mut |child, path| {
+                if child.class != "ModuleScript" {
+                    return ForEachAction::Continue;
+                }
+                done += 1;
+
+                let path_string = path.path_string();
+                let is_skipped_test = skip_tests && Self::is_test(test_paths, &path_string);
+                if !main_only && !is_skipped_test && !Self::is_excluded(exclude_libs, exclude_paths, &path_string) {
+                    let mut options = TranspileOptions {
+                        path_depth: path.depth(),
+                        ..base_options.clone()
+                    };
+                    apply_path_rules(path_rules, &path_string, &mut options.disabled_rules);
+                    let path = path.to_string();
+                    let processed = match Self::process_script(child, &path, &options, &mut visitor) {
+                        Ok(processed) => Some(processed),
+                        Err(script_error)
+                            if passthrough_unparsed && matches!(script_error.root(), Problem::TranspilerError(_)) =>
+                        {
+                            warn!("Kept the original source of {path}, it couldn't be parsed");
+                            Some(Self::pass_through(child, diagnose_unparsed))
+                        }
+                        Err(script_error) if allow_failures => {
+                            let error = script_error.root().report();
+                            warn!("Kept the original source of {path}, it failed to transpile: {error}");
+                            failures.push(ScriptFailure {
+                                path: path.clone(),
+                                error,
+                            });
+                            None
+                        }
+                        Err(script_error) => {
+                            error = Some(script_error);
+                            return ForEachAction::Break;
+                        }
+                    };
+
+                    if let Some(mut processed) = processed {
+                        if free_originals {
+                            processed.original = String::new();
+                        }
+                        processed_scripts.push((child.referent(), path, processed))
+                    }
+                }
+
+                progress(done, progress_total);
+                ForEachAction::Continue
+            },
+            0,
+        );
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        let transpiled_count = processed_scripts.len() + failures.len();
+        info!("Script total: {}, time: {:.2?}", done, now.elapsed());
+        info!("Skipped {} scripts", done - transpiled_count);
+        self.metrics.skipped = done - transpiled_count;
+
+        self.failures.extend(failures);
+        for (referent, path, processed) in processed_scripts {
+            self.record_script(referent, path, processed);
+        }
+
+        if !self.entry_scripts.is_empty() {
+            self.write_loader_source();
+        }
+        for referent in self.entry_scripts.clone() {
+            let options = self.script_options(0);
+            let script = self.tree.get_by_ref_mut(referent).unwrap();
+            let path = format!("script.{}", script.name);
+            let processed = Self::process_script(script, &path, &options, &mut visitor)?;
+            self.record_script(referent, path, processed);
+            done += 1;
+            progress(done, progress_total);
+        }
+
+        let mut options = self.script_options(0);
+        apply_path_rules(&self.path_rules, &DotPath::default().path_string(), &mut options.disabled_rules);
+        options.reload_modules = self.hot_reload.then(|| self.reload_modules());
+        options.plugin_info = self.plugin_info.clone();
+        options.plugin_id = self.plugin_id.clone();
+        if self.keep_directives {
+            let source = self.tree.get_by_ref(self.source_script).and_then(|script| script.source()).unwrap_or_default();
+            for directive in leading_directives(source) {
+                if !options.directives.contains(&directive) {
+                    options.directives.push(directive);
+                }
+            }
+        }
+        let script = self.tree.get_by_ref_mut(self.source_script).unwrap();
+        let processed = Self::process_script(script, "script", &options, &mut visitor)?;
+        self.record_script(self.source_script, String::from("script"), processed);
+        progress(progress_total, progress_total);
+
+        if self.globals_module {
+            self.insert_globals_module();
+        }
+        if self.record_metadata {
+            self.insert_metadata();
+        }
+        if let Some(plugin_id) = self.plugin_id.clone() {
+            let main = self.tree.get_by_ref_mut(self.source_script).unwrap();
+            main.set_attribute(PLUGIN_ID_NAME, Variant::String(plugin_id));
+        }
+        if let Some(census) = census {
+            let mut capabilities = CapabilityManifest::new(&census, &self.metrics, &self.diagnostics);
+            capabilities.add_rule_capabilities(&self.options.rules, &self.metrics);
+            info!("The plugin needs capabilities: [{}]", capabilities.needs.join(", "));
+            if let Some(embed) = self.embed_capabilities {
+                self.insert_capabilities(embed, &capabilities);
+            }
+            self.capabilities = Some(capabilities);
+        }
+
+        info!("Transpiled in {:.2?}", now.elapsed());
+        if !self.failures.is_empty() {
+            warn!("{} scripts failed to transpile and kept their original source", self.failures.len());
+        }
+
+        Ok(self)
+    }
+
+    /// Sets the source of the synthesized loader, which runs every entry script in order
+    fn write_loader_source(&mut self) {
+        let globals_name = &self.options.globals_name;
+        let mut source = generated_comment_line(self.options.generated_comment.as_deref());
+        for referent in &self.entry_scripts {
+            if let Some(script) = self.tree.get_by_ref(*referent) {
+                let mut path = DotPath::default();
+                path.push(&script.name);
+                source.push_str(&format!("require({}).init({globals_name})\n", path.to_lua(IndexStyle::FindFirstChild)));
+            }
+        }
+
+        let loader = self.tree.get_by_ref_mut(self.source_script).unwrap();
+        loader.set_source(source);
+    }
+
+    /// Inserts the Configuration describing how the output was produced, see [`DomTranspiler::record_metadata`]
+    fn insert_metadata(&mut self) {
+        let transpiled_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |time| time.as_secs_f64());
+        let disabled_rules = self.options.disabled_rules.iter().cloned().collect::<Vec<_>>().join(",");
+
+        let attributes = Attributes::new()
+            .with("TranspilerVersion", String::from(env!("CARGO_PKG_VERSION")))
+            .with("TranspiledAt", transpiled_at)
+            .with("SourceHash", format!("{:x}", self.source_hash.clone().finalize()))
+            .with("GlobalsName", self.options.globals_name.clone())
+            .with("GlobalsModule", self.globals_module)
+            .with("ExcludeLibs", self.exclude_libs)
+            .with("DisabledRules", disabled_rules);
+
+        self.tree.insert(
+            self.source_script,
+            InstanceBuilder::new("Configuration")
+                .with_name(METADATA_NAME)
+                .with_property("Attributes", attributes),
+        );
+    }
+
+    /// Embeds the capability manifest in the main script, see [`DomTranspiler::embed_capabilities`]
+    fn insert_capabilities(&mut self, embed: CapabilityEmbed, capabilities: &CapabilityManifest) {
+        match embed {
+            CapabilityEmbed::Attribute => {
+                let main = self.tree.get_by_ref_mut(self.source_script).unwrap();
+                main.set_attribute(CAPABILITIES_NAME, Variant::String(capabilities.needs.join(",")));
+            }
+            CapabilityEmbed::Module => {
+                let module = self
+                    .tree
+                    .insert(self.source_script, InstanceBuilder::new("ModuleScript").with_name(CAPABILITIES_NAME));
+                let source = capabilities.to_lua_with_comment(self.options.generated_comment.as_deref());
+                self.tree.get_by_ref_mut(module).unwrap().set_source(source);
+            }
+        }
+    }
+
+    /// Inserts the module that returns the plugin globals under the main script, see [`DomTranspiler::globals_module`]
+    fn insert_globals_module(&mut self) {
+        let globals_module = self
+            .tree
+            .insert(self.source_script, InstanceBuilder::new("ModuleScript").with_name(GLOBALS_MODULE_NAME));
+        let main_path = DotPath::relative_to(&self.tree, globals_module, self.source_script).unwrap();
+
+        let source = format!(
+            "{}return require({}).Globals\n",
+            generated_comment_line(self.options.generated_comment.as_deref()),
+            main_path.to_lua(IndexStyle::FindFirstChild)
+        );
+        let globals_module = self.tree.get_by_ref_mut(globals_module).unwrap();
+        globals_module.set_source(source);
+    }
+
+    /// Deletes the instances matching the strip patterns, see [`DomTranspiler::strip_paths`]
+    fn strip(&mut self) {
+        let strip_tests = self.test_modules == TestModules::Strip;
+        let mut stripped = Vec::new();
+        self.tree.foreach_descendant(
+            self.tree.get_by_ref(self.source_script).unwrap(),
+            &mut |child, path| {
+                let path = path.path_string();
+                let is_stripped_test = strip_tests && Self::is_test(&self.test_paths, &path);
+                if is_stripped_test || self.strip_paths.iter().any(|pattern| glob_match(pattern, &path)) {
+                    stripped.push((child.referent(), path));
+                }
+                ForEachAction::Continue
+            },
+            0,
+        );
 
-fn is_replacable_enum<T: HasAffixes>(node: &T) -> bool {
-    node.prefix().identifier().is_some_and(|p| p == "Enum")
-        && node
-            .suffixes()
-            .next()
-            .and_then(|s| s.identifier())
-            .is_some_and(|i| matches!(i, "StudioStyleGuideColor" | "StudioStyleGuideModifier" | "UITheme"))
-}
+        let mut count = 0;
+        for (referent, path) in stripped {
+            // descendants of a stripped instance are already gone
+            if self.tree.get_by_ref(referent).is_some() {
+                debug!("Stripped {path}");
+                self.tree.destroy(referent);
+                count += 1;
+            }
+        }
 
-fn is_settings_call<T: HasAffixes>(node: &T) -> bool {
-    node.prefix().identifier().is_some_and(|p| p == "settings")
-        && node.suffixes().next().map_or(false, |s| matches!(s, Suffix::Call(_)))
-}
+        info!("Stripped {count} instances");
+        self.metrics.stripped = count;
+    }
 
-impl PluginProxyVisitor {
-    pub fn process_common<T: HasAffixes + Node>(&mut self, node: T) -> T {
-        match node {
-            node if is_replacable_enum(&node) => {
-                self.requires.enums = true;
-                node.with_prefix(Prefix::Name(TokenReference::new_identifier("Enums")))
+    /// Removes ModuleScripts that are never required, see [`DomTranspiler::prune_unused`]
+    fn prune(&mut self) {
+        let graph = RequireGraph::build(&self.tree, self.source_script);
+
+        let unresolved = graph.reachable_unresolved();
+        if !unresolved.is_empty() {
+            for (path, require) in &unresolved {
+                debug!("Can't follow require({require}) in {path}");
             }
-            node if is_settings_call(&node) => {
-                self.requires.globals = true;
-                node.with_prefix(Prefix::Name(TokenReference::new_identifier(index_global!("settings"))))
+            warn!("Not pruning unused modules, {} requires can't be followed", unresolved.len());
+            return;
+        }
+
+        let unused = graph.unreachable_modules(&self.tree);
+        for (referent, path) in &unused {
+            debug!("Pruned {path}");
+            if self.tree.get_by_ref(*referent).is_some() {
+                self.tree.destroy(*referent);
             }
-            _ => node,
         }
-    }
-}
 
-impl VisitorMut for PluginProxyVisitor {
-    fn visit_var_expression(&mut self, node: VarExpression) -> VarExpression {
-        self.process_common(node)
+        info!("Pruned {} unused modules", unused.len());
+        self.metrics.pruned = unused.len();
     }
 
-    fn visit_function_call(&mut self, node: FunctionCall) -> FunctionCall {
-        self.process_common(node)
+    /// Whether the original sources are needed after transpiling, for snapshots, the manifest, the metadata hash or embedding
+    fn keeps_originals(&self) -> bool {
+        self.record_snapshots || self.record_manifest || self.record_metadata || self.code_frames || self.embed_original.is_some()
     }
 
-    // Using visit_expression for functions so one can be replaced with just an identifier
-    fn visit_expression(&mut self, node: Expression) -> Expression {
-        // replace script:FindFirstAncestorOfClass('Plugin') with plugin global
-        if let Expression::FunctionCall(function_call) = &node {
-            for suf in function_call.suffixes() {
-                // find a MethodCall in suffixes that searches for "Plugin"
-                if let Suffix::Call(Call::MethodCall(method_call)) = suf {
-                    if let Some(name) = method_call.name().identifier() {
-                        // preserve trivia by grabbing last token from parentheses or string
-                        let token_ref = {
-                            match method_call.args() {
-                                FunctionArgs::Parentheses { parentheses, .. } => parentheses.tokens().1,
-                                FunctionArgs::String(token_ref) => token_ref,
-                                _ => &TokenReference::new(Vec::new(), Token::new(TokenType::Eof), Vec::new()),
-                            }
-                        };
+    /// Clears the properties of instances that aren't saved, only their names and hierarchy are needed
+    /// for resolving paths, see [`DomTranspiler::low_memory`]
+    fn drop_unsaved_properties(&mut self) {
+        if self.save_full_tree {
+            return;
+        }
 
-                        match name {
-                            "FindFirstAncestorOfClass" | "FindFirstAncestorWhichIsA" => {
-                                if nth_arg_string!(method_call.args(), 0).is_some_and(|a| matches!(a, "Plugin")) {
-                                    self.requires.plugin = true;
-                                    return new_identifier_expression("plugin", Some(token_ref));
-                                }
-                            }
-                            "GetService" => {
-                                self.requires.globals = true;
-
-                                let suffixes = vec![
-                                    Suffix::Index(Index::Dot {
-                                        dot: TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Dot }),
-                                        name: TokenReference::new_identifier("game"),
-                                    }),
-                                    Suffix::Call(Call::MethodCall(method_call.clone())),
-                                ];
-
-                                return Expression::FunctionCall(
-                                    FunctionCall::new(Prefix::Name(TokenReference::new_identifier(GLOBAL_VAR_NAME)))
-                                        .with_suffixes(suffixes),
-                                );
-                            }
-                            _ => {}
-                        }
-                    }
+        let mut cleared = 0;
+        let mut stack = vec![self.tree.root_ref()];
+        while let Some(referent) = stack.pop() {
+            if referent == self.source_script {
+                continue;
+            }
+            let Some(instance) = self.tree.get_by_ref_mut(referent) else {
+                continue;
+            };
+            stack.extend(instance.children().iter().copied());
+            if !instance.properties.is_empty() {
+                instance.properties.clear();
+                instance.properties.shrink_to_fit();
+                cleared += 1;
+            }
+        }
+
+        debug!("Cleared the properties of {cleared} instances that aren't saved");
+    }
+
+    /// Lua expressions of the topmost modules under the main script, cloning them also clones the modules inside them
+    fn reload_modules(&self) -> Vec<String> {
+        let mut modules = Vec::new();
+        let mut stack = self.tree.get_by_ref(self.source_script).unwrap().children().to_vec();
+        while let Some(referent) = stack.pop() {
+            let Some(instance) = self.tree.get_by_ref(referent) else {
+                continue;
+            };
+            if instance.class == "ModuleScript" {
+                if let Some(path) = DotPath::relative_to(&self.tree, self.source_script, referent) {
+                    modules.push(path.to_lua(IndexStyle::FindFirstChild));
                 }
+            } else {
+                stack.extend(instance.children().iter().copied());
             }
         }
-        node
+        modules.sort();
+        modules
     }
-}
 
-fn indent_string(s: String) -> String {
-    let mut result = String::with_capacity(s.len() + s.lines().count());
-    let mut is_first_line = true;
-    for line in s.lines() {
-        if !is_first_line {
-            result.push('\n');
+    /// Name of the plugin from its metadata module, or else the name of the top-level instance containing the main script
+    fn plugin_name(&self) -> String {
+        if let Some(name) = self.plugin_info.as_ref().and_then(|info| info.name.clone()) {
+            return name;
         }
-        if !line.is_empty() {
-            result.push('\t');
+        let mut top = self.tree.get_by_ref(self.source_script).unwrap();
+        while let Some(parent) = self.tree.get_by_ref(top.parent()).filter(|parent| parent.referent() != self.tree.root_ref()) {
+            top = parent;
         }
-        result.push_str(line);
-        is_first_line = false;
+        top.name.clone()
     }
-    result
-}
 
-/// Wraps main plugin source with: return { init = function(_proxyGlobals) ... end }
-fn wrap_main_source(ast: Ast) -> String {
-    let code_block = indent_string(full_moon::print(&ast));
+    /// Options for a script at the depth, based on the options of the transpiler
+    fn script_options(&self, depth: usize) -> TranspileOptions {
+        let globals_module = self
+            .globals_module
+            .then(|| self.tree.get_by_ref(self.source_script).map(|main| main.name.clone()))
+            .flatten();
 
-    let init_func = FunctionBody::new()
-        .with_parameters(std::iter::once(Pair::End(Parameter::Name(TokenReference::new_identifier(GLOBAL_VAR_NAME)))).collect())
-        .with_parameters_parentheses(ContainedSpan::new(
-            TokenReference::symbol("(").unwrap(),
-            TokenReference::symbol(")\n").unwrap(),
-        ))
-        .with_block(Block::new().with_last_stmt(Some((
-            LastStmt::Return(Return::new().with_token(TokenReference::new_type(TokenType::Identifier {
-                identifier: ShortString::new(code_block),
-            }))),
-            None,
-        ))));
+        TranspileOptions {
+            path_depth: depth,
+            globals_module,
+            ..self.options.clone()
+        }
+    }
 
-    let mut returns = Punctuated::new();
-    returns.push(Pair::End(Expression::TableConstructor(
-        TableConstructor::new().with_fields(
-            std::iter::once(Pair::End(Field::NameKey {
-                key: TokenReference::new_identifier("init"),
-                equal: TokenReference::new_type(TokenType::Symbol { symbol: Symbol::Equal }).with_trivia(Some(" "), Some(" ")),
-                value: Expression::Function(Box::new((
-                    TokenReference::new_type(TokenType::Symbol {
-                        symbol: Symbol::Function,
-                    }),
-                    init_func,
-                ))),
-            }))
-            .collect(),
-        ),
-    )));
+    /// Adds the script to the metrics, diagnostics and manifest if it's recorded,
+    /// and keeps a snapshot of it if recording is enabled and the transpiler modified it
+    fn record_script(&mut self, referent: Ref, path: String, processed: ProcessedScript) {
+        let Some(transpiled) = self.tree.get_by_ref(referent).and_then(|script| script.source()) else {
+            return;
+        };
 
-    full_moon::print(
-        &ast.with_nodes(Block::new().with_last_stmt(Some((LastStmt::Return(Return::new().with_returns(returns)), None)))),
-    )
-}
+        let code_frames = self.code_frames && !processed.original.is_empty();
+        self.diagnostics.extend(processed.diagnostics.into_iter().map(|diagnostic| Diagnostic {
+            frame: diagnostic
+                .span
+                .filter(|_| code_frames)
+                .map(|span| code_frame(&processed.original, &path, span, &diagnostic.message)),
+            path: path.clone(),
+            ..diagnostic
+        }));
+        self.metrics.add(ScriptMetrics {
+            path: path.clone(),
+            rules: processed.applied.clone(),
+            requirements: processed.requirements,
+            line_offset: processed.line_offset,
+            passed_through: processed.passed_through,
+            minified: processed.minified,
+            bytes_before: processed.bytes_before,
+            bytes_after: transpiled.len(),
+        });
 
-pub struct DomTranspiler {
-    tree: WeakDom,
-    source_script: Ref,
-    exclude_libs: bool,
-}
+        if self.record_metadata {
+            self.source_hash.update(path.as_bytes());
+            self.source_hash.update(processed.original.as_bytes());
+        }
 
-impl DomTranspiler {
-    pub fn new(tree: WeakDom) -> Result<Self, Problem> {
-        let source_script = tree
-            .find_first_child_class(
-                tree.root(),
-                |class| matches!(class, "ModuleScript" | "Script" | "LocalScript"),
-                2,
-            )
-            .ok_or(Problem::NoMainSource)?;
+        if self.record_manifest {
+            self.manifest.scripts.push(ScriptChecksum::new(path.clone(), &processed.original, transpiled));
+        }
 
-        Ok(Self {
-            tree,
-            source_script,
-            exclude_libs: true,
-        })
-    }
+        if let Some(lines) = processed.line_map {
+            self.source_maps.push(SourceMap { path: path.clone(), lines });
+        }
 
-    /// Controls the exclusion of standard libraries that typically don't need plugin access.
-    ///
-    /// * **Default: true** (libraries are excluded)
-    /// * Set to `false` to include all libraries, even standard ones.
-    ///
-    /// Use `false` if your plugin relies on a module that shares a name with
-    /// a standard library (like React or Fusion) and needs plugin-specific methods.
-    ///
-    /// # Affected libraries
-    ///
-    /// (also any descendants of the path to these libraries)
-    /// * React/Roact, jsdotlua descendants
-    /// * Fusion
-    ///
-    /// # Returns
-    /// `&mut Self` for method chaining
-    pub fn exclude_libs(&mut self, exclude_libs: bool) -> &mut Self {
-        self.exclude_libs = exclude_libs;
-        self
-    }
+        let modified = transpiled != processed.original;
+        if self.record_snapshots && modified {
+            self.snapshots.push(ScriptSnapshot {
+                path,
+                original: processed.original.clone(),
+                transpiled: transpiled.to_string(),
+                rules: processed.applied.into_keys().collect(),
+            });
+        }
 
-    /// Check if path could be a library that does not require plugin access
-    fn is_excluded(&self, p: &str) -> bool {
-        self.exclude_libs
-            && (glob_match("**/[Rr][eo]act*/**", p) || glob_match("**/*jsdotlua*/**", p) || glob_match("**/Fusion/**", p))
+        if let Some(embed) = self.embed_original.filter(|_| modified) {
+            self.embed_original_source(referent, embed, processed.original);
+        }
     }
 
-    /// Saves the edited dom to a file path
-    ///
-    /// # Arguments
-    ///
-    /// * `file_path` - Path to save the dom to.<br>
-    /// Extension must be: <br>
-    /// `.rbxm`, `.rbxl` (**binary**) or <br>
-    /// `.rbxmx`, `.rbxlx` (**xml**)
-    pub fn save_to_file(&self, file_path: &Path) -> Result<(), Problem> {
-        let extension = RbxFileType::from_path(file_path)?;
-        let output = BufWriter::new(File::create(file_path).map_err(|error| Problem::IOError("create the output file", error))?);
-
-        match extension {
-            RbxFileType::XML => {
-                rbx_xml::to_writer_default(output, &self.tree, &[self.source_script]).map_err(Problem::XMLEncodeError)
+    /// Keeps the original source of a modified script in the output, see [`DomTranspiler::embed_original`]
+    fn embed_original_source(&mut self, referent: Ref, embed: EmbedOriginal, original: String) {
+        match embed {
+            EmbedOriginal::Attribute => {
+                let script = self.tree.get_by_ref_mut(referent).unwrap();
+                script.set_attribute(ORIGINAL_SOURCE_NAME, Variant::String(original));
             }
-            RbxFileType::Binary => {
-                rbx_binary::to_writer(output, &self.tree, &[self.source_script]).map_err(Problem::BinaryEncodeError)
+            EmbedOriginal::StringValue => {
+                self.tree.insert(
+                    referent,
+                    InstanceBuilder::new("StringValue")
+                        .with_name(ORIGINAL_SOURCE_NAME)
+                        .with_property("Value", original),
+                );
             }
-        }?;
-        Ok(())
+        }
     }
 
-    /// Transpiles the entire dom tree, which can then be saved to a file
-    ///
-    /// # Returns
-    /// `Result<&mut Self, Problem>` for method chaining and error handling
-    pub fn transpile_tree(&mut self) -> Result<&mut Self, Problem> {
-        let now = Instant::now();
-
-        let mut script_stack = Vec::new();
-        let mut total_count: usize = 0;
+    /// Keeps the source of a script that couldn't be parsed, with a diagnostic listing plugin API usage that wasn't rewritten
+    fn pass_through(script: &Instance, diagnose: bool) -> ProcessedScript {
+        let original = script.source().unwrap_or_default().to_string();
+        let mut diagnostics = Vec::new();
+        if diagnose {
+            let unrewritten = UNPARSED_API_PATTERNS
+                .iter()
+                .filter(|pattern| original.contains(**pattern))
+                .copied()
+                .collect::<Vec<_>>();
+            let mut message = String::from("couldn't be parsed, so it was kept untouched");
+            if !unrewritten.is_empty() {
+                message.push_str(&format!(" and plugin API usage in it wasn't rewritten ({})", unrewritten.join(", ")));
+            }
+            diagnostics.push(Diagnostic {
+                rule: rules::UNPARSED,
+                path: String::new(),
+                line: None,
+                span: None,
+                message,
+                frame: None,
+            });
+        }
 
-        self.tree.foreach_descendant(
-            self.tree.get_by_ref(self.source_script).unwrap(),
-            &mut |child, path| {
-                if child.class == "ModuleScript" {
-                    total_count += 1;
-                    if !self.is_excluded(&path.path_string()) {
-                        script_stack.push((child.referent(), path.depth()));
-                    }
-                }
-                ForEachAction::Continue
-            },
-            0,
-        );
+        ProcessedScript {
+            bytes_before: original.len(),
+            original,
+            passed_through: true,
+            minified: false,
+            line_offset: None,
+            line_map: None,
+            requirements: Requirements::default(),
+            applied: BTreeMap::new(),
+            diagnostics,
+        }
+    }
 
-        info!("Script total: {}, time: {:.2?}", total_count, now.elapsed());
-        info!("Skipped {} scripts", total_count.abs_diff(script_stack.len()));
+    fn process_script(
+        script: &mut Instance,
+        path: &str,
+        options: &TranspileOptions,
+        visitor: &mut PluginProxyVisitor,
+    ) -> Result<ProcessedScript, Problem> {
+        let depth = options.path_depth;
+        let _span = trace_span!("transpile_script", path, depth).entered();
+        let now = Instant::now();
 
-        for (referent, depth) in script_stack {
-            let script = self.tree.get_by_ref_mut(referent).unwrap();
-            Self::process_script(script, depth)?;
+        let Some(source) = script.source_mut() else {
+            return Err(Problem::NoScriptSource(script.name.clone()));
+        };
+        // cosmetic options could blow up a script that's one enormous line, and its lines mean little
+        let minified = is_minified(source);
+        let cosmetic_free;
+        let options = if minified {
+            warn!("{path} looks minified or obfuscated, rewrites in it may be incomplete, review it by hand");
+            cosmetic_free = TranspileOptions {
+                preserve_lines: false,
+                combine_locals: false,
+                place_after_requires: false,
+                ..options.clone()
+            };
+            &cosmetic_free
+        } else {
+            options
+        };
+        let transpiled = Self::transpile_script_with_visitor(source, options, visitor).map_err(|error| {
+            let frame = match &error {
+                Problem::TranspilerError(errors) => Some(parse_error_frames(source, path, errors)),
+                _ => None,
+            };
+            Problem::script(path, error).with_frame(frame)
+        })?;
+        let line_map = visitor.statement_lines.as_deref().filter(|_| !minified).and_then(|lines| {
+            let first = usize::from(depth == 0);
+            let start = first + visitor.generated_position;
+            let line_map = map_lines(lines, &transpiled, first, start..start + visitor.generated_statements);
+            if line_map.is_none() {
+                warn!("Couldn't map the lines of {path}, its source map is left out");
+            }
+            line_map
+        });
+        let original = std::mem::replace(source, transpiled);
+        if depth == 0 {
+            script.class = String::from("ModuleScript");
         }
 
-        let script = self.tree.get_by_ref_mut(self.source_script).unwrap();
-        Self::process_script(script, 0)?;
-
-        info!("Transpiled in {:.2?}", now.elapsed());
+        let applied = visitor.applied.iter().map(|(rule, count)| format!("{rule} x{count}")).collect::<Vec<_>>();
+        debug!("Transpiled {path} in {:.2?} [{}]", now.elapsed(), applied.join(", "));
+        let mut diagnostics = std::mem::take(&mut visitor.diagnostics);
+        if minified && !options.disabled_rules.contains(rules::MINIFIED) {
+            diagnostics.push(Diagnostic {
+                rule: rules::MINIFIED,
+                path: String::new(),
+                line: None,
+                span: None,
+                message: String::from("looks minified or obfuscated, cosmetic options were skipped and rewrites may be incomplete"),
+                frame: None,
+            });
+        }
+        Ok(ProcessedScript {
+            passed_through: false,
+            minified,
+            line_map,
+            line_offset: options
+                .preserve_lines
+                .then_some(if depth == 0 { MAIN_LINE_OFFSET + options.directives.len() } else { 0 }),
+            bytes_before: original.len(),
+            original,
+            requirements: Requirements::from(&visitor.requires),
+            applied: std::mem::take(&mut visitor.applied),
+            diagnostics,
+        })
+    }
 
-        Ok(self)
+    /// Transpiles a script's source code into the source that gets saved, the main script (depth 0) is also wrapped
+    ///
+    /// # Arguments
+    ///
+    /// `source` - The source code for a module/script
+    /// `options` - Options for this script
+    ///
+    /// Top-level declarations of `plugin` and the globals that the script already has are reused
+    /// instead of generating a second one
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pluginproxy_transpiler::{DomTranspiler, TranspileOptions};
+    ///
+    /// let options = TranspileOptions { path_depth: 1, ..Default::default() };
+    /// let source = "local plugin = script:FindFirstAncestorOfClass(\"Plugin\")\nprint(plugin)";
+    /// assert_eq!(
+    ///     DomTranspiler::transpile_script(source, &options).unwrap(),
+    ///     "local _proxyGlobals = require(script.Parent).Globals\n-- Autogenerated with PluginProxy Transpiler\n\n\
+    ///      local plugin = _proxyGlobals.plugin\nprint(plugin)"
+    /// );
+    /// ```
+    pub fn transpile_script(source: &str, options: &TranspileOptions) -> Result<String, Problem> {
+        Self::transpile_script_with_visitor(source, options, &mut PluginProxyVisitor::new(options))
     }
 
-    fn process_script(script: &mut Instance, depth: usize) -> Result<(), Problem> {
-        let source = script.properties.get_mut("Source");
-        if let Some(Variant::String(source_string)) = source {
-            if depth == 0 {
-                script.class = String::from("ModuleScript");
-                *source_string = wrap_main_source(Self::transpile_source(source_string, depth)?);
-            } else {
-                *source_string = full_moon::print(&Self::transpile_source(source_string, depth)?);
-            };
+    /// Same as [`DomTranspiler::transpile_script`], with a visitor that keeps what the rules did
+    fn transpile_script_with_visitor(
+        source: &str,
+        options: &TranspileOptions,
+        visitor: &mut PluginProxyVisitor,
+    ) -> Result<String, Problem> {
+        let ast = Self::transpile_ast(source, options, visitor)?;
+        let transpiled = if options.path_depth == 0 {
+            wrap_main_source(ast, options)
+        } else {
+            full_moon::print(&ast)
+        };
 
-            return Ok(());
-        }
-        Err(Problem::NoScriptSource(script.name.clone()))
+        Ok(transpiled)
     }
 
     /// Transpiles a string containing the source code
@@ -330,48 +3951,200 @@ impl DomTranspiler {
     ///
     /// `source` - The source code for a module/script
     /// `path_depth` - The depth of the script in the dom tree, used for requiring the plugin globals
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::DomTranspiler;
+    ///
+    /// let ast = DomTranspiler::transpile_source("game:GetService(\"Selection\"):GetPropertyChangedSignal(\"Name\"):Connect(f)", 1);
+    /// assert!(full_moon::print(&ast.unwrap())
+    ///     .contains("_proxyGlobals.game:GetService(\"Selection\"):GetPropertyChangedSignal(\"Name\"):Connect(f)"));
+    ///
+    /// // UI parented to a local holding PluginGuiService goes to the host's container, unless the local is shadowed
+    /// let source = "local guiService = game:FindService(\"PluginGuiService\")\nframe.Parent = guiService\n\
+    ///     local function show(guiService)\n\tframe.Parent = guiService\nend";
+    /// let source = full_moon::print(&DomTranspiler::transpile_source(source, 1).unwrap());
+    /// assert!(source.contains("frame.Parent = _proxyGlobals.game.PluginGuiService\n"));
+    /// assert!(source.contains("\tframe.Parent = guiService\nend"));
+    /// ```
     pub fn transpile_source(source: &str, path_depth: usize) -> Result<Ast, Problem> {
-        let mut visitor = PluginProxyVisitor::default();
+        Self::transpile_source_with_options(
+            source,
+            &TranspileOptions {
+                path_depth,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Same as [`DomTranspiler::transpile_source`], with all options for the script
+    pub fn transpile_source_with_options(source: &str, options: &TranspileOptions) -> Result<Ast, Problem> {
+        Self::transpile_ast(source, options, &mut PluginProxyVisitor::new(options))
+    }
+
+    /// Transpiles the source with the visitor, which is reset to the options first
+    fn transpile_ast(source: &str, options: &TranspileOptions, visitor: &mut PluginProxyVisitor) -> Result<Ast, Problem> {
+        let path_depth = options.path_depth;
+        visitor.reset(options);
         let mut ast = visitor.visit_ast(full_moon::parse(source).map_err(Problem::TranspilerError)?);
 
-        let mut requires: Vec<(Stmt, Option<TokenReference>)> = Vec::with_capacity(3);
+        let mut requires: Vec<(Stmt, Option<TokenReference>)> = Vec::with_capacity(6);
+        let globals_name = &options.globals_name;
+
+        // declarations the script already has are reused instead of generating a second one, the generated statements
+        // go after its globals, and the asserts need the generated plugin
+        let needs_plugin = visitor.requires.plugin || path_depth == 0;
+        let mut own_globals = (visitor.requires.globals() && path_depth > 0 && !options.preserve_lines)
+            .then(|| {
+                existing_declaration(ast.nodes(), globals_name, |expression| {
+                    expression.to_string().trim_start().starts_with("require(")
+                })
+            })
+            .flatten();
+        let own_plugin = (needs_plugin && !options.debug_runtime)
+            .then(|| {
+                existing_declaration(ast.nodes(), "plugin", |expression| {
+                    matches!(expression, Expression::Symbol(token) if token.identifier() == Some("plugin"))
+                })
+            })
+            .flatten()
+            .filter(|&plugin| match own_globals {
+                Some(globals) => plugin > globals,
+                None => true,
+            });
+        if let Some(globals) = own_globals {
+            let mut generated = Vec::new();
+            if needs_plugin && own_plugin.is_none() {
+                generated.push("plugin");
+            }
+            if visitor.requires.enums {
+                generated.push("Enums");
+            }
+            if ast.nodes().stmts().take(globals).any(|statement| uses_names(statement, &generated)) {
+                own_globals = None;
+            }
+        }
+        if let Some(plugin) = own_plugin {
+            debug!("Reusing the script's own declaration of plugin");
+            let plugin_global = TokenReference::new_identifier(&visitor.index_global("plugin"));
+            let statements = ast
+                .nodes()
+                .stmts_with_semicolon()
+                .enumerate()
+                .map(|(index, (statement, semicolon))| match statement {
+                    Stmt::LocalAssignment(local) if index == plugin => {
+                        // the value is the `plugin` token, its trivia is kept
+                        let value = match local.expressions().iter().next() {
+                            Some(Expression::Symbol(token)) => token.with_token(plugin_global.token().clone()),
+                            _ => plugin_global.clone(),
+                        };
+                        let expressions = std::iter::once(Pair::End(Expression::Symbol(value))).collect();
+                        (Stmt::LocalAssignment(local.clone().with_expressions(expressions)), semicolon.clone())
+                    }
+                    _ => (statement.clone(), semicolon.clone()),
+                })
+                .collect::<Vec<_>>();
+            *ast.nodes_mut() = Block::new()
+                .with_stmts(statements)
+                .with_last_stmt(ast.nodes().last_stmt_with_semicolon().cloned());
+        }
+        if own_globals.is_some() {
+            debug!("Reusing the script's own declaration of {globals_name}");
+        }
 
-        if visitor.requires.globals() && path_depth > 0 {
-            requires.push((Stmt::LocalAssignment(new_global_require(path_depth)), None));
+        if visitor.requires.globals() && path_depth > 0 && own_globals.is_none() {
+            let require = match &options.globals_module {
+                Some(main_name) => new_globals_module_require(&options.globals_name, main_name),
+                None => new_named_global_require(&options.globals_name, path_depth),
+            };
+            requires.push((Stmt::LocalAssignment(require), None));
         }
-        if visitor.requires.plugin || path_depth == 0 {
+        if options.debug_runtime && (visitor.requires.globals() || path_depth == 0) {
+            requires.push((new_capability_assert(globals_name, "the plugin globals"), None));
+            if visitor.applied.contains_key(rules::GET_SERVICE) {
+                requires.push((new_capability_assert(&format!("{globals_name}.game"), "game"), None));
+            }
+        }
+        if needs_plugin && own_plugin.is_none() {
             requires.push((
                 Stmt::LocalAssignment(new_local_assignment(
                     "plugin",
-                    Expression::Symbol(TokenReference::new_identifier(index_global!("plugin")).with_trivia(None, Some("\n"))),
+                    Expression::Symbol(TokenReference::new_identifier(&visitor.index_global("plugin")).with_trivia(None, Some("\n"))),
                 )),
                 None,
-            ))
+            ));
+            if options.debug_runtime {
+                requires.push((new_capability_assert("plugin", "plugin"), None));
+            }
         }
         if visitor.requires.enums {
             requires.push((
                 Stmt::LocalAssignment(new_local_assignment(
                     "Enums",
-                    Expression::Symbol(TokenReference::new_identifier(index_global!("Enums")).with_trivia(None, Some("\n"))),
+                    Expression::Symbol(TokenReference::new_identifier(&visitor.index_global("Enums")).with_trivia(None, Some("\n"))),
                 )),
                 None,
-            ))
+            ));
+            if options.debug_runtime {
+                requires.push((new_capability_assert("Enums", "Enums"), None));
+            }
         }
 
-        if let Some(last_req) = requires.last_mut() {
+        if options.combine_locals {
+            combine_locals(&mut requires, globals_name);
+        }
+
+        visitor.generated_statements = requires.len();
+        if options.preserve_lines && !requires.is_empty() {
+            move_header_comments(ast.nodes_mut(), &mut requires, &options.header_comments);
+            join_on_first_line(&mut requires, options.generated_comment.as_deref());
+            requires.extend(ast.nodes().stmts_with_semicolon().cloned());
+
+            *ast.nodes_mut() = Block::new()
+                .with_stmts(requires)
+                .with_last_stmt(ast.nodes().last_stmt_with_semicolon().cloned());
+        } else if let Some(last_req) = requires.last_mut() {
             *last_req = (
                 last_req
                     .0
-                    .update_trailing_trivia(FormatTriviaType::Append(vec![Token::new(TokenType::SingleLineComment {
-                        comment: ShortString::new(" Autogenerated with PluginProxy Transpiler\n\n"),
-                    })])),
+                    .update_trailing_trivia(FormatTriviaType::Append(vec![match &options.generated_comment {
+                        Some(comment) => Token::new(TokenType::SingleLineComment {
+                            comment: ShortString::new(format!(" {}\n\n", single_line(comment))),
+                        }),
+                        None => Token::new(TokenType::Whitespace {
+                            characters: ShortString::new("\n"),
+                        }),
+                    }])),
                 None,
             );
 
-            requires.extend(ast.nodes().stmts_with_semicolon().cloned());
+            let position = match own_globals {
+                Some(globals) => globals + 1,
+                None if options.place_after_requires => {
+                    let generated = requires
+                        .iter()
+                        .filter_map(|(statement, _)| match statement {
+                            Stmt::LocalAssignment(local) => Some(local.names().iter().map(|name| name.token().to_string())),
+                            _ => None,
+                        })
+                        .flatten()
+                        .collect::<Vec<_>>();
+                    leading_requires(ast.nodes(), &generated, globals_name)
+                }
+                None => 0,
+            };
+            visitor.generated_position = position;
+            if position == 0 {
+                move_header_comments(ast.nodes_mut(), &mut requires, &options.header_comments);
+            }
+
+            let mut statements = ast.nodes().stmts_with_semicolon().take(position).cloned().collect::<Vec<_>>();
+            statements.extend(requires);
+            statements.extend(ast.nodes().stmts_with_semicolon().skip(position).cloned());
 
             *ast.nodes_mut() = Block::new()
-                .with_stmts(requires)
+                .with_stmts(statements)
                 .with_last_stmt(ast.nodes().last_stmt_with_semicolon().cloned());
         }
 
@@ -379,11 +4152,31 @@ impl DomTranspiler {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RbxFileType {
     XML,
     Binary,
 }
 impl RbxFileType {
+    /// Determines the format from the header of the file contents, `None` if it isn't a Roblox file
+    pub fn from_contents(contents: &[u8]) -> Option<RbxFileType> {
+        if contents.starts_with(b"<roblox!") {
+            Some(RbxFileType::Binary)
+        } else if contents.trim_ascii_start().starts_with(b"<roblox") {
+            Some(RbxFileType::XML)
+        } else {
+            None
+        }
+    }
+
+    /// Uses the format if one is given, otherwise determines it from the extension of the path
+    pub fn from_path_or(file_path: &Path, format: Option<RbxFileType>) -> Result<RbxFileType, Problem> {
+        match format {
+            Some(format) => Ok(format),
+            None => Self::from_path(file_path),
+        }
+    }
+
     pub fn from_path(file_path: &Path) -> Result<RbxFileType, Problem> {
         match file_path.extension().map(|extension| extension.to_string_lossy()) {
             Some(Cow::Borrowed("rbxmx")) | Some(Cow::Borrowed("rbxlx")) => Ok(RbxFileType::XML),
@@ -393,19 +4186,81 @@ impl RbxFileType {
     }
 }
 
+impl std::str::FromStr for RbxFileType {
+    type Err = String;
+
+    /// Parses a format name (`binary`, `xml`) or extension (`rbxm`, `rbxmx`, ...)
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.trim_start_matches('.').to_ascii_lowercase().as_str() {
+            "xml" | "rbxmx" | "rbxlx" => Ok(RbxFileType::XML),
+            "binary" | "rbxm" | "rbxl" => Ok(RbxFileType::Binary),
+            _ => Err(format!("unknown format '{format}', expected binary, xml or an rbx file extension")),
+        }
+    }
+}
+
 pub fn from_dom(tree: WeakDom) -> Result<DomTranspiler, Problem> {
     DomTranspiler::new(tree)
 }
 
 pub fn from_file(file_path: &PathBuf) -> Result<DomTranspiler, Problem> {
+    DomTranspiler::new(decode_file(file_path)?)
+}
+
+/// Decodes a binary or xml file into a dom, without looking for a plugin in it
+pub fn decode_file(file_path: &Path) -> Result<WeakDom, Problem> {
+    decode_file_with_format(file_path, RbxFileType::from_path(file_path)?)
+}
+
+/// Same as [`decode_file`], but with an explicit format, ignoring the extension of the path
+pub fn decode_file_with_format(file_path: &Path, format: RbxFileType) -> Result<WeakDom, Problem> {
     let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
     let file_source = BufReader::new(fs::File::open(file_path).map_err(|error| Problem::IOError("read the place file", error))?);
 
+    let _span = debug_span!("decode", file = %file_name).entered();
     info!("Decoding {file_name}...");
-    let tree = match RbxFileType::from_path(file_path)? {
-        RbxFileType::XML => rbx_xml::from_reader_default(file_source).map_err(Problem::XMLDecodeError),
-        RbxFileType::Binary => rbx_binary::from_reader(file_source).map_err(Problem::BinaryDecodeError),
+    decode_reader(file_source, format)
+}
+
+/// Keeps only the instance at the full name, like `Workspace.Plugins.MyPlugin`, with its descendants and ancestors
+///
+/// Everything else is destroyed and the properties of the ancestors are cleared, so large assets in a place
+/// like meshes and terrain that aren't part of the plugin are freed right after decoding
+pub fn keep_only_path(tree: &mut WeakDom, full_name: &str) -> Result<(), Problem> {
+    let mut current = tree.root_ref();
+    let mut ancestors = Vec::new();
+    let mut removed = Vec::new();
+    for name in full_name.split('.') {
+        let mut found = None;
+        for child in tree.get_by_ref(current).unwrap().children().iter().copied() {
+            match tree.get_by_ref(child) {
+                Some(instance) if found.is_none() && instance.name == name => found = Some(child),
+                _ => removed.push(child),
+            }
+        }
+        ancestors.push(current);
+        current = found.ok_or_else(|| Problem::InstanceNotFound(full_name.to_string()))?;
+    }
+
+    for ancestor in ancestors {
+        tree.get_by_ref_mut(ancestor).unwrap().properties.clear();
+    }
+    for referent in &removed {
+        tree.destroy(*referent);
+    }
+
+    info!("Kept only {full_name}, removed {} instances and their descendants", removed.len());
+    Ok(())
+}
+
+/// Decodes a binary or xml dom from any reader, such as a buffer or stdin
+pub fn decode_reader<R: Read>(reader: R, format: RbxFileType) -> Result<WeakDom, Problem> {
+    let now = Instant::now();
+    let tree = match format {
+        RbxFileType::XML => rbx_xml::from_reader_default(reader).map_err(Problem::XMLDecodeError),
+        RbxFileType::Binary => rbx_binary::from_reader(reader).map_err(Problem::BinaryDecodeError),
     }?;
+    info!("Decoded in {:.2?}", now.elapsed());
 
-    DomTranspiler::new(tree)
+    Ok(tree)
 }
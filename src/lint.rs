@@ -0,0 +1,121 @@
+//! Structural checks of a plugin that are independent of transpiling
+
+use std::collections::BTreeMap;
+
+use rbx_dom_weak::{Instance, WeakDom};
+use serde::Serialize;
+
+use crate::{
+    dom::{
+        extension::{ForEachAction, InstanceExt, WeakDomExt},
+        rbx_path::{is_valid_identifier, DotPath},
+    },
+    is_script_class, main_script_candidates,
+};
+
+/// Nesting depth below the main script past which an instance is reported
+pub const MAX_NESTING_DEPTH: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintKind {
+    /// No script was found that could be the main plugin script
+    MissingMainScript,
+    /// A script has no Source property
+    MissingSource,
+    /// An instance is nested deeper than [`MAX_NESTING_DEPTH`]
+    DeepNesting,
+    /// Siblings share a name, so a path to them is ambiguous
+    DuplicateName,
+    /// A name on the path to a script can't be indexed with a dot
+    NonIdentifierName,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    /// Path of the instance in the format of script.Parent.Child, `script` is the main script
+    pub path: String,
+    pub message: String,
+}
+
+/// Checks the plugin in the dom for structural issues
+pub fn lint(tree: &WeakDom) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    let Some(main) = main_script_candidates(tree).first().and_then(|main| tree.get_by_ref(*main)) else {
+        issues.push(LintIssue {
+            kind: LintKind::MissingMainScript,
+            path: String::new(),
+            message: String::from("no script was found near the root that could be the main plugin script"),
+        });
+        return issues;
+    };
+
+    lint_script(main, &DotPath::default(), &mut issues);
+    lint_children(tree, main, &DotPath::default(), &mut issues);
+
+    tree.foreach_descendant(
+        main,
+        &mut |child, path| {
+            if is_script_class(&child.class) {
+                lint_script(child, path, &mut issues);
+            }
+            lint_children(tree, child, path, &mut issues);
+
+            if path.depth() == MAX_NESTING_DEPTH + 1 {
+                issues.push(LintIssue {
+                    kind: LintKind::DeepNesting,
+                    path: path.to_string(),
+                    message: format!("nested more than {MAX_NESTING_DEPTH} levels below the main script"),
+                });
+            }
+            ForEachAction::Continue
+        },
+        0,
+    );
+
+    issues
+}
+
+fn lint_script(script: &Instance, path: &DotPath, issues: &mut Vec<LintIssue>) {
+    if script.source().is_none() {
+        issues.push(LintIssue {
+            kind: LintKind::MissingSource,
+            path: path.to_string(),
+            message: format!("{} has no Source", script.class),
+        });
+    }
+
+    if let Some(name) = path.components().iter().find(|name| !is_valid_identifier(name)) {
+        issues.push(LintIssue {
+            kind: LintKind::NonIdentifierName,
+            path: path.to_string(),
+            message: format!("'{name}' is not a valid identifier, requiring this script needs brackets or FindFirstChild"),
+        });
+    }
+}
+
+/// Reports children that share a name, if any of them is or contains a script
+fn lint_children(tree: &WeakDom, parent: &Instance, path: &DotPath, issues: &mut Vec<LintIssue>) {
+    let mut by_name: BTreeMap<&str, Vec<&Instance>> = BTreeMap::new();
+    for child in parent.children().iter().filter_map(|child| tree.get_by_ref(*child)) {
+        by_name.entry(child.name.as_str()).or_default().push(child);
+    }
+
+    for (name, siblings) in by_name.into_iter().filter(|(_, siblings)| siblings.len() > 1) {
+        let has_script = siblings.iter().any(|sibling| {
+            is_script_class(&sibling.class) || tree.find_first_child_class(sibling, is_script_class, 0).is_some()
+        });
+
+        if has_script {
+            let mut child_path = path.clone();
+            child_path.push(name);
+            issues.push(LintIssue {
+                kind: LintKind::DuplicateName,
+                path: child_path.to_string(),
+                message: format!("{} siblings are named '{name}', a path to them is ambiguous", siblings.len()),
+            });
+        }
+    }
+}
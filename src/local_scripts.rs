@@ -0,0 +1,165 @@
+//! Nested LocalScripts converted into ModuleScripts that run on demand, since LocalScripts can't run inside proxy hosts
+//!
+//! Every LocalScript below the main script becomes a ModuleScript returning `{ run = function() ... end }`,
+//! and enabling one with `script.Template.Runner.Disabled = false` or `.Enabled = true` becomes
+//! `require(script.Template.Runner).run()`
+
+use std::{collections::HashSet, ops::Range};
+
+use full_moon::{
+    ast::*,
+    node::Node,
+    tokenizer::{Symbol, TokenType},
+    visitors::Visitor,
+};
+use rbx_dom_weak::{types::Ref, WeakDom};
+
+use crate::{
+    dom::extension::{AffixExt, ForEachAction, InstanceExt, WeakDomExt},
+    graph::{affixes_path, resolve},
+    indent_string, is_script_class,
+};
+
+/// Converts the LocalScripts below the main script into modules, apart from the `skipped` ones
+///
+/// # Returns
+/// The converted scripts
+pub(crate) fn convert(tree: &mut WeakDom, main: Ref, skipped: &[Ref], indent: &str) -> HashSet<Ref> {
+    let mut converted = HashSet::new();
+    tree.foreach_descendant(
+        tree.get_by_ref(main).unwrap(),
+        &mut |child, _| {
+            if child.class == "LocalScript" && !skipped.contains(&child.referent()) {
+                converted.insert(child.referent());
+            }
+            ForEachAction::Continue
+        },
+        0,
+    );
+
+    for referent in &converted {
+        let script = tree.get_by_ref_mut(*referent).unwrap();
+        script.class = String::from("ModuleScript");
+        for property in ["Disabled", "Enabled", "RunContext"] {
+            script.properties.remove(property);
+        }
+        if let Some(source) = script.source_mut() {
+            *source = format!("return {{ run = function()\n{}\nend }}\n", indent_string(std::mem::take(source), indent));
+        }
+    }
+
+    converted
+}
+
+/// Rewrites enabling the converted scripts into running them, in the main script and every script below it
+///
+/// # Returns
+/// Amount of rewritten statements
+pub(crate) fn rewrite_runs(tree: &mut WeakDom, main: Ref, converted: &HashSet<Ref>) -> usize {
+    let mut scripts = vec![main];
+    tree.foreach_descendant(
+        tree.get_by_ref(main).unwrap(),
+        &mut |child, _| {
+            if is_script_class(&child.class) {
+                scripts.push(child.referent());
+            }
+            ForEachAction::Continue
+        },
+        0,
+    );
+
+    let mut count = 0;
+    for script in scripts {
+        let Some(source) = tree.get_by_ref(script).and_then(|script| script.source()) else {
+            continue;
+        };
+        if !source.contains("Disabled") && !source.contains("Enabled") {
+            continue;
+        }
+        // scripts that can't be parsed are reported when they're transpiled
+        let Ok(ast) = full_moon::parse(source) else {
+            continue;
+        };
+
+        let mut finder = RunFinder {
+            tree,
+            script,
+            converted,
+            source,
+            runs: Vec::new(),
+        };
+        finder.visit_ast(&ast);
+        if finder.runs.is_empty() {
+            continue;
+        }
+
+        let mut runs = finder.runs;
+        let mut source = source.to_owned();
+        runs.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+        count += runs.len();
+        for (range, replacement) in runs {
+            source.replace_range(range, &replacement);
+        }
+        *tree.get_by_ref_mut(script).unwrap().source_mut().unwrap() = source;
+    }
+
+    count
+}
+
+/// Finds statements that enable a converted script, with what replaces them
+struct RunFinder<'a> {
+    tree: &'a WeakDom,
+    script: Ref,
+    converted: &'a HashSet<Ref>,
+    source: &'a str,
+    runs: Vec<(Range<usize>, String)>,
+}
+
+impl RunFinder<'_> {
+    /// Source of the path to the enabled script, `None` if it isn't enabling a converted script
+    fn enabled_script(&self, node: &Assignment) -> Option<String> {
+        if node.variables().len() != 1 || node.expressions().len() != 1 {
+            return None;
+        }
+        let (Some(Var::Expression(var)), Some(Expression::Symbol(value))) =
+            (node.variables().iter().next(), node.expressions().iter().next())
+        else {
+            return None;
+        };
+
+        let suffixes = var.suffixes().collect::<Vec<_>>();
+        let (property, path_suffixes) = suffixes.split_last()?;
+        let enables = match (property.identifier(), value.token_type()) {
+            (Some("Disabled"), TokenType::Symbol { symbol: Symbol::False }) => true,
+            (Some("Enabled"), TokenType::Symbol { symbol: Symbol::True }) => true,
+            _ => false,
+        };
+        if !enables {
+            return None;
+        }
+
+        let path = affixes_path(var.prefix(), path_suffixes.iter().copied())?;
+        let target = resolve(self.tree, self.script, &path)?;
+        if !self.converted.contains(&target) {
+            return None;
+        }
+
+        let start = var.start_position()?.bytes();
+        let end = match path_suffixes.last() {
+            Some(suffix) => suffix.end_position()?,
+            None => var.prefix().end_position()?,
+        };
+        Some(self.source[start..end.bytes()].to_owned())
+    }
+}
+
+impl Visitor for RunFinder<'_> {
+    fn visit_assignment(&mut self, node: &Assignment) {
+        let Some(path) = self.enabled_script(node) else {
+            return;
+        };
+        if let Some((start, end)) = node.range() {
+            self.runs.push((start.bytes()..end.bytes(), format!("require({path}).run()")));
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Async wrappers around the blocking transpiler API
+//!
+//! Decoding, transpiling and encoding a plugin can take several seconds, so each step is
+//! moved onto tokio's blocking thread pool instead of stalling the executor.
+
+use std::path::PathBuf;
+
+use tokio::task;
+
+use crate::{error::Problem, DomTranspiler};
+
+/// Async version of [`crate::from_file`]
+pub async fn from_file(file_path: PathBuf) -> Result<DomTranspiler, Problem> {
+    task::spawn_blocking(move || crate::from_file(&file_path))
+        .await
+        .map_err(Problem::TaskError)?
+}
+
+/// Async version of [`DomTranspiler::transpile_tree_with_progress`]
+///
+/// Takes ownership of the transpiler for the duration of the task and hands it back once done
+///
+/// # Arguments
+///
+/// * `progress` - function, receives the amount of transpiled scripts and the total amount to transpile,
+/// called from the blocking thread
+pub async fn transpile_tree<F>(mut transpiler: DomTranspiler, progress: F) -> Result<DomTranspiler, Problem>
+where
+    F: FnMut(usize, usize) + Send + 'static,
+{
+    task::spawn_blocking(move || -> Result<DomTranspiler, Problem> {
+        transpiler.transpile_tree_with_progress(progress)?;
+        Ok(transpiler)
+    })
+    .await
+    .map_err(Problem::TaskError)?
+}
+
+/// Async version of [`DomTranspiler::save_to_file`]
+///
+/// Takes ownership of the transpiler for the duration of the task and hands it back once done
+pub async fn save_to_file(mut transpiler: DomTranspiler, file_path: PathBuf) -> Result<DomTranspiler, Problem> {
+    task::spawn_blocking(move || -> Result<DomTranspiler, Problem> {
+        transpiler.save_to_file(&file_path)?;
+        Ok(transpiler)
+    })
+    .await
+    .map_err(Problem::TaskError)?
+}
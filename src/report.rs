@@ -0,0 +1,476 @@
+//! Information collected while transpiling, for reviewing what the transpiler did
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{capabilities::CapabilityManifest, frame::Span, info::PluginInfo};
+
+/// Original and transpiled source of a script that was modified by the transpiler
+#[derive(Clone, Debug, Serialize)]
+pub struct ScriptSnapshot {
+    /// Path of the script in the format of script.Parent.Child, `script` is the main script
+    pub path: String,
+    pub original: String,
+    pub transpiled: String,
+    /// Names of the rules that rewrote something in the script, see [`crate::rules`]
+    pub rules: Vec<&'static str>,
+}
+
+/// Lines of a script's original statements mapped to their lines in the transpiled source, for translating stack traces
+#[derive(Clone, Debug, Serialize)]
+pub struct SourceMap {
+    /// Path of the script in the format of script.Parent.Child, `script` is the main script
+    pub path: String,
+    /// Original line mapped to the transpiled line of the first statement starting on it
+    pub lines: BTreeMap<usize, usize>,
+}
+
+/// Something noteworthy found by a rule, that needs attention from the user
+#[derive(Clone, Debug, Serialize)]
+pub struct Diagnostic {
+    /// Name of the rule that reported it, see [`crate::rules`]
+    pub rule: &'static str,
+    /// Path of the script in the format of script.Parent.Child
+    pub path: String,
+    /// Line in the original source
+    pub line: Option<usize>,
+    /// Where in the original source, for the code frame
+    #[serde(skip)]
+    pub span: Option<Span>,
+    pub message: String,
+    /// Code frame of the span, when enabled with [`crate::DomTranspiler::code_frames`]
+    #[serde(skip)]
+    pub frame: Option<String>,
+}
+
+/// Diagnostics with the same rule, script and message
+#[derive(Clone, Debug, Serialize)]
+pub struct DiagnosticGroup<'a> {
+    pub rule: &'static str,
+    pub path: &'a str,
+    pub message: &'a str,
+    /// Lines of every diagnostic in the group, that have one
+    pub lines: Vec<usize>,
+    pub count: usize,
+}
+
+/// Groups repeated diagnostics by rule, script and message, in the order they were first reported
+pub fn group_diagnostics(diagnostics: &[Diagnostic]) -> Vec<DiagnosticGroup<'_>> {
+    let mut groups: Vec<DiagnosticGroup> = Vec::new();
+    let mut indices: BTreeMap<(&str, &str, &str), usize> = BTreeMap::new();
+
+    for diagnostic in diagnostics {
+        let key = (diagnostic.rule, diagnostic.path.as_str(), diagnostic.message.as_str());
+        let index = *indices.entry(key).or_insert_with(|| {
+            groups.push(DiagnosticGroup {
+                rule: diagnostic.rule,
+                path: &diagnostic.path,
+                message: &diagnostic.message,
+                lines: Vec::new(),
+                count: 0,
+            });
+            groups.len() - 1
+        });
+
+        let group = &mut groups[index];
+        group.count += 1;
+        group.lines.extend(diagnostic.line);
+    }
+
+    groups
+}
+
+/// A Ref property in the output that points to an instance which isn't saved with it
+#[derive(Clone, Debug, Serialize)]
+pub struct DanglingReference {
+    /// Path of the instance with the property in the format of script.Parent.Child
+    pub path: String,
+    pub property: String,
+    /// Full name of the instance the property points to, like `Instance:GetFullName()`
+    pub target: String,
+}
+
+/// A script that failed to transpile and was left with its original source
+#[derive(Clone, Debug, Serialize)]
+pub struct ScriptFailure {
+    /// Path of the script in the format of script.Parent.Child
+    pub path: String,
+    pub error: String,
+}
+
+/// Capabilities that transpiled code needs from the host at runtime, so hosts can gate loading
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Requirements {
+    /// Requires the globals of the main script
+    pub globals: bool,
+    /// Uses the plugin object
+    pub plugin: bool,
+    /// Uses `Enum` items that are emulated by the globals
+    pub enums: bool,
+    /// Gets change signals of proxied objects, like `settings().Studio:GetPropertyChangedSignal("Theme")`
+    pub signals: bool,
+}
+
+impl Requirements {
+    /// Adds the requirements of `other` to these
+    pub fn merge(&mut self, other: Requirements) {
+        self.globals |= other.globals;
+        self.plugin |= other.plugin;
+        self.enums |= other.enums;
+        self.signals |= other.signals;
+    }
+
+    /// Names of the capabilities that are required
+    pub fn names(&self) -> Vec<&'static str> {
+        [(self.globals, "globals"), (self.plugin, "plugin"), (self.enums, "Enums"), (self.signals, "signals")]
+            .into_iter()
+            .filter_map(|(required, name)| required.then_some(name))
+            .collect()
+    }
+}
+
+/// Counts of what the transpiler did to a single script
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ScriptMetrics {
+    /// Path of the script in the format of script.Parent.Child, `script` is the main script
+    pub path: String,
+    /// Amount of rewrites done by each rule, see [`crate::rules`]
+    pub rules: BTreeMap<&'static str, usize>,
+    /// What the transpiled script needs at runtime
+    pub requirements: Requirements,
+    /// Lines that the original statements were moved down by, when lines are preserved,
+    /// see [`crate::TranspileOptions::preserve_lines`]
+    pub line_offset: Option<usize>,
+    /// Kept untouched because it couldn't be parsed, see [`crate::DomTranspiler::passthrough_unparsed`]
+    pub passed_through: bool,
+    /// Looks minified or obfuscated, so cosmetic options were skipped and rewrites may be incomplete,
+    /// see [`crate::is_minified`]
+    pub minified: bool,
+    /// Length of the source before transpiling
+    pub bytes_before: usize,
+    /// Length of the source after transpiling
+    pub bytes_after: usize,
+}
+
+/// Counts of what the transpiler did, per script and in total
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Metrics {
+    /// Metrics of every transpiled script, in the order they were transpiled
+    pub scripts: Vec<ScriptMetrics>,
+    /// Total amount of rewrites done by each rule
+    pub rules: BTreeMap<&'static str, usize>,
+    /// What the whole plugin needs at runtime
+    pub requirements: Requirements,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    /// Amount of scripts that were excluded from transpiling
+    pub skipped: usize,
+    /// Amount of instances that were deleted by path, see [`crate::DomTranspiler::strip_paths`]
+    pub stripped: usize,
+    /// Amount of unused modules that were removed, see [`crate::DomTranspiler::prune_unused`]
+    pub pruned: usize,
+}
+
+impl Metrics {
+    pub(crate) fn add(&mut self, script: ScriptMetrics) {
+        for (rule, count) in &script.rules {
+            *self.rules.entry(*rule).or_default() += count;
+        }
+        self.requirements.merge(script.requirements);
+        self.bytes_before += script.bytes_before;
+        self.bytes_after += script.bytes_after;
+        self.scripts.push(script);
+    }
+
+    /// Total amount of rewrites done by all rules
+    pub fn total_rewrites(&self) -> usize {
+        self.rules.values().sum()
+    }
+
+    /// Fields of the plugin globals that the transpiled code indexes, the main script always uses `plugin`
+    pub fn required_globals(&self) -> Vec<&'static str> {
+        let mut globals = vec!["plugin"];
+        if self.requirements.enums {
+            globals.push("Enums");
+        }
+        if self.rules.contains_key(crate::rules::SETTINGS) {
+            globals.push("settings");
+        }
+        if self.rules.contains_key(crate::rules::GET_SERVICE) {
+            globals.push("game");
+        }
+        globals
+    }
+}
+
+/// SHA-256 checksums of a script's source before and after transpiling, as lowercase hex
+#[derive(Clone, Debug, Serialize)]
+pub struct ScriptChecksum {
+    /// Path of the script in the format of script.Parent.Child, `script` is the main script
+    pub path: String,
+    pub original: String,
+    pub transpiled: String,
+}
+
+impl ScriptChecksum {
+    pub(crate) fn new(path: String, original: &str, transpiled: &str) -> Self {
+        Self {
+            path,
+            original: sha256(original),
+            transpiled: sha256(transpiled),
+        }
+    }
+}
+
+fn sha256(source: &str) -> String {
+    format!("{:x}", Sha256::digest(source.as_bytes()))
+}
+
+/// Checksums of every transpiled script, for verifying that a distributed output wasn't modified
+#[derive(Clone, Debug, Serialize)]
+pub struct Manifest {
+    /// Version of the transpiler that produced the output
+    pub version: &'static str,
+    pub scripts: Vec<ScriptChecksum>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            scripts: Vec::new(),
+        }
+    }
+}
+
+/// Format of a [`TranspileReport`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportFormat {
+    #[serde(alias = "markdown")]
+    Md,
+    Html,
+    Json,
+}
+
+impl ReportFormat {
+    /// Extension of report files in the format, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Md => "md",
+            ReportFormat::Html => "html",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(ReportFormat::Md),
+            "html" => Ok(ReportFormat::Html),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unknown report format '{format}', expected md, html or json")),
+        }
+    }
+}
+
+/// Everything about a transpiled plugin that a review needs, like when attaching it to a pull request,
+/// see [`crate::DomTranspiler::report`]
+#[derive(Clone, Debug, Serialize)]
+pub struct TranspileReport<'a> {
+    /// Version of the transpiler that produced the report
+    pub version: &'static str,
+    pub plugin: Option<&'a PluginInfo>,
+    pub metrics: &'a Metrics,
+    pub diagnostics: &'a [Diagnostic],
+    pub failures: &'a [ScriptFailure],
+    /// Only when capabilities were recorded, see [`crate::DomTranspiler::record_capabilities`]
+    pub capabilities: Option<&'a CapabilityManifest>,
+}
+
+/// A titled table of a report, rendered the same way in every human-readable format
+struct Section {
+    title: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl TranspileReport<'_> {
+    /// Renders the report in a format
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::report::{Metrics, ReportFormat, TranspileReport};
+    ///
+    /// let metrics = Metrics::default();
+    /// let report = TranspileReport {
+    ///     version: "1.0.0",
+    ///     plugin: None,
+    ///     metrics: &metrics,
+    ///     diagnostics: &[],
+    ///     failures: &[],
+    ///     capabilities: None,
+    /// };
+    /// assert!(report.render(ReportFormat::Md).starts_with("# PluginProxy Transpiler report"));
+    /// assert!(report.render(ReportFormat::Html).contains("<h2>Summary</h2>"));
+    /// assert!(report.render(ReportFormat::Json).contains("\"version\": \"1.0.0\""));
+    /// ```
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Md => self.to_markdown(),
+            ReportFormat::Html => self.to_html(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.plugin {
+            Some(PluginInfo { name: Some(name), version, .. }) => {
+                format!("{name} {}", version.as_deref().unwrap_or_default()).trim_end().to_string()
+            }
+            _ => String::from("Unnamed plugin"),
+        }
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let metrics = self.metrics;
+        let mut summary = vec![
+            vec![String::from("Scripts"), metrics.scripts.len().to_string()],
+            vec![String::from("Rewrites"), metrics.total_rewrites().to_string()],
+            vec![String::from("Skipped"), metrics.skipped.to_string()],
+            vec![String::from("Stripped"), metrics.stripped.to_string()],
+            vec![String::from("Pruned"), metrics.pruned.to_string()],
+            vec![String::from("Bytes"), format!("{} -> {}", metrics.bytes_before, metrics.bytes_after)],
+            vec![String::from("Needs"), metrics.requirements.names().join(", ")],
+        ];
+        if let Some(capabilities) = self.capabilities {
+            summary.push(vec![String::from("Capabilities"), capabilities.needs.join(", ")]);
+        }
+
+        let scripts = metrics
+            .scripts
+            .iter()
+            .map(|script| {
+                let rules = script.rules.iter().map(|(rule, count)| format!("{rule} ({count})")).collect::<Vec<_>>();
+                vec![
+                    script.path.clone(),
+                    script.rules.values().sum::<usize>().to_string(),
+                    rules.join(", "),
+                    format!("{} -> {}", script.bytes_before, script.bytes_after),
+                ]
+            })
+            .collect();
+        let rules = metrics.rules.iter().map(|(rule, count)| vec![rule.to_string(), count.to_string()]).collect();
+        let diagnostics = group_diagnostics(self.diagnostics)
+            .into_iter()
+            .map(|group| {
+                let lines = group.lines.iter().map(usize::to_string).collect::<Vec<_>>();
+                vec![group.rule.to_string(), group.path.to_string(), lines.join(", "), group.message.to_string()]
+            })
+            .collect();
+        let failures = self.failures.iter().map(|failure| vec![failure.path.clone(), failure.error.clone()]).collect();
+        let minified = metrics
+            .scripts
+            .iter()
+            .filter(|script| script.minified)
+            .map(|script| vec![script.path.clone(), script.bytes_before.to_string()])
+            .collect();
+
+        vec![
+            Section {
+                title: "Summary",
+                headers: &["", ""],
+                rows: summary,
+            },
+            Section {
+                title: "Scripts",
+                headers: &["Script", "Rewrites", "Rules", "Bytes"],
+                rows: scripts,
+            },
+            Section {
+                title: "Rules",
+                headers: &["Rule", "Rewrites"],
+                rows: rules,
+            },
+            Section {
+                title: "Diagnostics",
+                headers: &["Rule", "Script", "Lines", "Message"],
+                rows: diagnostics,
+            },
+            Section {
+                title: "Failures",
+                headers: &["Script", "Error"],
+                rows: failures,
+            },
+            Section {
+                title: "Minified scripts",
+                headers: &["Script", "Bytes"],
+                rows: minified,
+            },
+        ]
+    }
+
+    fn to_markdown(&self) -> String {
+        let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+        let mut markdown = format!(
+            "# PluginProxy Transpiler report\n\n{} transpiled by version {}\n",
+            cell(&self.title()),
+            self.version
+        );
+        for section in self.sections() {
+            markdown.push_str(&format!("\n## {}\n\n", section.title));
+            if section.rows.is_empty() {
+                markdown.push_str("None\n");
+                continue;
+            }
+            markdown.push_str(&format!("| {} |\n", section.headers.join(" | ")));
+            markdown.push_str(&format!("|{}\n", " --- |".repeat(section.headers.len())));
+            for row in &section.rows {
+                let row = row.iter().map(|text| cell(text)).collect::<Vec<_>>();
+                markdown.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+        }
+        markdown
+    }
+
+    fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>PluginProxy Transpiler report</title>\n\
+            </head>\n<body>\n<h1>PluginProxy Transpiler report</h1>\n<p>{} transpiled by version {}</p>\n",
+            escape_html(&self.title()),
+            self.version
+        );
+        for section in self.sections() {
+            html.push_str(&format!("<h2>{}</h2>\n", section.title));
+            if section.rows.is_empty() {
+                html.push_str("<p>None</p>\n");
+                continue;
+            }
+            html.push_str("<table>\n<tr>");
+            for header in section.headers {
+                html.push_str(&format!("<th>{}</th>", escape_html(header)));
+            }
+            html.push_str("</tr>\n");
+            for row in &section.rows {
+                html.push_str("<tr>");
+                for text in row {
+                    html.push_str(&format!("<td>{}</td>", escape_html(text)));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,971 @@
+//! Names of the rules used by the transpiler to rewrite plugin code, and the [`Rule`] trait and [`RuleRegistry`]
+//! that the rules rewriting chains like `settings().Studio.Theme` are dispatched through, built-in and user-provided alike
+//!
+//! Rules that don't rewrite a chain stay in the visitor: stripping monetization statements, wrapping `rbxthumb://` URLs,
+//! replacing a bare `script:FindFirstAncestorOfClass("Plugin")` call with `plugin`, namespacing setting keys and
+//! the diagnostics of assignments, strings and whole scripts
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use full_moon::ast::{Call, Expression, FunctionArgs, Prefix, Suffix, Var};
+use serde::Deserialize;
+
+use crate::{
+    acquires_signal, capabilities, cleanup_expression,
+    dom::extension::{nth_arg, nth_arg_string, AffixExt, ArgValue, HasAffixes, TokenRefExt},
+    error::Problem,
+    plugin_ancestor_call,
+    report::Requirements,
+    service_name, unlisted_service, TranspileOptions, ASSET_INSERTIONS, KEY_SINKS, MONETIZATION_CALLS, RUN_CONTEXT_METHODS,
+    THUMBNAIL_CALLS,
+};
+
+/// `Enum.StudioStyleGuideColor` and other Studio only enums are indexed from the proxied `Enums`
+pub const ENUMS: &str = "enums";
+/// `settings()` is called through the plugin globals
+pub const SETTINGS: &str = "settings";
+/// Reports assignments to `settings()` properties, which hosts may block while allowing reads (diagnostic only)
+pub const SETTINGS_WRITE: &str = "settings-write";
+/// Keys of `plugin:GetSetting` and `plugin:SetSetting` are prefixed with a namespace,
+/// only with [`crate::TranspileOptions::settings_namespace`]
+pub const SETTINGS_NAMESPACE: &str = "settings-namespace";
+/// `script:FindFirstAncestorOfClass("Plugin")` is replaced with the proxied `plugin`
+pub const PLUGIN_ANCESTOR: &str = "plugin-ancestor";
+/// `game:GetService(...)` is called on the proxied `game`
+pub const GET_SERVICE: &str = "get-service";
+/// `game.PluginGuiService` is indexed from the proxied `game`, so UI parented to it reaches the host's container.
+/// UI parented to a local holding it is parented to the proxied service too
+pub const PLUGIN_GUI_SERVICE: &str = "plugin-gui-service";
+/// Reports UI parented straight to PluginGuiService, also through locals holding it or the parent of a widget,
+/// where only widgets show (diagnostic only)
+pub const PLUGIN_GUI_PARENT: &str = "plugin-gui-parent";
+/// `plugin.Unloading` is connected through the cleanup of the main script's wrapper,
+/// only with [`crate::TranspileOptions::unload_cleanup`]
+pub const UNLOADING: &str = "unloading";
+/// Calls that insert assets like `InsertService:LoadAsset` go through `_proxyGlobals.assets`
+/// with [`crate::TranspileOptions::proxy_assets`], otherwise they're reported
+pub const ASSET_INSERTION: &str = "asset-insertion";
+/// `RunService:IsEdit()` and the other checks of the mode Studio is in go through `_proxyGlobals.runContext`,
+/// only with [`crate::TranspileOptions::proxy_run_context`]
+pub const RUN_CONTEXT: &str = "run-context";
+/// AssetService calls and `ContentProvider:PreloadAsync` go through `_proxyGlobals.assets` and `rbxthumb://` URLs
+/// through `_proxyGlobals.assets:Thumbnail` with [`crate::TranspileOptions::proxy_thumbnails`], otherwise they're reported
+pub const THUMBNAILS: &str = "thumbnails";
+/// Reports calls that prompt purchases or check monetization policies, like `MarketplaceService:PromptPurchase`,
+/// statements calling them are stripped with [`crate::TranspileOptions::strip_monetization`]
+pub const MONETIZATION: &str = "monetization";
+/// Reports CoreGui access, which proxy hosts usually replace with a virtual container. Reads of what's below it go through
+/// `_proxyGlobals.coreGui` with [`crate::TranspileOptions::proxy_coregui`], UI parented to CoreGui is reported either way
+pub const COREGUI: &str = "coregui";
+/// Reports scripts that end up requiring themselves, which often breaks once the main script is wrapped (diagnostic only).
+/// Off by default since every script is parsed once more for it, see [`OPT_IN`]
+pub const REQUIRE_CYCLE: &str = "require-cycle";
+/// Reports services got with a name built at runtime, which can't be checked against what proxy hosts provide (diagnostic only)
+pub const DYNAMIC_SERVICE: &str = "dynamic-service";
+/// Reports services got that aren't in [`crate::TranspileOptions::allowed_services`], they're got from the real game
+/// instead of the proxied one (diagnostic only)
+pub const UNLISTED_SERVICE: &str = "unlisted-service";
+/// Reports requires of marketplace assets by id, `require(1234567)`, which proxy hosts can't load (diagnostic only)
+pub const ASSET_REQUIRE: &str = "asset-require";
+/// Reports keys of analytics events and plugin settings built from `plugin.Name`, `script.Name` or `script:GetFullName()`,
+/// since the name and path differ once the plugin is proxied, see [`crate::KEY_SINKS`] (diagnostic only)
+pub const TELEMETRY_KEY: &str = "telemetry-key";
+/// Reports scripts that look minified or obfuscated, which skip the cosmetic options and may hide plugin API usage
+/// from the rules, see [`crate::is_minified`] (diagnostic only)
+pub const MINIFIED: &str = "minified";
+/// Reports chains that more than one rule of the [`RuleRegistry`] matched, only the one with the highest priority
+/// rewrote them (diagnostic only)
+pub const RULE_CONFLICT: &str = "rule-conflict";
+/// Reports scripts that couldn't be parsed and were kept untouched, so plugin API usage in them wasn't rewritten (diagnostic only)
+pub const UNPARSED: &str = "unparsed";
+
+/// Rules that are disabled until enabled with [`crate::DomTranspiler::rule`]
+pub const OPT_IN: &[&str] = &[REQUIRE_CYCLE];
+
+/// All rules, in the order they're checked
+pub const ALL: &[&str] = &[
+    ENUMS,
+    SETTINGS,
+    SETTINGS_WRITE,
+    SETTINGS_NAMESPACE,
+    PLUGIN_ANCESTOR,
+    GET_SERVICE,
+    PLUGIN_GUI_SERVICE,
+    UNLOADING,
+    ASSET_INSERTION,
+    RUN_CONTEXT,
+    THUMBNAILS,
+    MONETIZATION,
+    COREGUI,
+    PLUGIN_GUI_PARENT,
+    REQUIRE_CYCLE,
+    DYNAMIC_SERVICE,
+    UNLISTED_SERVICE,
+    ASSET_REQUIRE,
+    TELEMETRY_KEY,
+    MINIFIED,
+    RULE_CONFLICT,
+    UNPARSED,
+];
+
+/// A chain of indexes and calls starting at a name, like `settings().Studio.Theme`, as rules see it.
+/// A chain in parentheses at the start is part of it, so `(settings()).Studio` is seen as `settings().Studio`
+/// and the parentheses are dropped when a rule rewrites it
+pub struct Chain<'a> {
+    pub prefix: &'a Prefix,
+    pub suffixes: Vec<&'a Suffix>,
+    /// Service held by the local the chain starts at, like `Selection` for `selection:Get()` after
+    /// `local selection = game:GetService("Selection")`
+    pub local_service: Option<&'a str>,
+}
+
+impl<'a> Chain<'a> {
+    pub fn of<T: HasAffixes>(node: &'a T) -> Self {
+        let mut chain = match node.prefix() {
+            Prefix::Expression(expression) => Self::parenthesized(expression),
+            _ => None,
+        }
+        .unwrap_or_else(|| Self {
+            prefix: node.prefix(),
+            suffixes: Vec::new(),
+            local_service: None,
+        });
+        chain.suffixes.extend(node.suffixes());
+        chain
+    }
+
+    /// Chain in parentheses, like `settings()` of `(settings()).Studio`
+    fn parenthesized(expression: &'a Expression) -> Option<Self> {
+        let Expression::Parentheses { expression, .. } = expression else {
+            return None;
+        };
+        match &**expression {
+            Expression::FunctionCall(function_call) => Some(Self::of(function_call)),
+            Expression::Var(Var::Expression(var)) => Some(Self::of(&**var)),
+            expression => Self::parenthesized(expression),
+        }
+    }
+
+    /// Name the chain starts at, `None` when it starts with a parenthesized expression
+    pub fn name(&self) -> Option<&'a str> {
+        self.prefix.identifier()
+    }
+
+    /// Name of the dot index at a position of the suffixes
+    pub fn index(&self, position: usize) -> Option<&'a str> {
+        self.suffixes.get(position).and_then(|suffix| AffixExt::identifier(*suffix))
+    }
+
+    /// Index of the first `:GetService(...)` call and its arguments
+    fn get_service_call(&self) -> Option<(usize, &'a FunctionArgs)> {
+        self.suffixes.iter().copied().enumerate().find_map(|(index, suffix)| match suffix {
+            Suffix::Call(Call::MethodCall(method_call)) if method_call.name().identifier() == Some("GetService") => {
+                Some((index, method_call.args()))
+            }
+            _ => None,
+        })
+    }
+
+    /// Finds a method call on `plugin`, on a service of `game`, also after the GetService rule rewrote it,
+    /// or on a local holding the service
+    ///
+    /// # Returns
+    /// Index of the suffix calling the method, the service or `plugin`, and the method
+    fn service_method_call(&self, options: &TranspileOptions) -> Option<(usize, &'a str, &'a str)> {
+        let (owner, index) = match (self.name()?, self.suffixes.as_slice()) {
+            ("plugin", _) => ("plugin", 0),
+            ("game", [service, ..]) => (service_name(*service)?, 1),
+            (globals, [game, service, ..]) if globals == options.globals_name && game.identifier() == Some("game") => {
+                (service_name(*service)?, 2)
+            }
+            _ => (self.local_service?, 0),
+        };
+
+        let Some(Suffix::Call(Call::MethodCall(method_call))) = self.suffixes.get(index).copied() else {
+            return None;
+        };
+        Some((index, owner, method_call.name().identifier()?))
+    }
+
+    /// Finds a read of something below CoreGui, like `game.CoreGui.SomePanel` or `CoreGui:FindFirstChild("SomePanel")`
+    /// on a local holding it, also after the GetService rule rewrote it
+    ///
+    /// # Returns
+    /// Index of the first suffix after CoreGui
+    fn coregui_read(&self, options: &TranspileOptions) -> Option<usize> {
+        let index = match (self.name()?, self.suffixes.as_slice()) {
+            ("game", [service, ..]) if service_name(service) == Some("CoreGui") => 1,
+            (globals, [game, service, ..])
+                if globals == options.globals_name
+                    && game.identifier() == Some("game")
+                    && service_name(service) == Some("CoreGui") =>
+            {
+                2
+            }
+            _ if self.local_service == Some("CoreGui") => 0,
+            _ => return None,
+        };
+        (self.suffixes.len() > index).then_some(index)
+    }
+}
+
+/// Finds a call of a method that inserts assets, see [`ASSET_INSERTIONS`]
+///
+/// # Returns
+/// Index of the suffix calling the method and the API like `InsertService:LoadAsset`
+fn asset_insertion(chain: &Chain, options: &TranspileOptions) -> Option<(usize, String)> {
+    let (index, owner, method) = chain.service_method_call(options)?;
+    ASSET_INSERTIONS
+        .iter()
+        .any(|insertion| *insertion == (owner, method))
+        .then(|| (index, format!("{owner}:{method}")))
+}
+
+/// Finds a call of an AssetService method or of a method that preloads assets, see [`THUMBNAIL_CALLS`]
+///
+/// # Returns
+/// Index of the suffix calling the method and the API like `ContentProvider:PreloadAsync`
+fn thumbnail_call(chain: &Chain, options: &TranspileOptions) -> Option<(usize, String)> {
+    let (index, owner, method) = chain.service_method_call(options)?;
+    (owner == "AssetService" || THUMBNAIL_CALLS.contains(&(owner, method))).then(|| (index, format!("{owner}:{method}")))
+}
+
+/// Finds a call that prompts a purchase or checks monetization policies, see [`MONETIZATION_CALLS`]
+///
+/// # Returns
+/// The API like `MarketplaceService:PromptPurchase`
+pub(crate) fn monetization_call(chain: &Chain, options: &TranspileOptions) -> Option<String> {
+    let (_, owner, method) = chain.service_method_call(options)?;
+    MONETIZATION_CALLS
+        .iter()
+        .any(|call| *call == (owner, method))
+        .then(|| format!("{owner}:{method}"))
+}
+
+/// Finds a call whose argument is a key of stored or reported data, see [`KEY_SINKS`]
+///
+/// # Returns
+/// Index of the suffix calling the method and the position of the key among its arguments
+pub(crate) fn key_sink(chain: &Chain, options: &TranspileOptions) -> Option<(usize, usize)> {
+    let (index, owner, method) = chain.service_method_call(options)?;
+    KEY_SINKS
+        .iter()
+        .find(|(sink_owner, sink_method, _)| (*sink_owner, *sink_method) == (owner, method))
+        .map(|(_, _, key)| (index, *key))
+}
+
+/// Finds a call of a RunService method that tells which mode Studio is in, see [`RUN_CONTEXT_METHODS`]
+///
+/// # Returns
+/// Index of the suffix calling the method
+fn run_context_check(chain: &Chain, options: &TranspileOptions) -> Option<usize> {
+    let (index, owner, method) = chain.service_method_call(options)?;
+    (owner == "RunService" && RUN_CONTEXT_METHODS.contains(&method)).then_some(index)
+}
+
+/// Rewrite of the suffixes before `keep_from` to a field of the globals, like `_proxyGlobals.assets`
+fn global_field(field: &str, keep_from: usize, options: &TranspileOptions) -> ChainRewrite {
+    ChainRewrite {
+        prefix: format!("{}.{field}", options.globals_name),
+        keep_from,
+        requirements: Requirements {
+            globals: true,
+            ..Default::default()
+        },
+    }
+}
+
+/// How a rule rewrites a chain, the name and the suffixes before `keep_from` are replaced with `prefix`.
+/// Indexes of suffixes are those of the whole [`Chain`], including the ones in parentheses
+#[derive(Clone, Debug)]
+pub struct ChainRewrite {
+    /// Lua the chain starts with instead, like `Enums` or `_proxyGlobals.settings`
+    pub prefix: String,
+    /// Index of the first suffix that's kept
+    pub keep_from: usize,
+    /// What the rewritten code needs at runtime
+    pub requirements: Requirements,
+}
+
+/// A rule rewriting chains. The enabled rule of the [`RuleRegistry`] with the highest [`Rule::priority`] matching
+/// a chain rewrites it, the others matching it are suppressed and reported as [`RULE_CONFLICT`], unless both are
+/// built-in rules, which are registered from the most specific to the most general on purpose.
+/// Rules are toggled by their name and their rewrites and diagnostics are counted under it.
+/// Rules that only report never rewrite
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use pluginproxy_transpiler::{
+///     report::Requirements,
+///     rules::{Chain, ChainRewrite, Rule},
+///     DomTranspiler, TranspileOptions,
+/// };
+///
+/// /// Routes `HttpService:RequestAsync(...)` through the host's HTTP client
+/// #[derive(Debug)]
+/// struct HostHttp;
+///
+/// impl Rule for HostHttp {
+///     fn name(&self) -> &'static str {
+///         "host-http"
+///     }
+///
+///     fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+///         (chain.name() == Some("HttpService")).then(|| ChainRewrite {
+///             prefix: format!("{}.http", options.globals_name),
+///             keep_from: 0,
+///             requirements: Requirements { globals: true, ..Default::default() },
+///         })
+///     }
+/// }
+///
+/// let mut options = TranspileOptions { path_depth: 1, ..Default::default() };
+/// options.rules.register(Arc::new(HostHttp));
+/// let transpiled = DomTranspiler::transpile_script("local response = HttpService:RequestAsync(request)", &options).unwrap();
+/// assert!(transpiled.contains("local response = _proxyGlobals.http:RequestAsync(request)"));
+/// assert!(transpiled.starts_with("local _proxyGlobals = require(script.Parent).Globals"));
+/// ```
+pub trait Rule: fmt::Debug + Send + Sync {
+    /// Name to toggle the rule with and to count its rewrites and diagnostics under
+    fn name(&self) -> &'static str;
+
+    /// Rules with a higher priority are tried first, rules with the same priority in the order they were registered.
+    /// The built-in rules have priority 0
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Capability the plugin needs from the host once the rule rewrote something, see [`crate::capabilities`]
+    fn capability(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The rewrite of a chain, when the rule matches it
+    fn rewrite(&self, _chain: &Chain, _options: &TranspileOptions) -> Option<ChainRewrite> {
+        None
+    }
+
+    /// Message to report about a chain, whether the rule rewrites it or not
+    fn diagnostic(&self, _chain: &Chain, _options: &TranspileOptions) -> Option<String> {
+        None
+    }
+}
+
+/// See [`ENUMS`]
+#[derive(Debug)]
+pub struct EnumsRule;
+
+impl Rule for EnumsRule {
+    fn name(&self) -> &'static str {
+        ENUMS
+    }
+
+    fn capability(&self) -> Option<&'static str> {
+        Some(capabilities::ENUMS)
+    }
+
+    fn rewrite(&self, chain: &Chain, _: &TranspileOptions) -> Option<ChainRewrite> {
+        let matches = chain.name() == Some("Enum")
+            && matches!(chain.index(0), Some("StudioStyleGuideColor" | "StudioStyleGuideModifier" | "UITheme"));
+        matches.then(|| ChainRewrite {
+            prefix: String::from("Enums"),
+            keep_from: 0,
+            requirements: Requirements {
+                enums: true,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// See [`PLUGIN_GUI_SERVICE`]
+#[derive(Debug)]
+pub struct PluginGuiServiceRule;
+
+impl Rule for PluginGuiServiceRule {
+    fn name(&self) -> &'static str {
+        PLUGIN_GUI_SERVICE
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let matches = chain.name() == Some("game") && chain.index(0) == Some("PluginGuiService");
+        matches.then(|| ChainRewrite {
+            prefix: format!("{}.game", options.globals_name),
+            keep_from: 0,
+            requirements: Requirements {
+                globals: true,
+                signals: acquires_signal(chain.suffixes.iter().copied()),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// See [`UNLOADING`]
+#[derive(Debug)]
+pub struct UnloadingRule;
+
+impl Rule for UnloadingRule {
+    fn name(&self) -> &'static str {
+        UNLOADING
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let matches = options.unload_cleanup && chain.name() == Some("plugin") && chain.index(0) == Some("Unloading");
+        matches.then(|| ChainRewrite {
+            prefix: cleanup_expression(options),
+            keep_from: 1,
+            requirements: Requirements::default(),
+        })
+    }
+}
+
+/// See [`SETTINGS`]
+#[derive(Debug)]
+pub struct SettingsRule;
+
+impl Rule for SettingsRule {
+    fn name(&self) -> &'static str {
+        SETTINGS
+    }
+
+    fn capability(&self) -> Option<&'static str> {
+        Some(capabilities::SETTINGS)
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let matches = chain.name() == Some("settings") && matches!(chain.suffixes.first(), Some(Suffix::Call(_)));
+        matches.then(|| ChainRewrite {
+            prefix: format!("{}.settings", options.globals_name),
+            keep_from: 0,
+            requirements: Requirements {
+                globals: true,
+                signals: acquires_signal(chain.suffixes.iter().copied()),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// See [`ASSET_INSERTION`]
+#[derive(Debug)]
+pub struct AssetInsertionRule;
+
+impl Rule for AssetInsertionRule {
+    fn name(&self) -> &'static str {
+        ASSET_INSERTION
+    }
+
+    fn capability(&self) -> Option<&'static str> {
+        Some(capabilities::ASSETS)
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let (index, _) = asset_insertion(chain, options).filter(|_| options.proxy_assets)?;
+        Some(global_field("assets", index, options))
+    }
+
+    fn diagnostic(&self, chain: &Chain, options: &TranspileOptions) -> Option<String> {
+        let (_, api) = asset_insertion(chain, options).filter(|_| !options.proxy_assets)?;
+        Some(format!("inserts assets with {api}, which proxy hosts usually sandbox, see --proxy-assets"))
+    }
+}
+
+/// See [`THUMBNAILS`], URLs are wrapped by the visitor
+#[derive(Debug)]
+pub struct ThumbnailsRule;
+
+impl Rule for ThumbnailsRule {
+    fn name(&self) -> &'static str {
+        THUMBNAILS
+    }
+
+    fn capability(&self) -> Option<&'static str> {
+        Some(capabilities::THUMBNAILS)
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let (index, _) = thumbnail_call(chain, options).filter(|_| options.proxy_thumbnails)?;
+        Some(global_field("assets", index, options))
+    }
+
+    fn diagnostic(&self, chain: &Chain, options: &TranspileOptions) -> Option<String> {
+        let (_, api) = thumbnail_call(chain, options).filter(|_| !options.proxy_thumbnails)?;
+        Some(format!("uses {api}, which proxy hosts may serve from their own thumbnail pipeline, see --proxy-thumbnails"))
+    }
+}
+
+/// See [`RUN_CONTEXT`]
+#[derive(Debug)]
+pub struct RunContextRule;
+
+impl Rule for RunContextRule {
+    fn name(&self) -> &'static str {
+        RUN_CONTEXT
+    }
+
+    fn capability(&self) -> Option<&'static str> {
+        Some(capabilities::RUN_CONTEXT)
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let index = run_context_check(chain, options).filter(|_| options.proxy_run_context)?;
+        Some(global_field("runContext", index, options))
+    }
+}
+
+/// See [`COREGUI`]
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::{rules, DomTranspiler};
+/// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+///
+/// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+/// let source = String::from("local panel = game.CoreGui.SomePanel\ngui.Parent = game.CoreGui");
+/// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("Script").with_name("Main").with_property("Source", source));
+///
+/// // reads are routed through the globals, parenting to CoreGui is still reported
+/// let mut transpiler = DomTranspiler::with_source_script(tree, main).unwrap();
+/// transpiler.proxy_coregui(true).transpile_tree().unwrap();
+/// let diagnostics = transpiler.diagnostics();
+/// let coregui: Vec<_> = diagnostics.iter().filter(|diagnostic| diagnostic.rule == rules::COREGUI).collect();
+/// assert_eq!(coregui.len(), 1);
+/// assert_eq!(coregui[0].line, Some(2));
+/// ```
+#[derive(Debug)]
+pub struct CoreGuiRule;
+
+impl Rule for CoreGuiRule {
+    fn name(&self) -> &'static str {
+        COREGUI
+    }
+
+    fn capability(&self) -> Option<&'static str> {
+        Some(capabilities::COREGUI)
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let index = chain.coregui_read(options).filter(|_| options.proxy_coregui)?;
+        Some(global_field("coreGui", index, options))
+    }
+
+    fn diagnostic(&self, chain: &Chain, options: &TranspileOptions) -> Option<String> {
+        let gets_coregui = chain.get_service_call().is_some_and(|(_, args)| nth_arg_string(args, 0) == Some("CoreGui"));
+        let is_index = chain.name() == Some("game") && chain.index(0) == Some("CoreGui");
+        match chain.coregui_read(options) {
+            // routed through the globals, UI parented to CoreGui itself is still reported
+            Some(_) if options.proxy_coregui => None,
+            Some(_) if !gets_coregui => Some(String::from(
+                "reads from CoreGui, which proxy hosts usually replace with a virtual container, see --proxy-coregui",
+            )),
+            _ if gets_coregui || is_index => {
+                Some(String::from("CoreGui is usually replaced by the proxy host, UI parented to it may not show"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// See [`MONETIZATION`], statements are stripped by the visitor
+#[derive(Debug)]
+pub struct MonetizationRule;
+
+impl Rule for MonetizationRule {
+    fn name(&self) -> &'static str {
+        MONETIZATION
+    }
+
+    fn capability(&self) -> Option<&'static str> {
+        Some(capabilities::MONETIZATION)
+    }
+
+    fn diagnostic(&self, chain: &Chain, options: &TranspileOptions) -> Option<String> {
+        let api = monetization_call(chain, options)?;
+        Some(format!(
+            "uses {api}, proxy hosts may forbid purchase prompts from proxied plugins, see --strip-monetization"
+        ))
+    }
+}
+
+/// See [`PLUGIN_ANCESTOR`], for lookups followed by more of the chain, like `:GetSetting(key)`.
+/// A bare lookup is replaced as a whole by the visitor
+#[derive(Debug)]
+pub struct PluginAncestorRule;
+
+impl Rule for PluginAncestorRule {
+    fn name(&self) -> &'static str {
+        PLUGIN_ANCESTOR
+    }
+
+    fn rewrite(&self, chain: &Chain, _: &TranspileOptions) -> Option<ChainRewrite> {
+        let index = plugin_ancestor_call(chain.suffixes.iter().copied()).filter(|index| index + 1 < chain.suffixes.len())?;
+        Some(ChainRewrite {
+            prefix: String::from("plugin"),
+            keep_from: index + 1,
+            requirements: Requirements {
+                plugin: true,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// See [`GET_SERVICE`]
+#[derive(Debug)]
+pub struct GetServiceRule;
+
+impl Rule for GetServiceRule {
+    fn name(&self) -> &'static str {
+        GET_SERVICE
+    }
+
+    /// The call is kept, with the chain after it like `:GetPropertyChangedSignal("Theme"):Connect(f)`.
+    /// Services missing from the allowed ones aren't rewritten
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        let (index, args) = chain.get_service_call()?;
+        if let Some(allowed) = &options.allowed_services {
+            if unlisted_service(allowed, nth_arg_string(args, 0)).is_some() {
+                return None;
+            }
+        }
+        Some(ChainRewrite {
+            prefix: format!("{}.game", options.globals_name),
+            keep_from: index,
+            requirements: Requirements {
+                globals: true,
+                signals: acquires_signal(chain.suffixes.iter().skip(index + 1).copied()),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// See [`DYNAMIC_SERVICE`]
+///
+/// # Example
+///
+/// ```rust
+/// use pluginproxy_transpiler::{rules, DomTranspiler};
+/// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+///
+/// let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+/// let source = String::from("local selection = game:GetService(`Selection`)\nlocal service = game:GetService(name)");
+/// let main = tree.insert(tree.root_ref(), InstanceBuilder::new("Script").with_name("Main").with_property("Source", source));
+///
+/// let mut transpiler = DomTranspiler::with_source_script(tree, main).unwrap();
+/// transpiler.transpile_tree().unwrap();
+/// let diagnostics = transpiler.diagnostics();
+/// let dynamic: Vec<_> = diagnostics.iter().filter(|diagnostic| diagnostic.rule == rules::DYNAMIC_SERVICE).collect();
+/// assert_eq!(dynamic.len(), 1);
+/// assert_eq!(dynamic[0].line, Some(2));
+/// ```
+#[derive(Debug)]
+pub struct DynamicServiceRule;
+
+impl Rule for DynamicServiceRule {
+    fn name(&self) -> &'static str {
+        DYNAMIC_SERVICE
+    }
+
+    fn diagnostic(&self, chain: &Chain, _: &TranspileOptions) -> Option<String> {
+        let (_, args) = chain.get_service_call()?;
+        match nth_arg(args, 0) {
+            Some(ArgValue::String(_)) | None => None,
+            Some(_) => Some(String::from("the service name is built at runtime, so it can't be checked statically")),
+        }
+    }
+}
+
+/// See [`UNLISTED_SERVICE`]
+#[derive(Debug)]
+pub struct UnlistedServiceRule;
+
+impl Rule for UnlistedServiceRule {
+    fn name(&self) -> &'static str {
+        UNLISTED_SERVICE
+    }
+
+    fn diagnostic(&self, chain: &Chain, options: &TranspileOptions) -> Option<String> {
+        let (_, args) = chain.get_service_call()?;
+        unlisted_service(options.allowed_services.as_ref()?, nth_arg_string(args, 0))
+    }
+}
+
+/// Rules that rewrite chains, in the order they're tried: by priority, then in the order they were registered
+#[derive(Clone, Debug)]
+pub struct RuleRegistry {
+    rules: Vec<Arc<dyn Rule>>,
+}
+
+impl RuleRegistry {
+    /// Just the built-in rules
+    pub fn builtin() -> Self {
+        Self {
+            rules: vec![
+                Arc::new(AssetInsertionRule),
+                Arc::new(ThumbnailsRule),
+                Arc::new(RunContextRule),
+                Arc::new(CoreGuiRule),
+                Arc::new(MonetizationRule),
+                Arc::new(PluginAncestorRule),
+                Arc::new(GetServiceRule),
+                Arc::new(DynamicServiceRule),
+                Arc::new(UnlistedServiceRule),
+                Arc::new(EnumsRule),
+                Arc::new(PluginGuiServiceRule),
+                Arc::new(UnloadingRule),
+                Arc::new(SettingsRule),
+            ],
+        }
+    }
+
+    /// Adds a rule, tried after the registered ones with the same or a higher priority.
+    /// It replaces a registered rule with the same name
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use pluginproxy_transpiler::{
+    ///     rules::{Chain, ChainRewrite, Rule, RuleRegistry},
+    ///     DomTranspiler, TranspileOptions,
+    /// };
+    ///
+    /// /// Reads the Studio theme from the host instead of the proxied settings
+    /// #[derive(Debug)]
+    /// struct HostTheme;
+    ///
+    /// impl Rule for HostTheme {
+    ///     fn name(&self) -> &'static str {
+    ///         "host-theme"
+    ///     }
+    ///
+    ///     fn priority(&self) -> i32 {
+    ///         1
+    ///     }
+    ///
+    ///     fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+    ///         let theme = chain.name() == Some("settings") && chain.index(1) == Some("Studio");
+    ///         (theme && chain.index(2) == Some("Theme")).then(|| ChainRewrite {
+    ///             prefix: format!("{}.theme", options.globals_name),
+    ///             keep_from: 3,
+    ///             requirements: Default::default(),
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let mut registry = RuleRegistry::builtin();
+    /// registry.register(Arc::new(HostTheme));
+    /// assert_eq!(registry.names().first(), Some(&"host-theme"));
+    ///
+    /// let options = TranspileOptions { path_depth: 1, rules: registry, ..Default::default() };
+    /// let source = "local theme = settings().Studio.Theme\nlocal studio = settings().Studio";
+    /// let transpiled = DomTranspiler::transpile_script(source, &options).unwrap();
+    /// assert!(transpiled.contains("local theme = _proxyGlobals.theme\n"));
+    /// assert!(transpiled.contains("local studio = _proxyGlobals.settings().Studio"));
+    /// ```
+    ///
+    /// # Returns
+    /// `&mut Self` for method chaining
+    pub fn register(&mut self, rule: Arc<dyn Rule>) -> &mut Self {
+        self.rules.retain(|registered| registered.name() != rule.name());
+        let index = self.rules.partition_point(|registered| registered.priority() >= rule.priority());
+        self.rules.insert(index, rule);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Rule> {
+        self.iter().find(|rule| rule.name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|rule| rule.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.iter().map(|rule| rule.name()).collect()
+    }
+}
+
+impl Default for RuleRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+/// A rule declared in the config file instead of written in Rust, compiled with [`RuleDefinition::compile`]
+///
+/// ```toml
+/// [[custom-rules]]
+/// name = "host-mouse"
+/// prefix = "plugin"
+/// method = "GetMouse"
+/// replacement = "{globals}.mouse"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RuleDefinition {
+    /// Name to toggle the rule with, it can't be the name of a built-in rule
+    pub name: String,
+    /// Name the chain starts with, like `plugin` or `HttpService`
+    pub prefix: String,
+    /// Method called right on the prefix, like `GetMouse` for `plugin:GetMouse()`
+    pub method: Option<String>,
+    /// String the method is called with as its first argument, needs `method`
+    pub argument: Option<String>,
+    /// Lua replacing the prefix, and the method call when there's a `method`. `{globals}` is the name of the globals,
+    /// `{argument}` the first argument as a string and `{arguments}` the arguments of the method call as written,
+    /// other braces are kept like those of a table
+    pub replacement: String,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+impl RuleDefinition {
+    /// Checks the definition, without compiling it
+    pub fn validate(&self) -> Result<(), Problem> {
+        let invalid = |message: String| Problem::InvalidRule(self.name.clone(), message);
+        if self.name.is_empty() || self.prefix.is_empty() {
+            return Err(invalid(String::from("the name and the prefix can't be empty")));
+        }
+        if ALL.contains(&self.name.as_str()) {
+            return Err(invalid(String::from("it has the name of a built-in rule")));
+        }
+        if self.argument.is_some() && self.method.is_none() {
+            return Err(invalid(String::from("an argument needs a method")));
+        }
+        let argument = ["{argument}", "{arguments}"].into_iter().find(|placeholder| self.replacement.contains(placeholder));
+        match argument {
+            Some(placeholder) if self.method.is_none() => Err(invalid(format!("{placeholder} needs a method"))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the definition and turns it into a rule for the [`RuleRegistry`]. The name is interned to give it the
+    /// `'static` lifetime of rule names, so compiling a definition again doesn't take more memory
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pluginproxy_transpiler::{
+    ///     rules::{Rule, RuleDefinition},
+    ///     DomTranspiler, TranspileOptions,
+    /// };
+    ///
+    /// let definition = RuleDefinition {
+    ///     name: String::from("host-mouse"),
+    ///     prefix: String::from("plugin"),
+    ///     method: Some(String::from("GetMouse")),
+    ///     argument: None,
+    ///     replacement: String::from("{globals}.mouse"),
+    ///     priority: 0,
+    /// };
+    /// let mut options = TranspileOptions { path_depth: 1, ..Default::default() };
+    /// options.rules.register(std::sync::Arc::new(definition.clone().compile().unwrap()));
+    /// let transpiled = DomTranspiler::transpile_script("local mouse = plugin:GetMouse()\nprint(mouse.Hit)", &options).unwrap();
+    /// assert!(transpiled.contains("local mouse = _proxyGlobals.mouse\n"));
+    ///
+    /// // compiling it again shares the name
+    /// let (first, second) = (definition.clone().compile().unwrap(), definition.clone().compile().unwrap());
+    /// assert!(std::ptr::eq(first.name(), second.name()));
+    ///
+    /// let table = RuleDefinition { replacement: String::from("({ Hit = Vector3.zero })"), ..definition.clone() };
+    /// assert!(table.validate().is_ok());
+    ///
+    /// let replacement = String::from("{globals}.mouse({arguments})");
+    /// let without_method = RuleDefinition { method: None, replacement, ..definition };
+    /// assert!(without_method.validate().is_err());
+    /// ```
+    pub fn compile(self) -> Result<TemplateRule, Problem> {
+        self.validate()?;
+        Ok(TemplateRule {
+            name: intern_rule_name(&self.name),
+            definition: self,
+        })
+    }
+}
+
+/// Names of the rules compiled from a [`RuleDefinition`], each is leaked once however often it's compiled
+static RULE_NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+fn intern_rule_name(name: &str) -> &'static str {
+    let mut names = RULE_NAMES.lock().unwrap();
+    if let Some(interned) = names.get(name) {
+        return interned;
+    }
+    let interned = Box::leak(name.to_string().into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// Rule compiled from a [`RuleDefinition`]
+#[derive(Debug)]
+pub struct TemplateRule {
+    name: &'static str,
+    definition: RuleDefinition,
+}
+
+impl TemplateRule {
+    /// Arguments of the method call of the definition, when the chain starts with it
+    fn method_arguments<'a>(&self, chain: &Chain<'a>) -> Option<&'a FunctionArgs> {
+        let method = self.definition.method.as_deref()?;
+        match chain.suffixes.first() {
+            Some(Suffix::Call(Call::MethodCall(method_call))) if method_call.name().identifier() == Some(method) => {
+                Some(method_call.args())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Rule for TemplateRule {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn priority(&self) -> i32 {
+        self.definition.priority
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        if chain.name() != Some(self.definition.prefix.as_str()) {
+            return None;
+        }
+        let args = self.method_arguments(chain);
+        let matches = match (&self.definition.method, args) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(_), Some(args)) => match &self.definition.argument {
+                Some(argument) => nth_arg_string(args, 0) == Some(argument.as_str()),
+                None => true,
+            },
+        };
+        if !matches {
+            return None;
+        }
+        let prefix = self
+            .definition
+            .replacement
+            .replace("{globals}", &options.globals_name)
+            .replace("{argument}", args.and_then(|args| nth_arg_string(args, 0)).unwrap_or_default())
+            .replace("{arguments}", &args.map(ToString::to_string).unwrap_or_default());
+        Some(ChainRewrite {
+            prefix,
+            keep_from: usize::from(args.is_some()),
+            requirements: Requirements {
+                globals: self.definition.replacement.contains("{globals}"),
+                ..Default::default()
+            },
+        })
+    }
+}
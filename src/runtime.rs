@@ -0,0 +1,60 @@
+//! Descriptions of what proxy runtimes provide, for checking that a transpiled plugin can run on a host version
+
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{capabilities::CapabilityManifest, error::Problem, report::Metrics};
+
+/// What a version of a proxy runtime provides, read from JSON
+///
+/// ```json
+/// { "name": "PluginProxy", "version": "1.4.0", "globals": ["plugin", "settings", "game"], "capabilities": ["toolbar"] }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RuntimeDescriptor {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Fields of the plugin globals the runtime provides, like `plugin` or `Enums`
+    pub globals: Vec<String>,
+    /// Capabilities the runtime grants, see [`crate::capabilities`]. Not checked if missing
+    pub capabilities: Option<Vec<String>>,
+}
+
+impl RuntimeDescriptor {
+    pub fn load(file_path: &Path) -> Result<Self, Problem> {
+        let contents = fs::read_to_string(file_path).map_err(|error| Problem::IOError("read the runtime descriptor", error))?;
+        serde_json::from_str(&contents).map_err(|error| Problem::InvalidRuntime(file_path.to_path_buf(), error.to_string()))
+    }
+
+    /// Display name of the runtime, like `PluginProxy 1.4.0`
+    pub fn display_name(&self) -> String {
+        let name = self.name.as_deref().unwrap_or("the runtime");
+        match &self.version {
+            Some(version) => format!("{name} {version}"),
+            None => name.to_string(),
+        }
+    }
+
+    /// Globals fields and capabilities that the transpiled plugin needs but the runtime doesn't provide
+    pub fn missing(&self, metrics: &Metrics, capabilities: Option<&CapabilityManifest>) -> Vec<String> {
+        let mut missing: Vec<String> = metrics
+            .required_globals()
+            .into_iter()
+            .filter(|global| !self.globals.iter().any(|provided| provided == global))
+            .map(String::from)
+            .collect();
+
+        if let (Some(granted), Some(capabilities)) = (&self.capabilities, capabilities) {
+            missing.extend(
+                capabilities
+                    .needs
+                    .iter()
+                    .filter(|need| !granted.iter().any(|provided| provided == *need))
+                    .map(|need| format!("capability {need}")),
+            );
+        }
+
+        missing
+    }
+}
@@ -0,0 +1,171 @@
+//! Helpers for writing concise tests against the transpiler
+//!
+//! Enabled with the `testing` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pluginproxy_transpiler::{testing::*, TranspileOptions};
+//!
+//! assert_transpiles(
+//!     "local theme = settings().Studio.Theme",
+//!     "local _proxyGlobals = require(script.Parent).Globals\n-- Autogenerated with PluginProxy Transpiler\n\nlocal theme = _proxyGlobals.settings().Studio.Theme",
+//!     &TranspileOptions { path_depth: 1, ..Default::default() },
+//! );
+//!
+//! let mut transpiler = PluginFixture::new("require(script.Lib.Util)")
+//!     .module("Lib/Util", "return settings()")
+//!     .transpiler();
+//! transpiler.transpile_tree().unwrap();
+//! let util = script_source(transpiler.tree(), transpiler.source_script(), "script.Lib.Util");
+//! assert!(util.unwrap().contains("_proxyGlobals.settings()"));
+//! ```
+
+use rbx_dom_weak::{types::Ref, InstanceBuilder, WeakDom};
+
+use crate::{
+    dom::extension::{ForEachAction, InstanceExt, WeakDomExt},
+    DomTranspiler, TranspileOptions,
+};
+
+/// Transpiles a script's source, panicking if it fails
+pub fn transpile(input: &str, options: &TranspileOptions) -> String {
+    DomTranspiler::transpile_script(input, options).unwrap_or_else(|error| panic!("failed to transpile: {}", error.report()))
+}
+
+/// Asserts that the input source transpiles into exactly the expected source
+///
+/// # Arguments
+///
+/// * `input` - Source code of the script before transpiling
+/// * `expected` - Source code the script should have after transpiling
+/// * `options` - Options for the script, `path_depth` 0 also wraps the source
+pub fn assert_transpiles(input: &str, expected: &str, options: &TranspileOptions) {
+    let output = transpile(input, options);
+    if output != expected {
+        panic!("transpiled source does not match\n--- expected ---\n{expected}\n--- transpiled ---\n{output}\n");
+    }
+}
+
+/// Gets the source of the script at a path relative to the main script
+///
+/// # Arguments
+///
+/// * `tree` - Dom containing the plugin
+/// * `main` - Referent of the main script
+/// * `path` - Path in the format of script.Lib.Util, `script` is the main script
+pub fn script_source(tree: &WeakDom, main: Ref, path: &str) -> Option<String> {
+    let main = tree.get_by_ref(main)?;
+    if path == "script" {
+        return main.source().map(String::from);
+    }
+
+    let mut result = None;
+    tree.foreach_descendant(
+        main,
+        &mut |child, child_path| {
+            if child_path.to_string() == path {
+                result = child.source().map(String::from);
+                return ForEachAction::Break;
+            }
+            ForEachAction::Continue
+        },
+        0,
+    );
+
+    result
+}
+
+/// Builder for plugin doms used as test fixtures
+///
+/// The plugin has a main `Script` named `Main` under the root, modules are added relative to it
+pub struct PluginFixture {
+    tree: WeakDom,
+    main: Ref,
+}
+
+impl PluginFixture {
+    /// Creates a plugin with a main `Script` containing `main_source`
+    pub fn new(main_source: &str) -> Self {
+        Self::with_main_class("Script", main_source)
+    }
+
+    /// Creates a plugin where the main script is of the specified class
+    pub fn with_main_class(class: &str, main_source: &str) -> Self {
+        let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let main = tree.insert(
+            tree.root_ref(),
+            InstanceBuilder::new(class)
+                .with_name("Main")
+                .with_property("Source", main_source.to_string()),
+        );
+
+        Self { tree, main }
+    }
+
+    /// Adds a `ModuleScript` at a path relative to the main script, missing ancestors are created as folders
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path in the format of Lib/Util, the last component is the name of the module
+    /// * `source` - Source code of the module
+    pub fn module(self, path: &str, source: &str) -> Self {
+        self.script("ModuleScript", path, source)
+    }
+
+    /// Same as [`PluginFixture::module`], but with any script class
+    pub fn script(mut self, class: &str, path: &str, source: &str) -> Self {
+        let mut parent = self.main;
+        let mut components = path.split('/').peekable();
+
+        while let Some(name) = components.next() {
+            if components.peek().is_none() {
+                self.tree.insert(
+                    parent,
+                    InstanceBuilder::new(class)
+                        .with_name(name)
+                        .with_property("Source", source.to_string()),
+                );
+                break;
+            }
+
+            let existing = self
+                .tree
+                .get_by_ref(parent)
+                .unwrap()
+                .children()
+                .iter()
+                .copied()
+                .find(|child| self.tree.get_by_ref(*child).is_some_and(|child| child.name == name));
+
+            parent = match existing {
+                Some(child) => child,
+                None => self.tree.insert(parent, InstanceBuilder::new("Folder").with_name(name)),
+            };
+        }
+
+        self
+    }
+
+    /// Referent of the main script
+    pub fn main(&self) -> Ref {
+        self.main
+    }
+
+    /// Finishes the fixture, returning the dom
+    pub fn build(self) -> WeakDom {
+        self.tree
+    }
+
+    /// Finishes the fixture, returning a transpiler for the dom
+    pub fn transpiler(self) -> DomTranspiler {
+        DomTranspiler::new(self.tree).expect("fixture has a main script")
+    }
+
+    /// Encodes the plugin as an rbxm model, the way it would be saved from Studio
+    pub fn to_rbxm(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        rbx_binary::to_writer(&mut buffer, &self.tree, &[self.main]).expect("fixture encodes to rbxm");
+        buffer
+    }
+}
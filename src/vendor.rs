@@ -0,0 +1,162 @@
+//! Marketplace modules required by asset id like `require(1234567)`, which proxy hosts can't load
+//!
+//! Once an asset is downloaded, its module is inserted into the [`VENDOR_FOLDER_NAME`] folder under the main script
+//! as `Asset1234567`, and the requires of the asset become requires of that module
+
+use std::{collections::BTreeSet, ops::Range};
+
+use full_moon::{
+    ast::{Call, FunctionCall, Suffix},
+    node::Node,
+    tokenizer::TokenType,
+    visitors::Visitor,
+};
+use rbx_dom_weak::{types::Ref, InstanceBuilder, WeakDom};
+
+use crate::{
+    dom::{
+        extension::{nth_arg, AffixExt, ArgValue, ForEachAction, HasAffixes, InstanceExt, WeakDomExt},
+        rbx_path::{DotPath, IndexStyle},
+    },
+    error::Problem,
+    is_script_class,
+};
+
+/// Name of the folder under the main script that vendored modules are inserted into
+pub const VENDOR_FOLDER_NAME: &str = "_Vendored";
+
+/// Id of the asset that a `require` call loads, `None` if it isn't a require of a positive whole number
+pub(crate) fn required_asset(node: &FunctionCall) -> Option<u64> {
+    if node.prefix().identifier() != Some("require") {
+        return None;
+    }
+    let Some(Suffix::Call(Call::AnonymousCall(args))) = node.suffixes().next() else {
+        return None;
+    };
+    match nth_arg(args, 0) {
+        Some(ArgValue::Number(id)) if id >= 1.0 && id.fract() == 0.0 => Some(id as u64),
+        _ => None,
+    }
+}
+
+/// Asset ids required by the main script and every script below it
+pub(crate) fn asset_requires(tree: &WeakDom, main: Ref) -> BTreeSet<u64> {
+    let mut ids = BTreeSet::new();
+    for script in scripts(tree, main) {
+        for (_, id) in find_requires(tree, script) {
+            ids.insert(id);
+        }
+    }
+    ids
+}
+
+/// Inserts the module of a downloaded asset under the vendor folder of the main script, the one named MainModule
+/// or else the first ModuleScript at the root of the asset, and makes the requires of the asset require it
+///
+/// # Returns
+/// Amount of rewritten requires
+pub(crate) fn vendor(tree: &mut WeakDom, main: Ref, id: u64, asset: WeakDom) -> Result<usize, Problem> {
+    let modules = asset
+        .root()
+        .children()
+        .iter()
+        .filter_map(|child| asset.get_by_ref(*child))
+        .filter(|child| child.class == "ModuleScript")
+        .collect::<Vec<_>>();
+    let module = modules
+        .iter()
+        .find(|module| module.name == "MainModule")
+        .or(modules.first())
+        .map(|module| module.referent())
+        .ok_or(Problem::AssetWithoutModule(id))?;
+
+    let existing = tree
+        .get_by_ref(main)
+        .unwrap()
+        .children()
+        .iter()
+        .copied()
+        .find(|child| tree.get_by_ref(*child).is_some_and(|child| child.name == VENDOR_FOLDER_NAME));
+    let folder = match existing {
+        Some(folder) => folder,
+        None => tree.insert(main, InstanceBuilder::new("Folder").with_name(VENDOR_FOLDER_NAME)),
+    };
+    let vendored = tree.graft(asset.clone_subtree(module), folder)[0];
+    tree.get_by_ref_mut(vendored).unwrap().name = format!("Asset{id}");
+
+    let mut count = 0;
+    for script in scripts(tree, main) {
+        let requires = find_requires(tree, script)
+            .into_iter()
+            .filter(|(_, required)| *required == id)
+            .collect::<Vec<_>>();
+        if requires.is_empty() {
+            continue;
+        }
+
+        let path = DotPath::relative_to(tree, script, vendored).unwrap().to_lua(IndexStyle::Brackets);
+        let source = tree.get_by_ref_mut(script).unwrap().source_mut().unwrap();
+        count += requires.len();
+        // from the end, so the ranges before stay valid
+        for (range, _) in requires.into_iter().rev() {
+            source.replace_range(range, &path);
+        }
+    }
+    Ok(count)
+}
+
+/// The main script and every script below it
+fn scripts(tree: &WeakDom, main: Ref) -> Vec<Ref> {
+    let mut scripts = vec![main];
+    tree.foreach_descendant(
+        tree.get_by_ref(main).unwrap(),
+        &mut |child, _| {
+            if is_script_class(&child.class) {
+                scripts.push(child.referent());
+            }
+            ForEachAction::Continue
+        },
+        0,
+    );
+    scripts
+}
+
+/// Byte ranges of the asset ids in the requires of the script, in source order
+fn find_requires(tree: &WeakDom, script: Ref) -> Vec<(Range<usize>, u64)> {
+    let Some(source) = tree.get_by_ref(script).and_then(|script| script.source()) else {
+        return Vec::new();
+    };
+    if !source.contains("require") {
+        return Vec::new();
+    }
+    // scripts that can't be parsed are reported when they're transpiled
+    let Ok(ast) = full_moon::parse(source) else {
+        return Vec::new();
+    };
+
+    let mut finder = RequireFinder::default();
+    finder.visit_ast(&ast);
+    finder.requires
+}
+
+#[derive(Default)]
+struct RequireFinder {
+    requires: Vec<(Range<usize>, u64)>,
+}
+
+impl Visitor for RequireFinder {
+    fn visit_function_call(&mut self, node: &FunctionCall) {
+        let Some(id) = required_asset(node) else {
+            return;
+        };
+        let Some(Suffix::Call(Call::AnonymousCall(args))) = node.suffixes().next() else {
+            return;
+        };
+        let Some(argument) = args.tokens().find(|token| matches!(token.token_type(), TokenType::Number { .. })) else {
+            return;
+        };
+        if let (Some(start), Some(end)) = (argument.start_position(), argument.end_position()) {
+            self.requires.push((start.bytes()..end.bytes(), id));
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Transpiling whole plugins, run with `cargo test --features testing`
+#![cfg(feature = "testing")]
+
+use pluginproxy_transpiler::{
+    error::Problem,
+    report::Requirements,
+    rules::{self, Chain, ChainRewrite, Rule},
+    testing::{script_source, PluginFixture},
+    DomTranspiler, TranspileOptions, LOADER_NAME,
+};
+use rbx_dom_weak::InstanceBuilder;
+
+/// Source of the script at the path, panicking if there's none
+fn source(transpiler: &DomTranspiler, path: &str) -> String {
+    script_source(transpiler.tree(), transpiler.source_script(), path).unwrap_or_else(|| panic!("no script at {path}"))
+}
+
+/// Rewrites `Probe` to the depth of the script the rule sees in its options
+#[derive(Debug)]
+struct DepthProbe;
+
+impl Rule for DepthProbe {
+    fn name(&self) -> &'static str {
+        "depth-probe"
+    }
+
+    fn rewrite(&self, chain: &Chain, options: &TranspileOptions) -> Option<ChainRewrite> {
+        (chain.name() == Some("Probe")).then(|| ChainRewrite {
+            prefix: format!("Depth{}", options.path_depth),
+            keep_from: 0,
+            requirements: Requirements::default(),
+        })
+    }
+}
+
+#[test]
+fn rules_see_the_options_of_every_script() {
+    let mut transpiler = PluginFixture::new("local value = Probe.value")
+        .module("Config", "return { theme = settings().Studio.Theme, value = Probe.value }")
+        .module("Lib/Util", "return { theme = settings().Studio.Theme, value = Probe.value }")
+        .transpiler();
+    transpiler.add_rule(DepthProbe).transpile_tree().unwrap();
+
+    let config = source(&transpiler, "script.Config");
+    assert!(config.starts_with("local _proxyGlobals = require(script.Parent).Globals"));
+    assert!(config.contains("value = Depth1.value"));
+
+    let util = source(&transpiler, "script.Lib.Util");
+    assert!(util.starts_with("local _proxyGlobals = require(script.Parent.Parent).Globals"));
+    assert!(util.contains("value = Depth2.value"));
+
+    assert!(source(&transpiler, "script").contains("local value = Depth0.value"));
+}
+
+#[test]
+fn unloading_is_rewritten_to_the_cleanup_of_the_main_script() {
+    let fixture = || PluginFixture::new("plugin.Unloading:Connect(stop)").module("Lib/Util", "plugin.Unloading:Connect(save)");
+
+    let mut transpiler = fixture().transpiler();
+    transpiler.unload_cleanup(true).transpile_tree().unwrap();
+    assert!(source(&transpiler, "script.Lib.Util").contains("require(script.Parent.Parent).cleanup:Connect(save)"));
+    assert!(source(&transpiler, "script").contains("require(script).cleanup:Connect(stop)"));
+
+    let mut transpiler = fixture().transpiler();
+    transpiler.unload_cleanup(true).globals_module(true).transpile_tree().unwrap();
+    assert!(source(&transpiler, "script.Lib.Util").contains("require(script:FindFirstAncestor(\"Main\")).cleanup:Connect(save)"));
+}
+
+#[test]
+fn globals_module_refuses_a_descendant_named_like_the_main_script() {
+    let fixture = || PluginFixture::new("print(settings().Studio.Theme)").module("Main/Util", "return settings().Studio.Theme");
+
+    let error = fixture().transpiler().globals_module(true).transpile_tree().map(|_| ()).unwrap_err();
+    assert!(matches!(error, Problem::MainNameShadowed(name, _) if name == "Main"));
+
+    // without the globals module, modules reach the main script by their depth, so the name doesn't matter
+    let mut transpiler = fixture().transpiler();
+    transpiler.transpile_tree().unwrap();
+    assert!(source(&transpiler, "script.Main.Util").starts_with("local _proxyGlobals = require(script.Parent.Parent).Globals"));
+}
+
+#[test]
+fn path_rules_only_apply_below_their_pattern() {
+    let source_code = "local selection = game:GetService(\"Selection\")\nreturn selection";
+    let mut transpiler = PluginFixture::new("local selection = game:GetService(\"Selection\")")
+        .module("Util", source_code)
+        .module("Vendor/Lib", source_code)
+        .transpiler();
+    transpiler.path_rule("**/Vendor/**", rules::GET_SERVICE, false).transpile_tree().unwrap();
+
+    let lib = source(&transpiler, "script.Vendor.Lib");
+    assert!(lib.contains("local selection = game:GetService(\"Selection\")"));
+    assert!(!lib.contains("_proxyGlobals.game"));
+    assert!(source(&transpiler, "script.Util").contains("local selection = _proxyGlobals.game:GetService(\"Selection\")"));
+    assert!(source(&transpiler, "script").contains("local selection = _proxyGlobals.game:GetService(\"Selection\")"));
+}
+
+#[test]
+fn unused_modules_are_pruned() {
+    let mut transpiler = PluginFixture::new("local util = require(script.Util)")
+        .module("Util", "return settings().Studio.Theme")
+        .module("Unused", "return settings().Studio.Theme")
+        .transpiler();
+    transpiler.prune_unused(true).transpile_tree().unwrap();
+
+    assert!(source(&transpiler, "script.Util").contains("return _proxyGlobals.settings().Studio.Theme"));
+    assert_eq!(script_source(transpiler.tree(), transpiler.source_script(), "script.Unused"), None);
+    assert_eq!(transpiler.metrics().pruned, 1);
+}
+
+#[test]
+fn local_scripts_become_modules_run_where_they_were_enabled() {
+    let mut transpiler = PluginFixture::new("script.Panel.Runner.Enabled = true")
+        .script("LocalScript", "Panel/Runner", "print(settings().Studio.Theme)")
+        .transpiler();
+    transpiler.local_scripts_as_modules(true).transpile_tree().unwrap();
+
+    let runner = source(&transpiler, "script.Panel.Runner");
+    assert!(runner.contains("return { run = function()"));
+    assert!(runner.contains("print(_proxyGlobals.settings().Studio.Theme)"));
+    assert!(source(&transpiler, "script").contains("require(script.Panel.Runner).run()"));
+
+    let tree = transpiler.tree();
+    let panel = tree.get_by_ref(tree.get_by_ref(transpiler.source_script()).unwrap().children()[0]).unwrap();
+    assert_eq!(tree.get_by_ref(panel.children()[0]).unwrap().class, "ModuleScript");
+}
+
+#[test]
+fn loader_runs_every_top_level_script() {
+    let fixture = PluginFixture::new("local theme = settings().Studio.Theme");
+    let mut tree = fixture.build();
+    let toolbar = InstanceBuilder::new("Script")
+        .with_name("Toolbar")
+        .with_property("Source", String::from("local toolbar = plugin:CreateToolbar(\"Tools\")"));
+    tree.insert(tree.root_ref(), toolbar);
+
+    let mut transpiler = DomTranspiler::with_loader(tree).unwrap();
+    transpiler.transpile_tree().unwrap();
+
+    let loader = transpiler.tree().get_by_ref(transpiler.source_script()).unwrap();
+    assert_eq!(loader.name, LOADER_NAME);
+    let loader_source = source(&transpiler, "script");
+    let main_run = loader_source.find("require(script.Main).init(_proxyGlobals)").unwrap();
+    let toolbar_run = loader_source.find("require(script.Toolbar).init(_proxyGlobals)").unwrap();
+    assert!(main_run < toolbar_run);
+
+    let main = source(&transpiler, "script.Main");
+    assert!(main.contains("init = function(_proxyGlobals)"));
+    assert!(main.contains("local theme = _proxyGlobals.settings().Studio.Theme"));
+    assert!(source(&transpiler, "script.Toolbar").contains("init = function(_proxyGlobals)"));
+}
+
+#[test]
+fn stripped_paths_are_removed_before_transpiling() {
+    let mut transpiler = PluginFixture::new("local util = require(script.Lib.Util)")
+        .module("Lib/Util", "return settings().Studio.Theme")
+        .module("Stories/Button", "return settings().Studio.Theme")
+        .transpiler();
+    transpiler.strip_paths(["**/Stories/**"]).transpile_tree().unwrap();
+
+    assert_eq!(script_source(transpiler.tree(), transpiler.source_script(), "script.Stories.Button"), None);
+    assert!(source(&transpiler, "script.Lib.Util").contains("return _proxyGlobals.settings().Studio.Theme"));
+    assert_eq!(transpiler.metrics().stripped, 1);
+}